@@ -7,14 +7,27 @@ use std::{
     io::{self, prelude::*},
     path::{Path, PathBuf},
 };
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 fn main() {
     // Instantiate a mnemonic generator with the location of the wordlist
-    let mut generator = Bip39Generator::new("english.txt");
+    let generator = Bip39Generator::new("english.txt");
     // Generate the mnemonic
     let mnemonic = generator.mnemonic::<16>().unwrap();
 
-    println!("Your mnemonic is: {}", &mnemonic);
+    // Print our index and each word. This
+    // will show the user the words in each
+    // line but with a number. eg
+    //  9. foo
+    // 10. bar
+    for (index, word) in mnemonic.words().enumerate() {
+        // Since indexes start at zero we add `1`
+        // to make them human readable (humans mostly count from 1)
+        println!("{:>2}. {}", index + 1, word);
+    }
+
+    println!("Your mnemonic is: {}", mnemonic.phrase());
 
     // Recover a wallet by running the mnemonic through seed generator without a passphrase
     let insecure_seed = Bip39Generator::insecure_seed(&mnemonic);
@@ -32,17 +45,19 @@ fn main() {
 pub const ITERATION_COUNT: u32 = 2048;
 /// The word used as a prefix for the salt for our key derivation function
 pub const SALT_PREFIX: &str = "mnemonic";
-
+/// The size in bytes of the seed produced by PBKDF2
+pub const SEED_LENGTH: usize = 64; // 512 bits == 64 bytes
+/// The longest word in the english wordlist (8 characters) plus a space
+/// separator. Used to reserve the mnemonic buffer up front so that growing
+/// it never leaves a reallocated copy of the secret behind on the heap.
+const MAX_WORD_LENGTH: usize = 9;
+
+/// The generator only holds the path to the wordlist. All the secret
+/// material (entropy, checksum and word indexes) lives in local
+/// zeroizing buffers inside [Bip39Generator::mnemonic] so
+/// none of it outlives the call that produced it.
 #[derive(Debug, Default)]
 pub struct Bip39Generator {
-    // This holds all our indexes that we will use to fetch
-    // our word from the word list
-    // with each index corresponding to an index
-    // from our wordlist contained in a Vec<word>
-    mnemonic_index: Vec<u16>,
-    // This field holds the random bytes with our checksum
-    // bytes appended to the end
-    appended: Vec<u8>,
     // This contains a path to our wordlist file
     path: PathBuf,
 }
@@ -60,108 +75,84 @@ impl Bip39Generator {
             // using `.as_ref()` method and convert it
             // to a `std::path::PathBuf` using the `.to_path_buf()`
             path: path_to_wordlist.as_ref().to_path_buf(),
-            // All other fields can hold default values
-            // and we can call this method since
-            // we derived `Default` values using `#[derive(Default)]`
-            // on our struct
-            ..Default::default()
         }
     }
 
     /// Generates a seed without a passphrase
-    pub fn insecure_seed(mnemonic: &str) -> io::Result<Vec<u8>> {
+    pub fn insecure_seed(mnemonic: &Mnemonic) -> io::Result<Seed> {
         Self::seed(mnemonic, None)
     }
 
     /// Generates a seed with a passphrase
-    pub fn secure_seed(mnemonic: &str, passphrase: &str) -> io::Result<Vec<u8>> {
+    pub fn secure_seed(mnemonic: &Mnemonic, passphrase: &str) -> io::Result<Seed> {
         Self::seed(mnemonic, Some(passphrase))
     }
 
     // We pass our mnemonic and an optional passphrase
-    fn seed(mnemonic: &str, passphrase: Option<&str>) -> io::Result<Vec<u8>> {
+    fn seed(mnemonic: &Mnemonic, passphrase: Option<&str>) -> io::Result<Seed> {
         // We check if there is a passphrase provided.
-        // if there is one we prefix our salt with the passphrase
-        let salt = if let Some(passphrase_required) = passphrase {
+        // if there is one we prefix our salt with the passphrase.
+        // The salt contains the passphrase so it is wiped once dropped
+        let salt = Zeroizing::new(if let Some(passphrase_required) = passphrase {
             String::new() + SALT_PREFIX + passphrase_required
         } else {
             String::from(SALT_PREFIX)
-        };
+        });
 
         // We want to generate a 512bit seed
         // so we create a buffer to hold this.
-        let mut wallet_seed = [0u8; 64]; // 512 bits == 64 bytes
+        let mut wallet_seed = Seed([0u8; SEED_LENGTH]);
 
         // We generate a key and push all the bytes to the `wallet_seed` buffer
         pbkdf2_hmac::<Sha512>(
-            mnemonic.as_bytes(),
+            mnemonic.phrase().as_bytes(),
             salt.as_bytes(),
             ITERATION_COUNT,
-            &mut wallet_seed,
+            &mut wallet_seed.0,
         );
 
         // We return our seed
-        Ok(wallet_seed.to_vec())
+        Ok(wallet_seed)
     }
 
-    pub fn mnemonic<const N: usize>(&mut self) -> io::Result<String> {
+    pub fn mnemonic<const N: usize>(&self) -> io::Result<Mnemonic> {
         // This generates the number of random bits we need
         let entropy = Entropy::<{ N }>::generate();
 
         // Next, let's generate our checksum
-        self.generate_checksum::<N>(entropy.0);
+        let appended = Self::generate_checksum(&entropy);
 
         // Next we compute the decimal numbers we will use
         // to get our wordlist
-        self.compute();
+        let mnemonic_index = Self::compute(&appended);
 
         // Load the wordlist into memory
         let wordlist = self.load_wordlist()?;
 
+        // Reserve enough space for the longest possible mnemonic
+        // so that pushing words never reallocates the buffer
+        let mut phrase = String::with_capacity(mnemonic_index.len() * MAX_WORD_LENGTH);
+
         // Iterate through the decimal numbers
         // and for each decimal number get the word
         // in it's index in the wordlist (wordlist[index from decimal number]
-        let mnemonic = self
-            .mnemonic_index
-            .iter()
-            // Enumerate to get the current count in our interation
-            .enumerate()
-            .map(|(index, line_number)| {
-                // Convert our decimal index (line_numer) to
-                // a usize since Rust is very strict in that
-                // you can only index an array using a usize
-                // so we dereference and cast using `as usize`
-                let word = (&wordlist[*line_number as usize]).clone() + " "; // Add a space in each word
-                                                                             // Since indexes start at zero we add `1`
-                                                                             // to make them human readable (humans mostly count from 1)
-                let index = index + 1;
-
-                // Check if we have our index is less than
-                // 10 so we add a padding to make printing
-                // to console neat
-                let indexed = if index < 10 {
-                    String::new() + " " + index.to_string().as_str()
-                } else {
-                    index.to_string()
-                };
-
-                // Print our index and each word. This
-                // will show the user the words in each
-                // line but with a number. eg
-                //  9. foo
-                // 10. bar
-                println!("{}. {}", indexed, &word);
-
-                // Return the word
-                word
-            })
-            .collect::<String>(); // Combine all strings into one
-
-        // Trim the last space in the and return the mnemonic
-        Ok(mnemonic.trim().to_owned())
+        for (position, line_number) in mnemonic_index.iter().enumerate() {
+            // Add a space between each word
+            if position > 0 {
+                phrase.push(' ');
+            }
+
+            // Convert our decimal index (line_numer) to
+            // a usize since Rust is very strict in that
+            // you can only index an array using a usize
+            // so we dereference and cast using `as usize`
+            phrase.push_str(&wordlist[*line_number as usize]);
+        }
+
+        Ok(Mnemonic(phrase))
     }
 
-    fn load_wordlist(&mut self) -> io::Result<Vec<String>> {
+    fn load_wordlist(&self) -> io::Result<Vec<String>> {
         // open the file using the path we passed
         // when instantiating our struct
         // using `Bip39Generator::new()`
@@ -185,24 +176,26 @@ impl Bip39Generator {
     }
 
     // Here we pass our generated random bytes as `entropy` argument
-    fn generate_checksum<const N: usize>(&mut self, entropy: [u8; N]) -> &mut Self {
+    // and get back the random bytes with our checksum
+    // bytes appended to the end
+    fn generate_checksum<const N: usize>(entropy: &Entropy<N>) -> Zeroizing<Vec<u8>> {
         // BIP39 spec requires a seed to be generated
         // using a SHA256 Psuedo Random Function (PRF)
         // so we instantiate a SHA256 hashing function.
         let mut hasher = Sha256::new();
 
         // We now pass our random bytes into our SHA256 PRF
-        hasher.update(entropy.as_slice());
+        hasher.update(entropy.as_bytes());
 
         // We now get our finalized value. Using
         // SHA256 always ensures that despite being
         // able to use variable length of random bytes
         // we always get back a 256 bit (32 byte) value.
-        let entropy_hash = hasher.finalize();
+        let mut entropy_hash = hasher.finalize();
 
         // Since we get a 32 byte value we multiply by
         // `8` to get number of bits since 1 byte == 8 bits
-        let bits_of_entropy = entropy.len() * 8;
+        let bits_of_entropy = N * 8;
         // We get our `n` bits for our checksum from the
         // length of the random bits (entropy)
         // where `n` is calculated as the
@@ -212,27 +205,34 @@ impl Bip39Generator {
         // bits of checksum from our
         // 256 bit hash in variable `entropy_hash`
         let significant = entropy_hash[0] >> bits_of_checksum;
-
-        let mut appended = entropy.to_vec();
+        // The hash is derived from the entropy so we wipe it
+        entropy_hash.zeroize();
+
+        // Allocate the exact size up front so that the
+        // push below does not reallocate and leave a copy
+        // of the entropy behind
+        let mut appended = Zeroizing::new(Vec::with_capacity(N + 1));
+        appended.extend_from_slice(entropy.as_bytes());
         // We then append our checksum to our random
         appended.push(significant);
 
-        // We now assign our appended bytes to the `appended`
-        // field of our `Bip39Generator` struct which is `Self`
-        self.appended = appended;
-
-        self
+        appended
     }
 
-    // We pass a mutable to self since we want to
-    // add the result of this computation to `Self`
-    fn compute(&mut self) -> &mut Self {
+    // We pass the random bytes with the checksum appended
+    // and get back the indexes that we will use to fetch
+    // our word from the word list
+    fn compute(appended: &[u8]) -> Zeroizing<Vec<u16>> {
         // This vector will hold the binary
         // representation of each byte in the `appended` vector.
-        let mut bits = vec![];
+        let mut bits = Zeroizing::new(Vec::with_capacity(appended.len() * 8));
+
+        // This vector holds all our indexes with each index
+        // corresponding to an index from our wordlist
+        let mut mnemonic_index = Zeroizing::new(Vec::with_capacity(appended.len() * 8 / 11));
 
-        // This line starts a loop that iterates over each byte in the `self.appended` vector.
-        for &byte in self.appended.iter() {
+        // This line starts a loop that iterates over each byte in the `appended` slice.
+        for &byte in appended.iter() {
             // This line starts a nested loop that
             // counts backwards from 7 to 0.
             // The variable `i` represents the position of
@@ -283,18 +283,19 @@ impl Bip39Generator {
                 }
                 // This line pushes the decimal
                 // representation of the current 11-bit chunk
-                // into the `self.mnemonic_index` vector.
-                self.mnemonic_index.push(value);
+                // into the `mnemonic_index` vector.
+                mnemonic_index.push(value);
             }
         }
 
-        self
+        mnemonic_index
     }
 }
 
 // This struct takes a constant `N` as a generic
-// enabling one to specify a variable length for the bytes generated
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+// enabling one to specify a variable length for the bytes generated.
+// It is wiped when dropped and deliberately does not implement
+// `Clone` or `Debug` so the random bytes cannot be copied or logged.
 pub struct Entropy<const N: usize>([u8; N]);
 
 impl<const N: usize> Entropy<N> {
@@ -303,11 +304,157 @@ impl<const N: usize> Entropy<N> {
         // Instantiate our cryptographically secure random byte generation algorithm
         let mut rng = ChaCha20Rng::from_entropy();
         // Create a zero filled buffer to hold our bytes
-        let mut buffer = [0u8; N];
+        let mut buffer = Self([0u8; N]);
         // Fill our buffer with random bytes
-        rng.fill_bytes(&mut buffer);
+        rng.fill_bytes(&mut buffer.0);
 
         // Return our buffer
-        Self(buffer)
+        buffer
+    }
+
+    /// Get the random bytes
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+}
+
+impl<const N: usize> ConstantTimeEq for Entropy<N> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl<const N: usize> PartialEq for Entropy<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<const N: usize> Eq for Entropy<N> {}
+
+impl<const N: usize> Drop for Entropy<N> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<const N: usize> ZeroizeOnDrop for Entropy<N> {}
+
+/// The mnemonic sentence with the words separated by a single space.
+/// It is wiped when dropped and does not implement `Clone` or `Debug`.
+pub struct Mnemonic(String);
+
+impl Mnemonic {
+    /// Wrap an existing mnemonic sentence, for example one entered
+    /// by a user to recover a wallet. Taking ownership of the `String`
+    /// ensures the only copy of the sentence is the one that gets wiped.
+    pub fn from_phrase(phrase: String) -> Self {
+        Self(phrase)
+    }
+
+    /// Get the mnemonic sentence
+    pub fn phrase(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Iterate over the words of the mnemonic
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.0.split(' ')
+    }
+}
+
+impl ConstantTimeEq for Mnemonic {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // Slices of different lengths compare unequal without
+        // inspecting the contents, which only reveals the length
+        self.0.as_bytes().ct_eq(other.0.as_bytes())
+    }
+}
+
+impl PartialEq for Mnemonic {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for Mnemonic {}
+
+impl Drop for Mnemonic {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Mnemonic {}
+
+/// The 512 bit seed derived from a mnemonic and an optional passphrase.
+/// It is wiped when dropped and does not implement `Clone` or `Debug`.
+pub struct Seed([u8; SEED_LENGTH]);
+
+impl Seed {
+    /// Get the seed bytes
+    pub fn as_bytes(&self) -> &[u8; SEED_LENGTH] {
+        &self.0
+    }
+}
+
+impl ConstantTimeEq for Seed {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for Seed {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for Seed {}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Seed {}
+
+#[cfg(test)]
+mod secret_types {
+    use crate::{Bip39Generator, Mnemonic};
+
+    #[test]
+    fn seed_matches_bip39_vector() {
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+                .to_owned(),
+        );
+
+        let seed = Bip39Generator::secure_seed(&mnemonic, "TREZOR").unwrap();
+
+        assert_eq!(
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            seed.as_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        );
+
+        let insecure_seed = Bip39Generator::insecure_seed(&mnemonic).unwrap();
+        assert!(Bip39Generator::secure_seed(&mnemonic, "").unwrap() == insecure_seed);
+        assert!(seed != insecure_seed);
+    }
+
+    #[test]
+    fn generator_is_stateless() {
+        let generator = Bip39Generator::new("english.txt");
+
+        let first = generator.mnemonic::<16>().unwrap();
+        let second = generator.mnemonic::<24>().unwrap();
+
+        assert_eq!(first.words().count(), 12);
+        assert_eq!(second.words().count(), 18);
+        assert!(first != Mnemonic::from_phrase(second.phrase().to_owned()));
     }
 }
@@ -0,0 +1,78 @@
+//! Mines a synthetic 300,000 transaction mempool by rebuilding the
+//! dependency graph and by maintaining an `IndexedMempool`.
+//! Run with `cargo bench --bench indexed_mempool`.
+
+use block_constructor::{
+    synthetic_mempool, BlockLimits, IndexedMempool, MempoolGraph, Projection, Transaction,
+};
+use std::time::{Duration, Instant};
+
+const MEMPOOL_SIZE: usize = 300_000;
+const SEED: u64 = 42;
+
+fn timed<T>(label: &str, run: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let outcome = run();
+    report(label, start.elapsed());
+
+    outcome
+}
+
+fn report(label: &str, elapsed: Duration) {
+    println!("{label:<44} {:>10.1} ms", elapsed.as_secs_f64() * 1000.0);
+}
+
+fn main() {
+    let limits = BlockLimits::default();
+    let mempool = timed("generate 300k transactions", || {
+        synthetic_mempool(MEMPOOL_SIZE, SEED)
+    });
+
+    let projection = timed("graph + ancestor package projection", || {
+        Projection::ancestor_packages(&MempoolGraph::new(&mempool), limits)
+    });
+
+    let mut indexed = IndexedMempool::new();
+    timed("index: add 300k transactions", || {
+        for tx in mempool.iter() {
+            indexed.add(tx.clone());
+        }
+    });
+
+    // Bump and restore the fee of every tenth transaction
+    timed("index: update 60k fees", || {
+        for tx in mempool.iter().step_by(10) {
+            indexed.update_fee(tx.txid(), tx.fee() * 2);
+            indexed.update_fee(tx.txid(), tx.fee());
+        }
+    });
+
+    // Evict and re-add every hundredth transaction with its descendants
+    timed("index: remove and re-add 3k subtrees", || {
+        for tx in mempool.iter().step_by(100) {
+            for removed in indexed.remove(tx.txid()) {
+                indexed.add(removed);
+            }
+        }
+    });
+
+    let blocks = timed("index: mine every block", || {
+        let mut blocks = Vec::<Vec<Transaction>>::new();
+        while let Some(block) =
+            indexed.next_block(limits.max_tx_weight(), limits.max_tx_sigop_cost())
+        {
+            blocks.push(block);
+        }
+
+        blocks
+    });
+
+    println!();
+    println!(
+        "projection: {} blocks, {} sats; index: {} blocks, {} sats",
+        projection.blocks().len(),
+        projection.total_fee(),
+        blocks.len(),
+        blocks.iter().flatten().map(|tx| tx.fee()).sum::<u64>()
+    );
+}
@@ -0,0 +1,313 @@
+use crate::MempoolGraph;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, BinaryHeap},
+};
+
+/// Selects transactions into blocks by ancestor fee rate the way
+/// Bitcoin Core's block assembler does. A transaction is scored by the
+/// fee and weight of itself plus all its ancestors that are not yet in a block
+/// so a high fee child can pull its low fee parents into the same block (CPFP).
+/// Once a package is included the scores of the descendants of every
+/// included transaction are updated to exclude it.
+#[derive(Debug, Clone)]
+pub struct AncestorPackageSelector<'a> {
+    graph: &'a MempoolGraph<'a>,
+    // Ancestors of each transaction that have not been included in a block yet
+    ancestors: Vec<BTreeSet<usize>>,
+    // Number of in-mempool ancestors before any were included,
+    // used to keep each package in topological order
+    ancestor_count: Vec<usize>,
+    ancestor_fee: Vec<u64>,
+    ancestor_weight: Vec<u64>,
+    ancestor_sigop_cost: Vec<u64>,
+    included: Vec<bool>,
+    unmineable: Vec<bool>,
+}
+
+impl<'a> AncestorPackageSelector<'a> {
+    pub fn new(graph: &'a MempoolGraph<'a>) -> Self {
+        let unmineable = graph.unmineable();
+        let mut ancestors = Vec::<BTreeSet<usize>>::with_capacity(graph.len());
+        let mut ancestor_fee = Vec::<u64>::with_capacity(graph.len());
+        let mut ancestor_weight = Vec::<u64>::with_capacity(graph.len());
+        let mut ancestor_sigop_cost = Vec::<u64>::with_capacity(graph.len());
+
+        for (position, &is_unmineable) in unmineable.iter().enumerate() {
+            let tx_ancestors = if is_unmineable {
+                BTreeSet::new()
+            } else {
+                graph.ancestors(position)
+            };
+
+            let tx = graph.transaction(position);
+            let mut fee = tx.fee();
+            let mut weight = tx.weight() as u64;
+            let mut sigop_cost = tx.sigop_cost();
+            for &ancestor in tx_ancestors.iter() {
+                fee += graph.transaction(ancestor).fee();
+                weight += graph.transaction(ancestor).weight() as u64;
+                sigop_cost += graph.transaction(ancestor).sigop_cost();
+            }
+
+            ancestors.push(tx_ancestors);
+            ancestor_fee.push(fee);
+            ancestor_weight.push(weight);
+            ancestor_sigop_cost.push(sigop_cost);
+        }
+
+        Self {
+            graph,
+            ancestor_count: ancestors.iter().map(|set| set.len()).collect(),
+            ancestors,
+            ancestor_fee,
+            ancestor_weight,
+            ancestor_sigop_cost,
+            included: vec![false; graph.len()],
+            unmineable,
+        }
+    }
+
+    /// Transactions that can never be selected because an ancestor is
+    /// missing from the mempool
+    pub fn unmineable(&self) -> impl Iterator<Item = usize> + '_ {
+        self.unmineable
+            .iter()
+            .enumerate()
+            .filter(|(_, unmineable)| **unmineable)
+            .map(|(position, _)| position)
+    }
+
+    /// Transactions that have not been selected into a block
+    /// and are not unmineable
+    pub fn remaining(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.graph.len())
+            .filter(|&position| !self.included[position] && !self.unmineable[position])
+    }
+
+    /// Fill the next block up to `max_weight` and `max_sigop_cost`
+    /// returning the positions of the selected transactions in topological
+    /// order. Returns `None` once no remaining transaction fits into an empty block.
+    pub fn next_block(&mut self, max_weight: u64, max_sigop_cost: u64) -> Option<Vec<usize>> {
+        let mut candidates = self
+            .remaining()
+            .map(|position| self.score(position))
+            .collect::<BinaryHeap<PackageScore>>();
+
+        let mut block = Vec::<usize>::new();
+        let mut block_weight = 0u64;
+        let mut block_sigop_cost = 0u64;
+
+        while let Some(candidate) = candidates.pop() {
+            let position = candidate.position;
+
+            // Skip transactions already included as part of another package
+            // and entries whose score changed after they were queued
+            if self.included[position]
+                || candidate.fee != self.ancestor_fee[position]
+                || candidate.weight != self.ancestor_weight[position]
+            {
+                continue;
+            }
+
+            if block_weight + candidate.weight > max_weight
+                || block_sigop_cost + self.ancestor_sigop_cost[position] > max_sigop_cost
+            {
+                continue;
+            }
+
+            let mut package = self.ancestors[position]
+                .iter()
+                .copied()
+                .chain(Some(position))
+                .collect::<Vec<usize>>();
+            // An ancestor always has fewer ancestors than its descendants
+            package.sort_by_key(|&member| (self.ancestor_count[member], member));
+
+            for &member in package.iter() {
+                block_weight += self.graph.transaction(member).weight() as u64;
+                block_sigop_cost += self.graph.transaction(member).sigop_cost();
+                block.push(member);
+            }
+
+            for descendant in self.include(&package) {
+                candidates.push(self.score(descendant));
+            }
+        }
+
+        if block.is_empty() {
+            None
+        } else {
+            Some(block)
+        }
+    }
+
+    /// Mark transactions as included in a block, which must list every
+    /// ancestor before its descendants, and remove them from the ancestor
+    /// sets of their descendants. Returns the descendants whose score changed.
+    pub fn include(&mut self, positions: &[usize]) -> Vec<usize> {
+        let mut updated = Vec::<usize>::new();
+
+        for &position in positions {
+            self.included[position] = true;
+        }
+
+        for &member in positions {
+            let fee = self.graph.transaction(member).fee();
+            let weight = self.graph.transaction(member).weight() as u64;
+            let sigop_cost = self.graph.transaction(member).sigop_cost();
+
+            for descendant in self.graph.descendants(member) {
+                if self.included[descendant] || self.unmineable[descendant] {
+                    continue;
+                }
+
+                if self.ancestors[descendant].remove(&member) {
+                    self.ancestor_fee[descendant] -= fee;
+                    self.ancestor_weight[descendant] -= weight;
+                    self.ancestor_sigop_cost[descendant] -= sigop_cost;
+                    updated.push(descendant);
+                }
+            }
+        }
+
+        updated
+    }
+
+    /// Ancestors of a transaction that have not been included yet
+    pub fn ancestors(&self, position: usize) -> &BTreeSet<usize> {
+        &self.ancestors[position]
+    }
+
+    /// Number of in-mempool ancestors the transaction had before any were included.
+    /// Sorting by it puts every ancestor before its descendants.
+    pub fn ancestor_count(&self, position: usize) -> usize {
+        self.ancestor_count[position]
+    }
+
+    fn score(&self, position: usize) -> PackageScore<'a> {
+        PackageScore {
+            position,
+            fee: self.ancestor_fee[position],
+            weight: self.ancestor_weight[position],
+            txid: self.graph.transaction(position).txid(),
+        }
+    }
+}
+
+/// A candidate in the selection queue ordered by ancestor fee rate
+#[derive(Debug, PartialEq, Eq)]
+struct PackageScore<'a> {
+    position: usize,
+    fee: u64,
+    weight: u64,
+    txid: &'a str,
+}
+
+impl PartialOrd for PackageScore<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PackageScore<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let self_fee_rate = self.fee as u128 * other.weight as u128;
+        let other_fee_rate = other.fee as u128 * self.weight as u128;
+
+        self_fee_rate
+            .cmp(&other_fee_rate)
+            .then_with(|| other.txid.cmp(self.txid))
+            .then_with(|| self.fee.cmp(&other.fee))
+            .then_with(|| other.position.cmp(&self.position))
+    }
+}
+
+#[cfg(test)]
+mod ancestor_package_selection {
+    use crate::{
+        AncestorPackageSelector, MempoolGraph, Miner, Transaction, MAX_BLOCK_SIGOPS_COST,
+        MAX_BLOCK_WEIGHT,
+    };
+    use std::collections::HashSet;
+
+    #[test]
+    fn child_pays_for_parent_in_the_same_block() {
+        let mempool = vec![
+            Transaction::new("parent", 100, 1000, &[]),
+            Transaction::new("child", 9000, 1000, &["parent"]),
+            Transaction::new("other", 3000, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let mut selector = AncestorPackageSelector::new(&graph);
+
+        // Room for two transactions: the parent and child package pays
+        // 4550 per 1000 weight units which beats `other`
+        let block = selector.next_block(2000, MAX_BLOCK_SIGOPS_COST).unwrap();
+        let txids = block
+            .iter()
+            .map(|&position| mempool[position].txid())
+            .collect::<Vec<&str>>();
+        assert_eq!(txids, vec!["parent", "child"]);
+
+        let block = selector.next_block(2000, MAX_BLOCK_SIGOPS_COST).unwrap();
+        assert_eq!(block, vec![2]);
+        assert_eq!(selector.next_block(2000, MAX_BLOCK_SIGOPS_COST), None);
+    }
+
+    #[test]
+    fn descendant_scores_are_updated() {
+        let mempool = vec![
+            Transaction::new("grandparent", 4000, 1000, &[]),
+            Transaction::new("parent", 100, 1000, &["grandparent"]),
+            Transaction::new("child", 3000, 1000, &["parent"]),
+            Transaction::new("other", 1500, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let mut selector = AncestorPackageSelector::new(&graph);
+
+        // Once the grandparent is included the parent and child package
+        // pays 1550 per 1000 weight units which beats `other`
+        let block = selector.next_block(3000, MAX_BLOCK_SIGOPS_COST).unwrap();
+        assert_eq!(block, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn missing_parents_are_never_selected() {
+        let mempool = vec![
+            Transaction::new("orphan", 100_000, 1000, &["not-in-mempool"]),
+            Transaction::new("orphan-child", 100_000, 1000, &["orphan"]),
+            Transaction::new("other", 100, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let mut selector = AncestorPackageSelector::new(&graph);
+
+        assert_eq!(
+            selector.next_block(MAX_BLOCK_WEIGHT, MAX_BLOCK_SIGOPS_COST),
+            Some(vec![2])
+        );
+        assert_eq!(selector.unmineable().collect::<Vec<usize>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn bundled_mempool_blocks_are_topological() {
+        let mut miner = Miner::load_mempool("mempool.csv").unwrap();
+        let mempool_size = miner.mempool().len();
+        miner.mine();
+
+        let mut seen = HashSet::<String>::new();
+        for block in miner.finalized() {
+            let weight = block.iter().map(|tx| tx.weight() as u64).sum::<u64>();
+            assert!(weight <= miner.limits().max_tx_weight());
+
+            for tx in block {
+                for parent in tx.parent_txids() {
+                    assert!(seen.contains(parent));
+                }
+                assert!(seen.insert(tx.txid().to_owned()));
+            }
+        }
+
+        assert_eq!(seen.len(), mempool_size);
+    }
+}
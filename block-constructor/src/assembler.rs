@@ -0,0 +1,621 @@
+use crate::{BlockLimits, Transaction};
+use bitcoin::{
+    absolute, block,
+    blockdata::{opcodes::all::OP_RETURN, script::Builder},
+    consensus::encode::{deserialize, serialize},
+    hash_types::WitnessMerkleNode,
+    hashes::Hash,
+    merkle_tree, transaction, Amount, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf,
+    Sequence, TxIn, TxMerkleNode, TxOut, Txid, Witness, WitnessCommitment, Wtxid,
+};
+use core::fmt;
+
+/// Block subsidy paid before the first halving, in sats
+pub const INITIAL_SUBSIDY: u64 = 50 * 100_000_000;
+/// Number of blocks between two subsidy halvings
+pub const HALVING_INTERVAL: u64 = 210_000;
+/// Compact target of regtest, met by about every other header
+pub const REGTEST_BITS: u32 = 0x207f_ffff;
+/// Header hashes tried before mining a block gives up, enough for a
+/// regtest target but far from any real difficulty
+pub const DEFAULT_MAX_ATTEMPTS: u64 = 1 << 20;
+/// First four bytes of the BIP141 witness commitment output script after
+/// `OP_RETURN OP_PUSHBYTES_36`
+pub const WITNESS_COMMITMENT_HEADER: [u8; 4] = [0xaa, 0x21, 0xa9, 0xed];
+
+/// Subsidy of the block at `height`
+pub fn block_subsidy(height: u32) -> u64 {
+    let halvings = height as u64 / HALVING_INTERVAL;
+
+    if halvings >= 64 {
+        0
+    } else {
+        INITIAL_SUBSIDY >> halvings
+    }
+}
+
+/// BIP141 commitment to the wtxids of the transactions after the coinbase
+/// and the all zero witness reserved value
+pub fn witness_commitment(wtxids: &[Wtxid]) -> WitnessCommitment {
+    // The coinbase wtxid is replaced by zeros in the witness merkle tree
+    let hashes = Some(Wtxid::all_zeros())
+        .into_iter()
+        .chain(wtxids.iter().copied())
+        .map(|wtxid| wtxid.to_raw_hash());
+    let witness_root = WitnessMerkleNode::from_raw_hash(
+        merkle_tree::calculate_root(hashes).expect("the coinbase is always there"),
+    );
+
+    Block::compute_witness_commitment(&witness_root, &[0u8; 32])
+}
+
+/// The coinbase output script carrying a witness commitment
+pub fn witness_commitment_script(commitment: &WitnessCommitment) -> ScriptBuf {
+    let mut pushed = [0u8; 36];
+    pushed[..4].copy_from_slice(&WITNESS_COMMITMENT_HEADER);
+    pushed[4..].copy_from_slice(commitment.as_byte_array());
+
+    Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_slice(pushed)
+        .into_script()
+}
+
+/// A mempool transaction to be included in an assembled block
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BlockTransaction {
+    txid: Txid,
+    wtxid: Wtxid,
+    fee: u64,
+    transaction: Option<bitcoin::Transaction>,
+}
+
+impl BlockTransaction {
+    pub fn new(txid: Txid, wtxid: Wtxid, fee: u64) -> Self {
+        Self {
+            txid,
+            wtxid,
+            fee,
+            transaction: None,
+        }
+    }
+
+    /// A mempool transaction. A transaction loaded from raw hex is
+    /// decoded for its wtxid, a transaction of the CSV mempool carries no
+    /// witness data so its wtxid is taken to be its txid
+    pub fn from_transaction(tx: &Transaction) -> Result<Self, AssemblyError> {
+        let txid = tx
+            .txid()
+            .parse::<Txid>()
+            .map_err(|_| AssemblyError::InvalidTxid {
+                txid: tx.txid().to_owned(),
+            })?;
+
+        Ok(match tx.raw() {
+            Some(raw) => {
+                let transaction = deserialize::<bitcoin::Transaction>(raw).map_err(|_| {
+                    AssemblyError::InvalidRawTransaction {
+                        txid: tx.txid().to_owned(),
+                    }
+                })?;
+
+                Self::new(txid, transaction.wtxid(), tx.fee()).with_transaction(transaction)
+            }
+            None => Self::new(txid, Wtxid::from_raw_hash(txid.to_raw_hash()), tx.fee()),
+        })
+    }
+
+    /// The decoded transaction, required to serialize the whole block
+    pub fn with_transaction(mut self, transaction: bitcoin::Transaction) -> Self {
+        self.transaction = Some(transaction);
+
+        self
+    }
+
+    pub fn txid(&self) -> &Txid {
+        &self.txid
+    }
+
+    pub fn wtxid(&self) -> &Wtxid {
+        &self.wtxid
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn transaction(&self) -> Option<&bitcoin::Transaction> {
+        self.transaction.as_ref()
+    }
+}
+
+/// Builds the coinbase and header of a block from selected transactions
+/// and grinds a nonce that meets the target
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BlockAssembler {
+    payout_script: ScriptBuf,
+    limits: BlockLimits,
+    version: i32,
+    height: u32,
+    prev_blockhash: BlockHash,
+    time: u32,
+    bits: u32,
+    max_attempts: u64,
+}
+
+impl BlockAssembler {
+    /// Pay the subsidy and fees to `payout_script`
+    pub fn new(payout_script: Vec<u8>) -> Self {
+        Self {
+            payout_script: ScriptBuf::from(payout_script),
+            limits: BlockLimits::default(),
+            // BIP9 top bits with no deployment signalled
+            version: 0x2000_0000,
+            height: 1,
+            prev_blockhash: BlockHash::all_zeros(),
+            time: 0,
+            bits: REGTEST_BITS,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+
+    /// Whether the coinbase carries the witness commitment and
+    /// the largest scriptSig it may have
+    pub fn with_limits(mut self, limits: BlockLimits) -> Self {
+        self.limits = limits;
+
+        self
+    }
+
+    pub fn with_version(mut self, version: i32) -> Self {
+        self.version = version;
+
+        self
+    }
+
+    /// Height of the block, committed to in the coinbase scriptSig (BIP34)
+    /// and determining the subsidy
+    pub fn with_height(mut self, height: u32) -> Self {
+        self.height = height;
+
+        self
+    }
+
+    pub fn with_prev_blockhash(mut self, prev_blockhash: BlockHash) -> Self {
+        self.prev_blockhash = prev_blockhash;
+
+        self
+    }
+
+    pub fn with_time(mut self, time: u32) -> Self {
+        self.time = time;
+
+        self
+    }
+
+    /// Compact target the header hash must meet
+    pub fn with_bits(mut self, bits: u32) -> Self {
+        self.bits = bits;
+
+        self
+    }
+
+    /// Header hashes `mine` tries, across every extra nonce, before
+    /// failing with `AssemblyError::TargetNotMet`
+    pub fn with_max_attempts(mut self, max_attempts: u64) -> Self {
+        self.max_attempts = max_attempts;
+
+        self
+    }
+
+    pub fn payout_script(&self) -> &ScriptBuf {
+        &self.payout_script
+    }
+
+    pub fn limits(&self) -> &BlockLimits {
+        &self.limits
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn prev_blockhash(&self) -> &BlockHash {
+        &self.prev_blockhash
+    }
+
+    pub fn max_attempts(&self) -> u64 {
+        self.max_attempts
+    }
+
+    /// Assemble a block with the given extra nonce and a zero header nonce
+    pub fn assemble(
+        &self,
+        transactions: Vec<BlockTransaction>,
+        extra_nonce: u64,
+    ) -> Result<AssembledBlock, AssemblyError> {
+        if self.payout_script.len() as u64 > self.limits.coinbase_payout_script_size() {
+            return Err(AssemblyError::PayoutScriptTooLarge {
+                size: self.payout_script.len(),
+                max_size: self.limits.coinbase_payout_script_size() as usize,
+            });
+        }
+
+        let fees = transactions.iter().map(|tx| tx.fee).sum::<u64>();
+        let coinbase_value = block_subsidy(self.height) + fees;
+
+        let script_sig = Builder::new()
+            .push_int(self.height as i64)
+            .push_int(extra_nonce as i64)
+            .into_script();
+        if script_sig.len() as u64 > self.limits.coinbase_script_sig_size() {
+            return Err(AssemblyError::ScriptSigTooLarge {
+                size: script_sig.len(),
+                max_size: self.limits.coinbase_script_sig_size() as usize,
+            });
+        }
+
+        let mut output = vec![TxOut {
+            value: Amount::from_sat(coinbase_value),
+            script_pubkey: self.payout_script.clone(),
+        }];
+        let mut witness = Witness::new();
+        if self.limits.witness_commitment() {
+            let wtxids = transactions.iter().map(|tx| tx.wtxid).collect::<Vec<_>>();
+            output.push(TxOut {
+                value: Amount::ZERO,
+                script_pubkey: witness_commitment_script(&witness_commitment(&wtxids)),
+            });
+            // The witness reserved value
+            witness.push([0u8; 32]);
+        }
+
+        let coinbase = bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig,
+                sequence: Sequence::MAX,
+                witness,
+            }],
+            output,
+        };
+
+        let txids = Some(coinbase.txid())
+            .into_iter()
+            .chain(transactions.iter().map(|tx| tx.txid))
+            .map(|txid| txid.to_raw_hash());
+        let merkle_root = TxMerkleNode::from_raw_hash(
+            merkle_tree::calculate_root(txids).expect("the coinbase is always there"),
+        );
+
+        Ok(AssembledBlock {
+            header: block::Header {
+                version: block::Version::from_consensus(self.version),
+                prev_blockhash: self.prev_blockhash,
+                merkle_root,
+                time: self.time,
+                bits: CompactTarget::from_consensus(self.bits),
+                nonce: 0,
+            },
+            coinbase,
+            coinbase_value,
+            transactions,
+        })
+    }
+
+    /// Assemble a block and grind the header nonce, moving on to the
+    /// next extra nonce whenever every header nonce has been tried.
+    /// Fails once `max_attempts` header hashes have missed the target.
+    pub fn mine(
+        &self,
+        transactions: Vec<BlockTransaction>,
+    ) -> Result<AssembledBlock, AssemblyError> {
+        let mut attempts = 0u64;
+        let mut extra_nonce = 0u64;
+
+        loop {
+            let mut block = self.assemble(transactions.clone(), extra_nonce)?;
+
+            loop {
+                if attempts == self.max_attempts {
+                    return Err(AssemblyError::TargetNotMet { attempts });
+                }
+                attempts += 1;
+
+                if block.meets_target() {
+                    return Ok(block);
+                }
+                if block.header.nonce == u32::MAX {
+                    break;
+                }
+                block.header.nonce += 1;
+            }
+
+            extra_nonce += 1;
+        }
+    }
+}
+
+/// A block with its coinbase built and its header filled in
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AssembledBlock {
+    header: block::Header,
+    coinbase: bitcoin::Transaction,
+    coinbase_value: u64,
+    transactions: Vec<BlockTransaction>,
+}
+
+impl AssembledBlock {
+    pub fn header(&self) -> &block::Header {
+        &self.header
+    }
+
+    pub fn block_hash(&self) -> BlockHash {
+        self.header.block_hash()
+    }
+
+    /// The header hash is at most the target its bits encode
+    pub fn meets_target(&self) -> bool {
+        self.header.validate_pow(self.header.target()).is_ok()
+    }
+
+    /// The coinbase transaction including its witness
+    pub fn coinbase(&self) -> &bitcoin::Transaction {
+        &self.coinbase
+    }
+
+    pub fn coinbase_txid(&self) -> Txid {
+        self.coinbase.txid()
+    }
+
+    /// Subsidy plus the fees of every transaction in the block
+    pub fn coinbase_value(&self) -> u64 {
+        self.coinbase_value
+    }
+
+    /// The transactions after the coinbase
+    pub fn transactions(&self) -> &[BlockTransaction] {
+        self.transactions.as_slice()
+    }
+
+    /// The whole block, which needs every transaction decoded
+    pub fn to_block(&self) -> Result<Block, AssemblyError> {
+        let mut txdata = Vec::<bitcoin::Transaction>::with_capacity(self.transactions.len() + 1);
+        txdata.push(self.coinbase.clone());
+
+        for tx in self.transactions.iter() {
+            let transaction =
+                tx.transaction()
+                    .ok_or_else(|| AssemblyError::MissingRawTransaction {
+                        txid: tx.txid.to_string(),
+                    })?;
+            txdata.push(transaction.clone());
+        }
+
+        Ok(Block {
+            header: self.header,
+            txdata,
+        })
+    }
+
+    /// Serialize the header, the transaction count, the coinbase and
+    /// every transaction, all of which need their raw bytes
+    pub fn serialize(&self) -> Result<Vec<u8>, AssemblyError> {
+        Ok(serialize(&self.to_block()?))
+    }
+}
+
+/// Why a block could not be assembled or serialized
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AssemblyError {
+    /// A mempool txid is not 64 hex characters
+    InvalidTxid { txid: String },
+    /// The raw bytes of a mempool transaction do not decode
+    InvalidRawTransaction { txid: String },
+    /// The coinbase scriptSig is larger than reserved for in the block limits
+    ScriptSigTooLarge { size: usize, max_size: usize },
+    /// The payout script is larger than reserved for in the block limits
+    PayoutScriptTooLarge { size: usize, max_size: usize },
+    /// A transaction cannot be serialized without its raw bytes
+    MissingRawTransaction { txid: String },
+    /// No header hash met the target within the assembler's attempts
+    TargetNotMet { attempts: u64 },
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTxid { txid } => write!(f, "`{txid}` is not a 64 character hex txid"),
+            Self::InvalidRawTransaction { txid } => {
+                write!(f, "the raw bytes of {txid} are not a transaction")
+            }
+            Self::ScriptSigTooLarge { size, max_size } => write!(
+                f,
+                "the coinbase scriptSig is {size} bytes, more than the {max_size} reserved"
+            ),
+            Self::PayoutScriptTooLarge { size, max_size } => write!(
+                f,
+                "the payout script is {size} bytes, more than the {max_size} reserved"
+            ),
+            Self::MissingRawTransaction { txid } => {
+                write!(
+                    f,
+                    "the raw bytes of {txid} are needed to serialize the block"
+                )
+            }
+            Self::TargetNotMet { attempts } => {
+                write!(f, "no header met the target in {attempts} attempts")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+#[cfg(test)]
+mod block_assembly {
+    use crate::{
+        block_subsidy, AssemblyError, BlockAssembler, BlockLimits, BlockTransaction, Miner,
+    };
+    use bitcoin::{
+        consensus::encode::{deserialize, serialize},
+        hashes::Hash,
+        merkle_tree, Block, TxMerkleNode, Txid, Wtxid,
+    };
+
+    #[test]
+    fn subsidy_halves() {
+        assert_eq!(block_subsidy(0), 5_000_000_000);
+        assert_eq!(block_subsidy(210_000), 2_500_000_000);
+        assert_eq!(block_subsidy(840_000), 312_500_000);
+        assert_eq!(block_subsidy(64 * 210_000), 0);
+    }
+
+    #[test]
+    fn coinbase_pays_subsidy_and_fees_and_fits_its_reservation() {
+        let transactions = vec![
+            BlockTransaction::new(
+                Txid::from_byte_array([1u8; 32]),
+                Wtxid::from_byte_array([1u8; 32]),
+                1_000,
+            ),
+            BlockTransaction::new(
+                Txid::from_byte_array([2u8; 32]),
+                Wtxid::from_byte_array([3u8; 32]),
+                2_500,
+            ),
+        ];
+        let payout_script = [&[0x00, 0x14][..], &[0xab; 20]].concat();
+        let assembler = BlockAssembler::new(payout_script)
+            .with_height(840_000)
+            .with_time(1_700_000_000);
+
+        let block = assembler.mine(transactions).unwrap();
+        let coinbase = block.coinbase();
+        assert_eq!(block.coinbase_value(), 312_500_000 + 3_500);
+        assert_eq!(coinbase.output[0].value.to_sat(), block.coinbase_value());
+        assert!(block.meets_target());
+
+        // A BIP34 height of 840,000 (0x0cd140) followed by the extra nonce
+        assert!(coinbase.is_coinbase());
+        assert_eq!(
+            &coinbase.input[0].script_sig.as_bytes()[..4],
+            &[0x03, 0x40, 0xd1, 0x0c]
+        );
+        assert!(coinbase.weight().to_wu() <= BlockLimits::default().coinbase_weight());
+
+        // The commitment output and the coinbase txid over the stripped serialization
+        assert_eq!(
+            &coinbase.output[1].script_pubkey.as_bytes()[..6],
+            &[0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed]
+        );
+        let txids = [block.coinbase_txid(), Txid::from_byte_array([1u8; 32])]
+            .into_iter()
+            .chain(Some(Txid::from_byte_array([2u8; 32])))
+            .map(|txid| txid.to_raw_hash());
+        assert_eq!(
+            block.header().merkle_root,
+            TxMerkleNode::from_raw_hash(merkle_tree::calculate_root(txids).unwrap())
+        );
+
+        assert_eq!(
+            block.serialize(),
+            Err(AssemblyError::MissingRawTransaction {
+                txid: Txid::from_byte_array([1u8; 32]).to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn coinbase_only_block_serializes() {
+        let block = BlockAssembler::new(vec![0x51])
+            .with_limits(BlockLimits::default().with_witness_commitment(false))
+            .mine(Vec::new())
+            .unwrap();
+
+        let serialized = block.serialize().unwrap();
+        assert_eq!(&serialized[..80], &serialize(block.header()));
+        assert_eq!(serialized[80], 1);
+        assert_eq!(&serialized[81..], &serialize(block.coinbase()));
+        // Without a commitment the coinbase has no witness and is the merkle root
+        assert!(block.coinbase().input[0].witness.is_empty());
+        assert_eq!(
+            block.header().merkle_root.to_raw_hash(),
+            block.coinbase_txid().to_raw_hash()
+        );
+    }
+
+    #[test]
+    fn assembled_block_is_valid_for_rust_bitcoin() {
+        let block = BlockAssembler::new(vec![0x51])
+            .with_height(1_000)
+            .with_time(1_700_000_000)
+            .mine(Vec::new())
+            .unwrap();
+        let decoded = deserialize::<Block>(&block.serialize().unwrap()).unwrap();
+
+        assert!(decoded.check_merkle_root());
+        assert!(decoded.check_witness_commitment());
+        assert_eq!(decoded.bip34_block_height().unwrap(), 1_000);
+        assert_eq!(decoded.block_hash(), block.block_hash());
+        assert_eq!(decoded.txdata[0].txid(), block.coinbase_txid());
+        assert!(decoded.txdata[0].weight().to_wu() <= BlockLimits::default().coinbase_weight());
+    }
+
+    #[test]
+    fn payout_script_must_fit_its_reservation() {
+        // A 35 byte script, one more than a P2WSH or P2TR output script
+        let assembler = BlockAssembler::new(vec![0x51; 35]);
+        assert_eq!(
+            assembler.assemble(Vec::new(), 0),
+            Err(AssemblyError::PayoutScriptTooLarge {
+                size: 35,
+                max_size: 34
+            })
+        );
+
+        let limits = BlockLimits::default().with_coinbase_payout_script_size(35);
+        let block = assembler
+            .with_limits(limits)
+            .assemble(Vec::new(), 0)
+            .unwrap();
+        assert!(block.coinbase().weight().to_wu() <= limits.coinbase_weight());
+    }
+
+    #[test]
+    fn mining_gives_up_after_its_attempts() {
+        // Mainnet's minimum difficulty takes billions of attempts
+        let assembler = BlockAssembler::new(vec![0x51])
+            .with_bits(0x1d00ffff)
+            .with_max_attempts(1_000);
+
+        assert_eq!(
+            assembler.mine(Vec::new()),
+            Err(AssemblyError::TargetNotMet { attempts: 1_000 })
+        );
+    }
+
+    #[test]
+    fn mined_blocks_chain_together() {
+        let mut miner = Miner::load_mempool("mempool.csv").unwrap();
+        miner.mine();
+
+        let blocks = miner
+            .assemble(&BlockAssembler::new(vec![0x51]).with_height(100))
+            .unwrap();
+        assert_eq!(blocks.len(), miner.finalized().len());
+
+        for (index, block) in blocks.iter().enumerate() {
+            assert!(block.meets_target());
+            assert_eq!(block.transactions().len(), miner.finalized()[index].len());
+            if index > 0 {
+                assert_eq!(
+                    block.header().prev_blockhash,
+                    blocks[index - 1].block_hash()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,769 @@
+use crate::MempoolGraph;
+use core::fmt;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, VecDeque},
+};
+
+/// Default limit on the number of transactions in a cluster, as in Bitcoin Core
+pub const MAX_CLUSTER_COUNT: usize = 64;
+/// Default limit on the weight of a cluster, 101 kvB as in Bitcoin Core
+pub const MAX_CLUSTER_WEIGHT: u64 = 101_000 * 4;
+/// Clusters up to this size are linearized optimally by searching every
+/// topologically closed subset; larger ones by best ancestor set
+pub const MAX_EXACT_CLUSTER_SIZE: usize = 16;
+
+/// A set of transactions mined together, their total fee, weight and sigop cost
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Chunk {
+    positions: Vec<usize>,
+    fee: u64,
+    weight: u64,
+    sigop_cost: u64,
+}
+
+impl Chunk {
+    /// Positions of the transactions in the mempool, parents first
+    pub fn positions(&self) -> &[usize] {
+        self.positions.as_slice()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    pub fn sigop_cost(&self) -> u64 {
+        self.sigop_cost
+    }
+
+    /// Compare fee rates exactly by cross-multiplying
+    pub fn cmp_fee_rate(&self, other: &Self) -> Ordering {
+        fee_rate_cmp(self.fee, self.weight, other.fee, other.weight)
+    }
+}
+
+/// A connected component of the mempool graph: transactions linked by
+/// spending each other directly or through other members
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Cluster {
+    linearization: Vec<usize>,
+    chunks: Vec<Chunk>,
+    // One chunk per transaction in linearization order
+    transactions: Vec<Chunk>,
+    optimal: bool,
+}
+
+impl Cluster {
+    /// Positions in the order the cluster should be mined, parents first
+    pub fn linearization(&self) -> &[usize] {
+        self.linearization.as_slice()
+    }
+
+    /// The linearization split into chunks of non-increasing fee rate
+    pub fn chunks(&self) -> &[Chunk] {
+        self.chunks.as_slice()
+    }
+
+    /// The linearization was found by exhaustive search so no other
+    /// order has a better chunk fee rate at any weight
+    pub fn optimal(&self) -> bool {
+        self.optimal
+    }
+
+    pub fn len(&self) -> usize {
+        self.linearization.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.linearization.is_empty()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.fee).sum()
+    }
+
+    pub fn weight(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.weight).sum()
+    }
+
+    // The transactions of a chunk one by one, parents first
+    fn split(&self, chunk: usize) -> Vec<Chunk> {
+        let start = self.chunks[..chunk]
+            .iter()
+            .map(|chunk| chunk.positions.len())
+            .sum::<usize>();
+
+        self.transactions[start..start + self.chunks[chunk].positions.len()].to_vec()
+    }
+}
+
+/// Limits on the size of a cluster, only checked by `ClusterMempool::check_limits`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ClusterLimits {
+    max_count: usize,
+    max_weight: u64,
+}
+
+impl Default for ClusterLimits {
+    fn default() -> Self {
+        Self {
+            max_count: MAX_CLUSTER_COUNT,
+            max_weight: MAX_CLUSTER_WEIGHT,
+        }
+    }
+}
+
+impl ClusterLimits {
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = max_count;
+
+        self
+    }
+
+    pub fn with_max_weight(mut self, max_weight: u64) -> Self {
+        self.max_weight = max_weight;
+
+        self
+    }
+
+    pub fn max_count(&self) -> usize {
+        self.max_count
+    }
+
+    pub fn max_weight(&self) -> u64 {
+        self.max_weight
+    }
+}
+
+/// A cluster over the limits
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ClusterViolation {
+    /// The cluster has more transactions than allowed
+    TooManyTransactions {
+        cluster: usize,
+        count: usize,
+        max_count: usize,
+    },
+    /// The transactions of the cluster weigh more than allowed
+    TooHeavy {
+        cluster: usize,
+        weight: u64,
+        max_weight: u64,
+    },
+}
+
+impl fmt::Display for ClusterViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyTransactions {
+                cluster,
+                count,
+                max_count,
+            } => write!(
+                f,
+                "cluster #{cluster} has {count} transactions, more than the limit of {max_count}"
+            ),
+            Self::TooHeavy {
+                cluster,
+                weight,
+                max_weight,
+            } => write!(
+                f,
+                "cluster #{cluster} weighs {weight}, more than the limit of {max_weight}"
+            ),
+        }
+    }
+}
+
+/// The mineable part of a mempool partitioned into linearized and chunked clusters
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ClusterMempool {
+    clusters: Vec<Cluster>,
+}
+
+impl ClusterMempool {
+    pub fn new(graph: &MempoolGraph) -> Self {
+        let unmineable = graph.unmineable();
+        let mut visited = unmineable.clone();
+        let mut clusters = Vec::<Cluster>::new();
+
+        for start in 0..graph.len() {
+            if visited[start] {
+                continue;
+            }
+
+            let mut members = Vec::<usize>::new();
+            let mut queue = VecDeque::from([start]);
+            visited[start] = true;
+
+            while let Some(position) = queue.pop_front() {
+                members.push(position);
+
+                for &linked in graph
+                    .parents(position)
+                    .iter()
+                    .chain(graph.children(position))
+                {
+                    if !visited[linked] {
+                        visited[linked] = true;
+                        queue.push_back(linked);
+                    }
+                }
+            }
+
+            clusters.push(linearize(graph, members));
+        }
+
+        Self { clusters }
+    }
+
+    pub fn clusters(&self) -> &[Cluster] {
+        self.clusters.as_slice()
+    }
+
+    /// Clusters with more transactions or weight than the limits allow.
+    /// The check is advisory: clusters over the limits are still built,
+    /// linearized and mined, the way a mempool loaded from elsewhere
+    /// already holds them, and only reported here.
+    pub fn check_limits(&self, limits: ClusterLimits) -> Vec<ClusterViolation> {
+        let mut violations = Vec::<ClusterViolation>::new();
+
+        for (index, cluster) in self.clusters.iter().enumerate() {
+            if cluster.len() > limits.max_count {
+                violations.push(ClusterViolation::TooManyTransactions {
+                    cluster: index,
+                    count: cluster.len(),
+                    max_count: limits.max_count,
+                });
+            }
+            if cluster.weight() > limits.max_weight {
+                violations.push(ClusterViolation::TooHeavy {
+                    cluster: index,
+                    weight: cluster.weight(),
+                    max_weight: limits.max_weight,
+                });
+            }
+        }
+
+        violations
+    }
+}
+
+/// Selects whole chunks into blocks by chunk fee rate. The next chunk of a
+/// cluster becomes a candidate once the previous one is included, and a
+/// cluster whose next chunk does not fit is left for the next block. A
+/// chunk too large for an empty block is split into its transactions.
+#[derive(Debug, Clone)]
+pub struct ChunkSelector {
+    mempool: ClusterMempool,
+    // Index of the next chunk of each cluster to be mined
+    next_chunk: Vec<usize>,
+    // Transactions left of a split chunk of each cluster, last one first
+    split: Vec<Vec<Chunk>>,
+}
+
+impl ChunkSelector {
+    pub fn new(graph: &MempoolGraph) -> Self {
+        Self::from_clusters(ClusterMempool::new(graph))
+    }
+
+    pub fn from_clusters(mempool: ClusterMempool) -> Self {
+        Self {
+            next_chunk: vec![0; mempool.clusters.len()],
+            split: vec![Vec::new(); mempool.clusters.len()],
+            mempool,
+        }
+    }
+
+    pub fn mempool(&self) -> &ClusterMempool {
+        &self.mempool
+    }
+
+    /// Fill the next block up to `max_weight` and `max_sigop_cost`
+    /// returning the positions of the selected transactions in topological
+    /// order. Returns `None` once no remaining chunk fits into an empty block.
+    pub fn next_block(&mut self, max_weight: u64, max_sigop_cost: u64) -> Option<Vec<usize>> {
+        let mut candidates = (0..self.mempool.clusters.len())
+            .filter_map(|cluster| self.candidate(cluster))
+            .collect::<BinaryHeap<ChunkCandidate>>();
+
+        let mut block = Vec::<usize>::new();
+        let mut block_weight = 0u64;
+        let mut block_sigop_cost = 0u64;
+
+        while let Some(candidate) = candidates.pop() {
+            let chunk = self
+                .chunk(candidate.cluster)
+                .expect("candidates have a next chunk");
+            if chunk.weight > max_weight || chunk.sigop_cost > max_sigop_cost {
+                // Never fits so its transactions are mined one at a time instead
+                if chunk.positions.len() > 1 {
+                    let mut split = self.mempool.clusters[candidate.cluster].split(candidate.chunk);
+                    split.reverse();
+                    self.split[candidate.cluster] = split;
+                    self.next_chunk[candidate.cluster] += 1;
+                    if let Some(next) = self.candidate(candidate.cluster) {
+                        candidates.push(next);
+                    }
+                }
+                continue;
+            }
+            if block_weight + chunk.weight > max_weight
+                || block_sigop_cost + chunk.sigop_cost > max_sigop_cost
+            {
+                continue;
+            }
+
+            block_weight += chunk.weight;
+            block_sigop_cost += chunk.sigop_cost;
+            block.extend_from_slice(&chunk.positions);
+            if self.split[candidate.cluster].pop().is_none() {
+                self.next_chunk[candidate.cluster] += 1;
+            }
+
+            if let Some(next) = self.candidate(candidate.cluster) {
+                candidates.push(next);
+            }
+        }
+
+        if block.is_empty() {
+            None
+        } else {
+            Some(block)
+        }
+    }
+
+    // The next transaction of a split chunk, or else the next chunk of the cluster
+    fn chunk(&self, cluster: usize) -> Option<&Chunk> {
+        self.split[cluster].last().or_else(|| {
+            self.mempool.clusters[cluster]
+                .chunks
+                .get(self.next_chunk[cluster])
+        })
+    }
+
+    fn candidate(&self, cluster: usize) -> Option<ChunkCandidate> {
+        self.chunk(cluster).map(|data| ChunkCandidate {
+            cluster,
+            chunk: self.next_chunk[cluster],
+            fee: data.fee,
+            weight: data.weight,
+        })
+    }
+}
+
+/// The next chunk of a cluster ordered by fee rate,
+/// then by the cluster that comes first
+#[derive(Debug, PartialEq, Eq)]
+struct ChunkCandidate {
+    cluster: usize,
+    chunk: usize,
+    fee: u64,
+    weight: u64,
+}
+
+impl PartialOrd for ChunkCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ChunkCandidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fee_rate_cmp(self.fee, self.weight, other.fee, other.weight)
+            .then_with(|| other.cluster.cmp(&self.cluster))
+    }
+}
+
+fn fee_rate_cmp(fee: u64, weight: u64, other_fee: u64, other_weight: u64) -> Ordering {
+    (fee as u128 * other_weight as u128).cmp(&(other_fee as u128 * weight as u128))
+}
+
+// Linearize a cluster by repeatedly moving the remaining topologically
+// closed subset with the best fee rate to the end of the linearization,
+// then merge it into chunks
+fn linearize(graph: &MempoolGraph, members: Vec<usize>) -> Cluster {
+    let order = topological_order(graph, members);
+    let local = order
+        .iter()
+        .enumerate()
+        .map(|(index, &position)| (position, index))
+        .collect::<HashMap<usize, usize>>();
+    let parents = order
+        .iter()
+        .map(|&position| {
+            graph
+                .parents(position)
+                .iter()
+                .filter_map(|parent| local.get(parent).copied())
+                .collect::<Vec<usize>>()
+        })
+        .collect::<Vec<Vec<usize>>>();
+    let fees = order
+        .iter()
+        .map(|&position| graph.transaction(position).fee())
+        .collect::<Vec<u64>>();
+    let weights = order
+        .iter()
+        .map(|&position| graph.transaction(position).weight() as u64)
+        .collect::<Vec<u64>>();
+
+    let optimal = order.len() <= MAX_EXACT_CLUSTER_SIZE;
+    let mut remaining = vec![true; order.len()];
+    let mut linearization = Vec::<usize>::with_capacity(order.len());
+
+    while linearization.len() < order.len() {
+        let mut best = vec![false; order.len()];
+        if optimal {
+            let mut search = ClosedSubsetSearch {
+                parents: &parents,
+                fees: &fees,
+                weights: &weights,
+                remaining: &remaining,
+                chosen: vec![false; order.len()],
+                best: &mut best,
+                best_fee: 0,
+                best_weight: 0,
+            };
+            search.branch(0, 0, 0);
+        } else {
+            best_ancestor_set(&parents, &fees, &weights, &remaining, &mut best);
+        }
+
+        // Local indexes follow a topological order so the set stays parents first
+        for (index, &chosen) in best.iter().enumerate() {
+            if chosen {
+                remaining[index] = false;
+                linearization.push(index);
+            }
+        }
+    }
+
+    let transactions = linearization
+        .iter()
+        .map(|&index| Chunk {
+            positions: vec![order[index]],
+            fee: fees[index],
+            weight: weights[index],
+            sigop_cost: graph.transaction(order[index]).sigop_cost(),
+        })
+        .collect::<Vec<Chunk>>();
+
+    let mut chunks = Vec::<Chunk>::new();
+    for transaction in transactions.iter() {
+        let mut chunk = transaction.clone();
+
+        // Merge into the previous chunk while it pays a lower fee rate
+        while let Some(previous) = chunks.last() {
+            if previous.cmp_fee_rate(&chunk) == Ordering::Greater {
+                break;
+            }
+
+            let mut previous = chunks.pop().expect("checked above");
+            previous.positions.append(&mut chunk.positions);
+            previous.fee += chunk.fee;
+            previous.weight += chunk.weight;
+            previous.sigop_cost += chunk.sigop_cost;
+            chunk = previous;
+        }
+
+        chunks.push(chunk);
+    }
+
+    Cluster {
+        linearization: linearization.iter().map(|&index| order[index]).collect(),
+        chunks,
+        transactions,
+        optimal,
+    }
+}
+
+// Members sorted so that every parent comes before its children,
+// ties broken by mempool position
+fn topological_order(graph: &MempoolGraph, mut members: Vec<usize>) -> Vec<usize> {
+    members.sort_unstable();
+
+    let mut pending = members
+        .iter()
+        .map(|&position| graph.parents(position).len())
+        .collect::<Vec<usize>>();
+    let mut ready = members
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| pending[*index] == 0)
+        .map(|(_, &position)| std::cmp::Reverse(position))
+        .collect::<BinaryHeap<_>>();
+    let mut order = Vec::<usize>::with_capacity(members.len());
+
+    while let Some(std::cmp::Reverse(position)) = ready.pop() {
+        order.push(position);
+
+        // Unmineable children are left out of the cluster
+        for &child in graph.children(position) {
+            let Ok(index) = members.binary_search(&child) else {
+                continue;
+            };
+            pending[index] -= 1;
+            if pending[index] == 0 {
+                ready.push(std::cmp::Reverse(child));
+            }
+        }
+    }
+
+    order
+}
+
+/// Exhaustive search for the remaining topologically closed subset
+/// with the best fee rate, preferring the larger of two equal ones
+struct ClosedSubsetSearch<'b> {
+    parents: &'b [Vec<usize>],
+    fees: &'b [u64],
+    weights: &'b [u64],
+    remaining: &'b [bool],
+    chosen: Vec<bool>,
+    best: &'b mut Vec<bool>,
+    best_fee: u64,
+    best_weight: u64,
+}
+
+impl ClosedSubsetSearch<'_> {
+    fn branch(&mut self, index: usize, fee: u64, weight: u64) {
+        if index == self.chosen.len() {
+            let better = match fee_rate_cmp(fee, weight, self.best_fee, self.best_weight) {
+                _ if weight == 0 => false,
+                _ if self.best_weight == 0 => true,
+                Ordering::Greater => true,
+                Ordering::Equal => weight > self.best_weight,
+                Ordering::Less => false,
+            };
+            if better {
+                self.best.clone_from(&self.chosen);
+                self.best_fee = fee;
+                self.best_weight = weight;
+            }
+            return;
+        }
+
+        let closed = self.parents[index]
+            .iter()
+            .all(|&parent| self.chosen[parent] || !self.remaining[parent]);
+
+        if self.remaining[index] && closed {
+            self.chosen[index] = true;
+            self.branch(
+                index + 1,
+                fee + self.fees[index],
+                weight + self.weights[index],
+            );
+            self.chosen[index] = false;
+        }
+
+        self.branch(index + 1, fee, weight);
+    }
+}
+
+// The remaining transaction whose remaining ancestors together with
+// itself pay the best fee rate
+fn best_ancestor_set(
+    parents: &[Vec<usize>],
+    fees: &[u64],
+    weights: &[u64],
+    remaining: &[bool],
+    best: &mut [bool],
+) {
+    let mut best_fee = 0u64;
+    let mut best_weight = 0u64;
+
+    for index in (0..parents.len()).filter(|&index| remaining[index]) {
+        let mut set = vec![false; parents.len()];
+        let mut stack = vec![index];
+        while let Some(member) = stack.pop() {
+            if set[member] {
+                continue;
+            }
+            set[member] = true;
+            stack.extend(
+                parents[member]
+                    .iter()
+                    .filter(|&&parent| remaining[parent] && !set[parent]),
+            );
+        }
+
+        let (fee, weight) = (0..parents.len())
+            .filter(|&member| set[member])
+            .fold((0, 0), |(fee, weight), member| {
+                (fee + fees[member], weight + weights[member])
+            });
+
+        if best_weight == 0 || fee_rate_cmp(fee, weight, best_fee, best_weight) == Ordering::Greater
+        {
+            best.copy_from_slice(&set);
+            best_fee = fee;
+            best_weight = weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod cluster_linearization {
+    use crate::{
+        BlockLimits, ClusterLimits, ClusterMempool, ClusterViolation, FeeEstimator, MempoolGraph,
+        Miner, Projection, Transaction,
+    };
+
+    #[test]
+    fn mempool_is_partitioned_into_clusters() {
+        let mempool = vec![
+            Transaction::new("a", 100, 400, &[]),
+            Transaction::new("b", 100, 400, &[]),
+            Transaction::new("c", 100, 400, &["a", "b"]),
+            Transaction::new("d", 100, 400, &[]),
+            Transaction::new("orphan", 100, 400, &["missing"]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let clusters = ClusterMempool::new(&graph);
+
+        let members = clusters
+            .clusters()
+            .iter()
+            .map(|cluster| {
+                let mut positions = cluster.linearization().to_vec();
+                positions.sort();
+                positions
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(members, vec![vec![0, 1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn chunks_have_non_increasing_fee_rates() {
+        // The best closed subset is `parent` with `rich-child`, the poor
+        // child follows and the unrelated sibling pays the least
+        let mempool = vec![
+            Transaction::new("parent", 100, 1000, &[]),
+            Transaction::new("poor-child", 500, 1000, &["parent"]),
+            Transaction::new("rich-child", 5000, 1000, &["parent"]),
+            Transaction::new("grandchild", 100, 1000, &["poor-child"]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let clusters = ClusterMempool::new(&graph);
+        let cluster = &clusters.clusters()[0];
+
+        assert!(cluster.optimal());
+        assert_eq!(cluster.linearization(), &[0, 2, 1, 3]);
+
+        let chunks = cluster
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.positions().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, vec![vec![0, 2], vec![1], vec![3]]);
+        assert!(cluster
+            .chunks()
+            .windows(2)
+            .all(|pair| pair[0].cmp_fee_rate(&pair[1]).is_ge()));
+    }
+
+    #[test]
+    fn cluster_limits_are_checked() {
+        let mempool = vec![
+            Transaction::new("a", 100, 400, &[]),
+            Transaction::new("b", 100, 400, &["a"]),
+            Transaction::new("c", 100, 400, &["b"]),
+            Transaction::new("d", 100, 400, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let clusters = ClusterMempool::new(&graph);
+
+        assert!(clusters.check_limits(ClusterLimits::default()).is_empty());
+        assert_eq!(
+            clusters.check_limits(
+                ClusterLimits::default()
+                    .with_max_count(2)
+                    .with_max_weight(1000)
+            ),
+            vec![
+                ClusterViolation::TooManyTransactions {
+                    cluster: 0,
+                    count: 3,
+                    max_count: 2
+                },
+                ClusterViolation::TooHeavy {
+                    cluster: 0,
+                    weight: 1200,
+                    max_weight: 1000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unmineable_children_are_left_out() {
+        let mempool = vec![
+            Transaction::new("p", 100, 400, &[]),
+            Transaction::new("c", 100, 400, &["p", "missing"]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        let projection = Projection::chunks(&graph, BlockLimits::default());
+        assert_eq!(projection.blocks().len(), 1);
+        assert_eq!(projection.blocks()[0].positions(), &[0]);
+        assert_eq!(projection.unmineable().len(), 1);
+    }
+
+    #[test]
+    fn chunks_heavier_than_a_block_are_split() {
+        // The child pays for its parent but both do not fit in one block
+        let reserved = BlockLimits::default().reserved_weight();
+        let limits = BlockLimits::default().with_max_block_weight(2000 + reserved);
+        let mempool = vec![
+            Transaction::new("p", 0, 1500, &[]),
+            Transaction::new("c", 10000, 1000, &["p"]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        assert_eq!(ClusterMempool::new(&graph).clusters()[0].chunks().len(), 1);
+
+        let projection = Projection::chunks(&graph, limits);
+        let blocks = projection
+            .blocks()
+            .iter()
+            .map(|block| block.positions().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(blocks, vec![vec![0], vec![1]]);
+        assert!(projection.unmineable().is_empty());
+
+        let estimates = FeeEstimator::default()
+            .with_limits(limits)
+            .with_targets(vec![1, 2])
+            .estimate(&graph);
+        assert_eq!(estimates.estimates()[1].block().unwrap().tx_count(), 1);
+    }
+
+    #[test]
+    fn bundled_mempool_is_chunked() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let graph = MempoolGraph::new(miner.mempool());
+        let clusters = ClusterMempool::new(&graph);
+
+        assert_eq!(
+            clusters
+                .clusters()
+                .iter()
+                .map(|cluster| cluster.len())
+                .sum::<usize>(),
+            5214
+        );
+        assert!(clusters.check_limits(ClusterLimits::default()).is_empty());
+
+        let projection = Projection::chunks(&graph, BlockLimits::default());
+        assert!(projection.unmineable().is_empty());
+        assert!(
+            projection.blocks()[0].total_fee()
+                >= Projection::greedy(&graph, BlockLimits::default()).blocks()[0].total_fee()
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use crate::OutPoint;
+use core::fmt;
+use std::{io, num::ParseIntError, path::PathBuf};
+
+/// Errors returned while loading a mempool
+#[derive(Debug)]
+pub enum MempoolError {
+    /// The mempool file could not be opened or read
+    Io(io::Error),
+    /// A line of the mempool file is malformed
+    Line(LineError),
+    /// The mempool JSON snapshot could not be parsed
+    Json(serde_json::Error),
+    /// An entry of the mempool JSON snapshot is malformed
+    Entry(EntryError),
+    /// A raw transaction cannot be decoded or its fee cannot be computed
+    RawTransaction(RawTransactionError),
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "unable to read the mempool: {error}"),
+            Self::Line(error) => write!(f, "{error}"),
+            Self::Json(error) => write!(f, "invalid mempool JSON: {error}"),
+            Self::Entry(error) => write!(f, "{error}"),
+            Self::RawTransaction(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Json(error) => Some(error),
+            Self::Line(_) | Self::Entry(_) | Self::RawTransaction(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for MempoolError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for MempoolError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl From<EntryError> for MempoolError {
+    fn from(error: EntryError) -> Self {
+        Self::Entry(error)
+    }
+}
+
+impl From<RawTransactionError> for MempoolError {
+    fn from(error: RawTransactionError) -> Self {
+        Self::RawTransaction(error)
+    }
+}
+
+impl From<LineError> for MempoolError {
+    fn from(error: LineError) -> Self {
+        Self::Line(error)
+    }
+}
+
+/// A malformed line of the mempool file.
+/// Line numbers start from `1`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LineError {
+    line: usize,
+    field: Field,
+    kind: LineErrorKind,
+}
+
+impl LineError {
+    pub fn new(line: usize, field: Field, kind: LineErrorKind) -> Self {
+        Self { line, field, kind }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    pub fn kind(&self) -> &LineErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, field `{}`: {}",
+            self.line, self.field, self.kind
+        )
+    }
+}
+
+impl std::error::Error for LineError {}
+
+/// A malformed entry of a mempool JSON snapshot
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EntryError {
+    txid: String,
+    field: Field,
+    kind: LineErrorKind,
+}
+
+impl EntryError {
+    pub fn new(txid: &str, field: Field, kind: LineErrorKind) -> Self {
+        Self {
+            txid: txid.to_owned(),
+            field,
+            kind,
+        }
+    }
+
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    pub fn kind(&self) -> &LineErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "entry {}, field `{}`: {}",
+            self.txid, self.field, self.kind
+        )
+    }
+}
+
+impl std::error::Error for EntryError {}
+
+/// A raw transaction that cannot be turned into a mempool transaction
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RawTransactionError {
+    /// The line is not a hex encoded transaction
+    Decode {
+        path: PathBuf,
+        line: usize,
+        error: String,
+    },
+    /// The transaction was already loaded
+    DuplicateTxid { txid: String },
+    /// A coinbase transaction cannot be in the mempool
+    Coinbase { txid: String },
+    /// An input spends an output that is neither in the UTXO set nor
+    /// created by another loaded transaction
+    MissingPrevout { txid: String, outpoint: OutPoint },
+    /// The outputs are worth more than the inputs
+    NegativeFee {
+        txid: String,
+        input_value: u64,
+        output_value: u64,
+    },
+    /// The inputs or the outputs add up to more than a `u64` holds
+    ValueOverflow { txid: String },
+    /// The transaction weighs more than a `u32` holds
+    WeightOutOfRange { txid: String, weight: u64 },
+}
+
+impl fmt::Display for RawTransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode { path, line, error } => {
+                write!(f, "{} line {line}: {error}", path.display())
+            }
+            Self::DuplicateTxid { txid } => write!(f, "{txid} was already loaded"),
+            Self::Coinbase { txid } => write!(f, "{txid} is a coinbase transaction"),
+            Self::MissingPrevout { txid, outpoint } => write!(
+                f,
+                "{txid} spends {outpoint} which is neither in the UTXO set nor created by a loaded transaction"
+            ),
+            Self::NegativeFee {
+                txid,
+                input_value,
+                output_value,
+            } => write!(
+                f,
+                "{txid} pays out {output_value} sats but only spends {input_value}"
+            ),
+            Self::ValueOverflow { txid } => {
+                write!(f, "the values of {txid} add up to more than {} sats", u64::MAX)
+            }
+            Self::WeightOutOfRange { txid, weight } => {
+                write!(f, "{txid} weighs {weight}, more than {}", u32::MAX)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RawTransactionError {}
+
+/// The fields of a mempool record
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Field {
+    Txid,
+    Fee,
+    Weight,
+    Parents,
+    SigopCost,
+    Inputs,
+    ArrivalTime,
+    Value,
+    ScriptPubkey,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Txid => "txid",
+            Self::Fee => "fee",
+            Self::Weight => "weight",
+            Self::Parents => "parents",
+            Self::SigopCost => "sigop cost",
+            Self::Inputs => "inputs",
+            Self::ArrivalTime => "arrival time",
+            Self::Value => "value",
+            Self::ScriptPubkey => "script pubkey",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// What is wrong with a field of a mempool record
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LineErrorKind {
+    /// The field is missing from the record
+    Missing,
+    /// The field is not a valid unsigned integer
+    InvalidInteger { value: String, error: ParseIntError },
+    /// The integer does not fit the range of the field
+    IntegerOutOfRange { value: String },
+    /// The txid is not 64 hex characters
+    InvalidTxid { value: String },
+    /// The input is not a `txid:vout` outpoint
+    InvalidOutpoint { value: String },
+    /// The BTC amount is negative or not a number
+    InvalidAmount { value: String },
+    /// The field is not hex encoded
+    InvalidHex { value: String },
+    /// A transaction cannot weigh zero weight units
+    ZeroWeight,
+    /// The txid was already loaded from an earlier line
+    DuplicateTxid { txid: String, first_line: usize },
+}
+
+impl fmt::Display for LineErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::InvalidInteger { value, error } => {
+                write!(f, "invalid integer `{value}`: {error}")
+            }
+            Self::IntegerOutOfRange { value } => write!(f, "integer `{value}` is out of range"),
+            Self::InvalidTxid { value } => write!(f, "`{value}` is not a 64 character hex txid"),
+            Self::InvalidOutpoint { value } => {
+                write!(f, "`{value}` is not a `txid:vout` outpoint")
+            }
+            Self::InvalidAmount { value } => write!(f, "invalid BTC amount `{value}`"),
+            Self::InvalidHex { value } => write!(f, "`{value}` is not hex"),
+            Self::ZeroWeight => write!(f, "weight must be greater than zero"),
+            Self::DuplicateTxid { txid, first_line } => {
+                write!(f, "{txid} was already loaded from line {first_line}")
+            }
+        }
+    }
+}
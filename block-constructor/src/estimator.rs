@@ -0,0 +1,373 @@
+use crate::{
+    BlockLimits, ChunkSelector, ClusterMempool, MempoolGraph, Projection, WITNESS_SCALE_FACTOR,
+};
+use core::fmt;
+use serde::Serialize;
+
+/// Blocks a fee suggestion is given for by default
+pub const DEFAULT_TARGETS: [usize; 4] = [1, 2, 3, 6];
+/// Percentiles of the fee rates in each projected block
+pub const FEE_RATE_PERCENTILES: [u8; 5] = [10, 25, 50, 75, 90];
+/// Bitcoin Core's default minimum relay fee rate in sat/vB
+pub const DEFAULT_MIN_FEE_RATE: f64 = 1.0;
+
+/// Suggests the fee rate needed to be mined within a number of blocks by
+/// projecting the mempool into blocks by cluster chunk. A transaction's
+/// fee rate is the one of the chunk it is mined with, so a parent paid
+/// for by its child counts at the rate of the package.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FeeEstimator {
+    limits: BlockLimits,
+    targets: Vec<usize>,
+    min_fee_rate: f64,
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        Self {
+            limits: BlockLimits::default(),
+            targets: DEFAULT_TARGETS.to_vec(),
+            min_fee_rate: DEFAULT_MIN_FEE_RATE,
+        }
+    }
+}
+
+impl FeeEstimator {
+    pub fn with_limits(mut self, limits: BlockLimits) -> Self {
+        self.limits = limits;
+
+        self
+    }
+
+    /// Number of blocks within which to be mined, one estimate each
+    pub fn with_targets(mut self, targets: Vec<usize>) -> Self {
+        self.targets = targets;
+
+        self
+    }
+
+    /// Fee rate in sat/vB suggested when the mempool does not fill the blocks
+    pub fn with_min_fee_rate(mut self, min_fee_rate: f64) -> Self {
+        self.min_fee_rate = min_fee_rate;
+
+        self
+    }
+
+    pub fn limits(&self) -> &BlockLimits {
+        &self.limits
+    }
+
+    pub fn targets(&self) -> &[usize] {
+        self.targets.as_slice()
+    }
+
+    pub fn min_fee_rate(&self) -> f64 {
+        self.min_fee_rate
+    }
+
+    pub fn estimate(&self, graph: &MempoolGraph) -> FeeEstimates {
+        let clusters = ClusterMempool::new(graph);
+
+        // Fee rate in sat/vB each transaction is mined at
+        let mut fee_rates = vec![0f64; graph.len()];
+        for chunk in clusters
+            .clusters()
+            .iter()
+            .flat_map(|cluster| cluster.chunks())
+        {
+            let fee_rate = fee_rate(chunk.fee(), chunk.weight());
+            for &position in chunk.positions() {
+                fee_rates[position] = fee_rate;
+            }
+        }
+
+        let mut selector = ChunkSelector::from_clusters(clusters);
+        let projection = Projection::project(graph, self.limits, |max_weight, max_sigop_cost| {
+            selector.next_block(max_weight, max_sigop_cost)
+        });
+        let blocks = projection.blocks();
+
+        // The last block is the rest of the mempool so any fee rate gets into it
+        let block_estimates = blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| {
+                let mut rates = block
+                    .positions()
+                    .iter()
+                    .map(|&position| {
+                        (
+                            fee_rates[position],
+                            graph.transaction(position).weight() as u64,
+                        )
+                    })
+                    .collect::<Vec<(f64, u64)>>();
+                rates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let min_fee_rate = if index + 1 == blocks.len() {
+                    self.min_fee_rate
+                } else {
+                    rates
+                        .first()
+                        .map_or(self.min_fee_rate, |&(rate, _)| rate.max(self.min_fee_rate))
+                };
+
+                BlockFeeRates {
+                    block: index + 1,
+                    min_fee_rate,
+                    percentiles: FEE_RATE_PERCENTILES
+                        .iter()
+                        .map(|&percentile| FeeRatePercentile {
+                            percentile,
+                            fee_rate: weighted_percentile(&rates, percentile),
+                        })
+                        .collect(),
+                    tx_count: rates.len(),
+                    total_fee: block.total_fee(),
+                }
+            })
+            .collect::<Vec<BlockFeeRates>>();
+
+        let estimates = self
+            .targets
+            .iter()
+            .map(|&target| {
+                // Paying any of the first `target` blocks' minimum gets in by then
+                let fee_rate = block_estimates
+                    .iter()
+                    .take(target)
+                    .map(|block| block.min_fee_rate)
+                    .fold(f64::INFINITY, f64::min);
+
+                FeeEstimate {
+                    target,
+                    fee_rate: if fee_rate.is_finite() && target <= block_estimates.len() {
+                        fee_rate
+                    } else {
+                        self.min_fee_rate
+                    },
+                    block: target
+                        .checked_sub(1)
+                        .and_then(|index| block_estimates.get(index))
+                        .cloned(),
+                }
+            })
+            .collect();
+
+        FeeEstimates { estimates }
+    }
+}
+
+/// Fee rate suggestions, one per target
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct FeeEstimates {
+    estimates: Vec<FeeEstimate>,
+}
+
+impl FeeEstimates {
+    pub fn estimates(&self) -> &[FeeEstimate] {
+        self.estimates.as_slice()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("fee estimates serialize to JSON")
+    }
+}
+
+impl fmt::Display for FeeEstimates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<7} {:>10}", "blocks", "sat/vB")?;
+        for percentile in FEE_RATE_PERCENTILES {
+            write!(f, " {:>9}", format!("p{percentile}"))?;
+        }
+        writeln!(f, " {:>8} {:>14}", "txs", "fees (sats)")?;
+
+        for estimate in self.estimates.iter() {
+            write!(f, "{:<7} {:>10.2}", estimate.target, estimate.fee_rate)?;
+            match &estimate.block {
+                Some(block) => {
+                    for percentile in block.percentiles.iter() {
+                        write!(f, " {:>9.2}", percentile.fee_rate)?;
+                    }
+                    writeln!(f, " {:>8} {:>14}", block.tx_count, block.total_fee)?;
+                }
+                None => {
+                    for _ in FEE_RATE_PERCENTILES {
+                        write!(f, " {:>9}", "-")?;
+                    }
+                    writeln!(f, " {:>8} {:>14}", 0, 0)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The fee rate needed to be mined within `target` blocks
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct FeeEstimate {
+    target: usize,
+    fee_rate: f64,
+    block: Option<BlockFeeRates>,
+}
+
+impl FeeEstimate {
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// Suggested fee rate in sat/vB
+    pub fn fee_rate(&self) -> f64 {
+        self.fee_rate
+    }
+
+    /// The projected block at the target, `None` if the mempool does not reach it
+    pub fn block(&self) -> Option<&BlockFeeRates> {
+        self.block.as_ref()
+    }
+}
+
+/// The fee rates of the transactions in a projected block
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct BlockFeeRates {
+    block: usize,
+    min_fee_rate: f64,
+    percentiles: Vec<FeeRatePercentile>,
+    tx_count: usize,
+    total_fee: u64,
+}
+
+impl BlockFeeRates {
+    /// Height of the block relative to the tip, starting at 1
+    pub fn block(&self) -> usize {
+        self.block
+    }
+
+    /// Lowest fee rate that got into the block, or the minimum fee rate
+    /// if the block has room to spare
+    pub fn min_fee_rate(&self) -> f64 {
+        self.min_fee_rate
+    }
+
+    pub fn percentiles(&self) -> &[FeeRatePercentile] {
+        self.percentiles.as_slice()
+    }
+
+    pub fn tx_count(&self) -> usize {
+        self.tx_count
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.total_fee
+    }
+}
+
+/// Fee rate in sat/vB below which `percentile` percent of the block's weight lies
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub struct FeeRatePercentile {
+    percentile: u8,
+    fee_rate: f64,
+}
+
+impl FeeRatePercentile {
+    pub fn percentile(&self) -> u8 {
+        self.percentile
+    }
+
+    pub fn fee_rate(&self) -> f64 {
+        self.fee_rate
+    }
+}
+
+fn fee_rate(fee: u64, weight: u64) -> f64 {
+    (fee * WITNESS_SCALE_FACTOR) as f64 / weight as f64
+}
+
+// `rates` are (fee rate, weight) sorted by fee rate
+fn weighted_percentile(rates: &[(f64, u64)], percentile: u8) -> f64 {
+    let total_weight = rates.iter().map(|&(_, weight)| weight).sum::<u64>();
+    let threshold = total_weight * percentile as u64;
+    let mut weight = 0u64;
+
+    for &(rate, tx_weight) in rates {
+        weight += tx_weight;
+        if weight * 100 >= threshold {
+            return rate;
+        }
+    }
+
+    rates.last().map_or(0.0, |&(rate, _)| rate)
+}
+
+#[cfg(test)]
+mod fee_estimation {
+    use crate::{BlockLimits, FeeEstimator, MempoolGraph, Miner, Transaction};
+
+    #[test]
+    fn estimates_follow_the_projected_blocks() {
+        // Four 1000 weight unit transactions per block
+        let reserved = BlockLimits::default().reserved_weight();
+        let limits = BlockLimits::default().with_max_block_weight(4000 + reserved);
+        let mempool = (0..10)
+            .map(|index| Transaction::new(&format!("{index:064x}"), 1000 * (index + 1), 1000, &[]))
+            .collect::<Vec<Transaction>>();
+        let graph = MempoolGraph::new(&mempool);
+
+        let estimates = FeeEstimator::default().with_limits(limits).estimate(&graph);
+        let fee_rates = estimates
+            .estimates()
+            .iter()
+            .map(|estimate| (estimate.target(), estimate.fee_rate()))
+            .collect::<Vec<_>>();
+        // Block 1 pays 28 to 40 sat/vB, block 2 12 to 24 and block 3 holds the rest
+        assert_eq!(fee_rates, vec![(1, 28.0), (2, 12.0), (3, 1.0), (6, 1.0)]);
+
+        let first = estimates.estimates()[0].block().unwrap();
+        assert_eq!(first.tx_count(), 4);
+        assert_eq!(first.percentiles()[0].fee_rate(), 28.0);
+        assert_eq!(first.percentiles()[2].fee_rate(), 32.0);
+        assert_eq!(first.percentiles()[4].fee_rate(), 40.0);
+        assert!(estimates.estimates()[3].block().is_none());
+    }
+
+    #[test]
+    fn children_raise_the_fee_rate_of_their_parents() {
+        let reserved = BlockLimits::default().reserved_weight();
+        let limits = BlockLimits::default().with_max_block_weight(2000 + reserved);
+        let mempool = vec![
+            Transaction::new("parent", 0, 1000, &[]),
+            Transaction::new("child", 4000, 1000, &["parent"]),
+            Transaction::new("single", 1000, 1000, &[]),
+            Transaction::new("other", 1000, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        let estimates = FeeEstimator::default()
+            .with_limits(limits)
+            .with_targets(vec![1])
+            .estimate(&graph);
+        let block = estimates.estimates()[0].block().unwrap();
+        assert_eq!(estimates.estimates()[0].fee_rate(), 8.0);
+        assert_eq!(block.percentiles()[0].fee_rate(), 8.0);
+    }
+
+    #[test]
+    fn bundled_mempool_estimates() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let estimates = FeeEstimator::default().estimate(&MempoolGraph::new(miner.mempool()));
+        assert_eq!(estimates.to_string().lines().count(), 5);
+
+        let fee_rates = estimates
+            .estimates()
+            .iter()
+            .map(|estimate| estimate.fee_rate())
+            .collect::<Vec<f64>>();
+        assert!(fee_rates.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(fee_rates[0] > 1.0);
+
+        let json = serde_json::from_str::<serde_json::Value>(&estimates.to_json()).unwrap();
+        assert_eq!(json["estimates"].as_array().unwrap().len(), 4);
+        assert_eq!(json["estimates"][0]["target"], 1);
+    }
+}
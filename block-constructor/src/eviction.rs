@@ -0,0 +1,371 @@
+use crate::{
+    virtual_size, MempoolGraph, Transaction, DEFAULT_BYTES_PER_SIGOP, DEFAULT_INCREMENTAL_RELAY_FEE,
+};
+use core::fmt;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashSet},
+};
+
+/// Bitcoin Core's `-maxmempool` of 300 MB, taken as virtual bytes
+pub const DEFAULT_MAX_MEMPOOL_SIZE: u64 = 300_000_000;
+
+/// Caps the size of the mempool the way Bitcoin Core trims it under
+/// congestion. While the mempool is too large the transaction with the
+/// lowest descendant score, the higher of its own fee rate and the fee rate
+/// of it plus its descendants, is evicted together with its descendants,
+/// and the minimum fee rate rises to the evicted package's fee rate plus
+/// the incremental relay fee. Core caps the memory the mempool uses, which
+/// is larger than the virtual size capped here.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MempoolSizeLimit {
+    max_size: u64,
+    incremental_relay_fee: u64,
+    bytes_per_sigop: u64,
+}
+
+impl Default for MempoolSizeLimit {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_MEMPOOL_SIZE,
+            incremental_relay_fee: DEFAULT_INCREMENTAL_RELAY_FEE,
+            bytes_per_sigop: DEFAULT_BYTES_PER_SIGOP,
+        }
+    }
+}
+
+impl MempoolSizeLimit {
+    /// Limit in virtual bytes
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+
+        self
+    }
+
+    /// Fee rate in sat/kvB added to the fee rate of the evicted packages
+    pub fn with_incremental_relay_fee(mut self, incremental_relay_fee: u64) -> Self {
+        self.incremental_relay_fee = incremental_relay_fee;
+
+        self
+    }
+
+    pub fn with_bytes_per_sigop(mut self, bytes_per_sigop: u64) -> Self {
+        self.bytes_per_sigop = bytes_per_sigop;
+
+        self
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    pub fn incremental_relay_fee(&self) -> u64 {
+        self.incremental_relay_fee
+    }
+
+    pub fn bytes_per_sigop(&self) -> u64 {
+        self.bytes_per_sigop
+    }
+
+    /// Evict packages until the sigop adjusted virtual size of the
+    /// mempool fits the limit, returning the transactions left in their
+    /// original order
+    pub fn trim(&self, transactions: Vec<Transaction>) -> (Vec<Transaction>, TrimReport) {
+        let graph = MempoolGraph::new(&transactions);
+        let vsizes = (0..graph.len())
+            .map(|position| {
+                let tx = graph.transaction(position);
+                virtual_size(tx.weight() as u64, tx.sigop_cost(), self.bytes_per_sigop)
+            })
+            .collect::<Vec<u64>>();
+
+        let mut report = TrimReport {
+            size: vsizes.iter().sum(),
+            ..Default::default()
+        };
+
+        let mut descendant_fee = vec![0u64; graph.len()];
+        let mut descendant_size = vec![0u64; graph.len()];
+        for position in 0..graph.len() {
+            let descendants = graph.descendants(position);
+            descendant_fee[position] = graph.transaction(position).fee()
+                + descendants
+                    .iter()
+                    .filter(|&&descendant| descendant != position)
+                    .map(|&descendant| graph.transaction(descendant).fee())
+                    .sum::<u64>();
+            descendant_size[position] = vsizes[position]
+                + descendants
+                    .iter()
+                    .filter(|&&descendant| descendant != position)
+                    .map(|&descendant| vsizes[descendant])
+                    .sum::<u64>();
+        }
+
+        let score = |position: usize, fee: &[u64], size: &[u64]| {
+            DescendantScore::new(
+                position,
+                (graph.transaction(position).fee(), vsizes[position]),
+                (fee[position], size[position]),
+            )
+        };
+        let mut queue = (0..graph.len())
+            .map(|position| score(position, &descendant_fee, &descendant_size))
+            .collect::<BTreeSet<DescendantScore>>();
+        let mut evicted = vec![false; graph.len()];
+
+        while report.size > self.max_size {
+            let Some(lowest) = queue.pop_first() else {
+                break;
+            };
+
+            // The package is the transaction and its descendants still in the mempool
+            let mut package = Some(lowest.position)
+                .into_iter()
+                .chain(graph.descendants(lowest.position))
+                .filter(|&member| !evicted[member])
+                .collect::<Vec<usize>>();
+            package.sort_unstable();
+            package.dedup();
+
+            let package_fee = package
+                .iter()
+                .map(|&member| graph.transaction(member).fee())
+                .sum::<u64>();
+            let package_size = package.iter().map(|&member| vsizes[member]).sum::<u64>();
+
+            // Ancestors still in the mempool lose the evicted descendants
+            let mut rescored = HashSet::<usize>::new();
+            for &member in package.iter() {
+                evicted[member] = true;
+                queue.remove(&score(member, &descendant_fee, &descendant_size));
+
+                for ancestor in graph.ancestors(member) {
+                    if evicted[ancestor] || package.contains(&ancestor) {
+                        continue;
+                    }
+
+                    if rescored.insert(ancestor) {
+                        queue.remove(&score(ancestor, &descendant_fee, &descendant_size));
+                    }
+                    descendant_fee[ancestor] -= graph.transaction(member).fee();
+                    descendant_size[ancestor] -= vsizes[member];
+                }
+            }
+            for ancestor in rescored {
+                queue.insert(score(ancestor, &descendant_fee, &descendant_size));
+            }
+
+            // Fee rate in sat/kvB a transaction now has to pay to be accepted
+            let fee_rate = package_fee * 1000 / package_size.max(1);
+            report.min_fee_rate = report
+                .min_fee_rate
+                .max(fee_rate + self.incremental_relay_fee);
+            report.size -= package_size;
+            report.evicted.push(EvictedPackage {
+                txids: package
+                    .iter()
+                    .map(|&member| graph.transaction(member).txid().to_owned())
+                    .collect(),
+                fee: package_fee,
+                size: package_size,
+            });
+        }
+
+        let survivors = transactions
+            .into_iter()
+            .zip(evicted)
+            .filter_map(|(tx, evicted)| (!evicted).then_some(tx))
+            .collect();
+
+        (survivors, report)
+    }
+}
+
+/// What trimming the mempool to its size limit evicted
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TrimReport {
+    evicted: Vec<EvictedPackage>,
+    size: u64,
+    min_fee_rate: u64,
+}
+
+impl TrimReport {
+    /// Packages in the order they were evicted
+    pub fn evicted(&self) -> &[EvictedPackage] {
+        self.evicted.as_slice()
+    }
+
+    pub fn evicted_count(&self) -> usize {
+        self.evicted.iter().map(|package| package.txids.len()).sum()
+    }
+
+    /// Virtual size of the mempool left
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Fee rate in sat/kvB transactions need to enter the trimmed
+    /// mempool, zero if nothing was evicted
+    pub fn min_fee_rate(&self) -> u64 {
+        self.min_fee_rate
+    }
+}
+
+impl fmt::Display for TrimReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for package in self.evicted.iter() {
+            writeln!(f, "{package}")?;
+        }
+
+        writeln!(
+            f,
+            "Evicted {} transactions in {} packages, {} vB left, minimum fee rate {} sat/kvB",
+            self.evicted_count(),
+            self.evicted.len(),
+            self.size,
+            self.min_fee_rate
+        )
+    }
+}
+
+/// A transaction evicted together with its descendants
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EvictedPackage {
+    txids: Vec<String>,
+    fee: u64,
+    size: u64,
+}
+
+impl EvictedPackage {
+    pub fn txids(&self) -> &[String] {
+        self.txids.as_slice()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    /// Virtual size in vbytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl fmt::Display for EvictedPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Evicted {} ({} sats, {} vB)",
+            self.txids.join(", "),
+            self.fee,
+            self.size
+        )
+    }
+}
+
+/// A transaction in the eviction queue, the lowest score is evicted first
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct DescendantScore {
+    fee: u64,
+    size: u64,
+    position: usize,
+}
+
+impl DescendantScore {
+    // The higher of the transaction's own and its descendant package's fee rate
+    fn new(position: usize, own: (u64, u64), descendants: (u64, u64)) -> Self {
+        let (fee, size) =
+            if own.0 as u128 * descendants.1 as u128 > descendants.0 as u128 * own.1 as u128 {
+                own
+            } else {
+                descendants
+            };
+
+        Self {
+            fee,
+            size,
+            position,
+        }
+    }
+}
+
+impl PartialOrd for DescendantScore {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DescendantScore {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.fee as u128 * other.size as u128)
+            .cmp(&(other.fee as u128 * self.size as u128))
+            .then_with(|| other.position.cmp(&self.position))
+    }
+}
+
+#[cfg(test)]
+mod mempool_trimming {
+    use crate::{MempoolSizeLimit, Miner, Transaction};
+
+    #[test]
+    fn lowest_descendant_score_is_evicted_first() {
+        let mempool = vec![
+            // A high fee child protects its parent
+            Transaction::new("parent", 100, 400, &[]),
+            Transaction::new("child", 5000, 400, &["parent"]),
+            Transaction::new("cheap", 100, 400, &[]),
+            Transaction::new("cheap-child", 200, 400, &["cheap"]),
+            Transaction::new("fair", 1000, 400, &[]),
+        ];
+
+        let (survivors, report) = MempoolSizeLimit::default().with_max_size(300).trim(mempool);
+        let txids = survivors.iter().map(|tx| tx.txid()).collect::<Vec<_>>();
+        assert_eq!(txids, vec!["parent", "child", "fair"]);
+
+        assert_eq!(report.evicted().len(), 1);
+        assert_eq!(report.evicted()[0].txids(), &["cheap", "cheap-child"]);
+        assert_eq!(report.evicted()[0].size(), 200);
+        // 300 sats for 200 vB is 1500 sat/kvB, plus the incremental relay fee
+        assert_eq!(report.min_fee_rate(), 2500);
+        assert_eq!(report.size(), 300);
+    }
+
+    #[test]
+    fn evicting_a_child_rescores_its_parent() {
+        let mempool = vec![
+            Transaction::new("parent", 100, 400, &[]),
+            Transaction::new("child-a", 150, 400, &["parent"]),
+            Transaction::new("child-b", 120, 400, &["parent"]),
+            Transaction::new("single", 300, 400, &[]),
+        ];
+
+        // The poorer child goes alone, then the parent takes the other child with it
+        let (survivors, report) = MempoolSizeLimit::default().with_max_size(100).trim(mempool);
+        assert_eq!(survivors[0].txid(), "single");
+        let evicted = report
+            .evicted()
+            .iter()
+            .map(|package| package.txids().join(","))
+            .collect::<Vec<_>>();
+        assert_eq!(evicted, vec!["child-b", "parent,child-a"]);
+    }
+
+    #[test]
+    fn bundled_mempool_trimmed_to_half() {
+        let mut miner = Miner::load_mempool("mempool.csv").unwrap();
+        let size = MempoolSizeLimit::default()
+            .trim(miner.mempool().to_vec())
+            .1
+            .size();
+
+        let report = miner.trim_mempool(MempoolSizeLimit::default().with_max_size(size / 2));
+        assert!(report.size() <= size / 2);
+        assert!(report.min_fee_rate() > 1000);
+        assert_eq!(miner.mempool().len() + report.evicted_count(), 5214);
+
+        // Nothing left depends on an evicted transaction
+        miner.mine();
+        assert!(miner.unmineable().is_empty());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{
+    witness_commitment, witness_commitment_script, AssembledBlock, BlockLimits, Transaction,
+    WITNESS_SCALE_FACTOR,
+};
+use bitcoin::consensus::encode::serialize_hex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Largest serialized block in bytes, reported as `sizelimit`
+pub const MAX_BLOCK_SERIALIZED_SIZE: u64 = 4_000_000;
+
+/// A block template in the shape of Bitcoin Core's `getblocktemplate`
+/// result (BIP22, BIP23 and BIP145) so it can be fed to pool software.
+/// Field names follow the RPC rather than Rust conventions.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct GetBlockTemplate {
+    version: i32,
+    rules: Vec<String>,
+    previousblockhash: String,
+    transactions: Vec<TemplateTransaction>,
+    coinbasevalue: u64,
+    target: String,
+    mutable: Vec<String>,
+    noncerange: String,
+    sigoplimit: u64,
+    sizelimit: u64,
+    weightlimit: u64,
+    curtime: u32,
+    bits: String,
+    height: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_witness_commitment: Option<String>,
+}
+
+/// A transaction of a `getblocktemplate` result
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct TemplateTransaction {
+    /// Raw transaction hex, left out when the mempool does not carry it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    txid: String,
+    hash: String,
+    /// 1-based indexes of the parents earlier in the template
+    depends: Vec<usize>,
+    fee: u64,
+    sigops: u64,
+    weight: u32,
+}
+
+impl GetBlockTemplate {
+    /// Describe the mined `transactions` with the header fields of the
+    /// block assembled from them at `height`
+    pub fn new(
+        transactions: &[Transaction],
+        block: &AssembledBlock,
+        height: u32,
+        limits: &BlockLimits,
+    ) -> Self {
+        let header = block.header();
+        let positions = transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| (tx.txid(), index + 1))
+            .collect::<HashMap<&str, usize>>();
+
+        let template_transactions = transactions
+            .iter()
+            .zip(block.transactions())
+            .map(|(tx, block_tx)| {
+                let mut depends = tx
+                    .parent_txids()
+                    .iter()
+                    .filter_map(|parent| positions.get(parent.as_str()).copied())
+                    .collect::<Vec<usize>>();
+                depends.sort_unstable();
+
+                TemplateTransaction {
+                    data: block_tx.transaction().map(serialize_hex),
+                    txid: tx.txid().to_owned(),
+                    hash: block_tx.wtxid().to_string(),
+                    depends,
+                    fee: tx.fee(),
+                    sigops: tx.sigop_cost(),
+                    weight: tx.weight(),
+                }
+            })
+            .collect::<Vec<TemplateTransaction>>();
+
+        let default_witness_commitment = limits.witness_commitment().then(|| {
+            let wtxids = block
+                .transactions()
+                .iter()
+                .map(|tx| *tx.wtxid())
+                .collect::<Vec<_>>();
+
+            witness_commitment_script(&witness_commitment(&wtxids)).to_hex_string()
+        });
+
+        Self {
+            version: header.version.to_consensus(),
+            rules: if limits.witness_commitment() {
+                vec!["csv".to_owned(), "!segwit".to_owned(), "taproot".to_owned()]
+            } else {
+                vec!["csv".to_owned()]
+            },
+            previousblockhash: header.prev_blockhash.to_string(),
+            coinbasevalue: block.coinbase_value(),
+            target: hex::encode(header.target().to_be_bytes()),
+            mutable: vec![
+                "time".to_owned(),
+                "transactions".to_owned(),
+                "prevblock".to_owned(),
+            ],
+            noncerange: "00000000ffffffff".to_owned(),
+            sigoplimit: limits.max_block_sigop_cost(),
+            sizelimit: MAX_BLOCK_SERIALIZED_SIZE,
+            weightlimit: limits.max_block_weight(),
+            curtime: header.time,
+            bits: format!("{:08x}", header.bits.to_consensus()),
+            height,
+            transactions: template_transactions,
+            default_witness_commitment,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn previous_blockhash(&self) -> &str {
+        self.previousblockhash.as_str()
+    }
+
+    pub fn transactions(&self) -> &[TemplateTransaction] {
+        self.transactions.as_slice()
+    }
+
+    /// Subsidy plus fees
+    pub fn coinbase_value(&self) -> u64 {
+        self.coinbasevalue
+    }
+
+    pub fn default_witness_commitment(&self) -> Option<&str> {
+        self.default_witness_commitment.as_deref()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("block templates serialize to JSON")
+    }
+}
+
+impl TemplateTransaction {
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn depends(&self) -> &[usize] {
+        self.depends.as_slice()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+}
+
+/// One CSV line per template: height, previous block hash, transaction
+/// count, transaction weight, sigop cost, fees, coinbase value and the
+/// lowest and highest transaction fee rate in sat/vB
+pub fn templates_csv(templates: &[GetBlockTemplate]) -> String {
+    let mut csv = "height,previousblockhash,transactions,weight,sigops,fees,coinbasevalue,min_fee_rate,max_fee_rate\n".to_owned();
+
+    for template in templates {
+        let transactions = template.transactions.as_slice();
+        let fee_rates = transactions
+            .iter()
+            .map(|tx| (tx.fee * WITNESS_SCALE_FACTOR) as f64 / tx.weight as f64);
+        let min_fee_rate = fee_rates.clone().reduce(f64::min).unwrap_or_default();
+        let max_fee_rate = fee_rates.reduce(f64::max).unwrap_or_default();
+
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{:.2},{:.2}\n",
+            template.height,
+            template.previousblockhash,
+            transactions.len(),
+            transactions.iter().map(|tx| tx.weight as u64).sum::<u64>(),
+            transactions.iter().map(|tx| tx.sigops).sum::<u64>(),
+            transactions.iter().map(|tx| tx.fee).sum::<u64>(),
+            template.coinbasevalue,
+            min_fee_rate,
+            max_fee_rate
+        ));
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod block_template_output {
+    use crate::{templates_csv, BlockAssembler, BlockLimits, Miner, Transaction};
+
+    const PARENT: &str = "2e3da8fbc1eaca8ed9b7c2db9e6545d8ccac3c67deadee95db050e41c1eedfc0";
+    const CHILD: &str = "b0ef627c8dc2a706475d33d7712209ec779f7a8302aaeab86c64cf00316a3df8";
+    const SINGLE: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    #[test]
+    fn templates_list_dependencies_as_indexes() {
+        let mut miner = Miner::from_transactions(vec![
+            Transaction::new(PARENT, 100, 1000, &[]),
+            Transaction::new(CHILD, 9000, 1000, &[PARENT]).with_sigop_cost(4),
+            Transaction::new(SINGLE, 2000, 1000, &[]),
+        ]);
+        miner.mine();
+
+        let assembler = BlockAssembler::new(vec![0x51]).with_height(10);
+        let templates = miner.block_templates(&assembler).unwrap();
+        assert_eq!(templates.len(), 1);
+
+        let template = &templates[0];
+        assert_eq!(template.height(), 10);
+        assert_eq!(template.coinbase_value(), 50 * 100_000_000 + 11_100);
+        assert_eq!(
+            template.coinbase_value(),
+            miner.assemble(&assembler).unwrap()[0].coinbase_value()
+        );
+        let txids = template
+            .transactions()
+            .iter()
+            .map(|tx| (tx.txid(), tx.depends()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            txids,
+            vec![(PARENT, &[][..]), (CHILD, &[1][..]), (SINGLE, &[][..])]
+        );
+
+        let json = serde_json::from_str::<serde_json::Value>(&template.to_json()).unwrap();
+        assert_eq!(json["coinbasevalue"], 5_000_011_100u64);
+        assert_eq!(json["transactions"][1]["depends"][0], 1);
+        assert_eq!(json["transactions"][1]["sigops"], 4);
+        assert_eq!(json["weightlimit"], 4_000_000);
+        assert_eq!(json["bits"], "207fffff");
+        assert!(json["transactions"][0].get("data").is_none());
+        assert!(template
+            .default_witness_commitment()
+            .unwrap()
+            .starts_with("6a24aa21a9ed"));
+    }
+
+    #[test]
+    fn templates_chain_and_summarise_as_csv() {
+        let reserved = BlockLimits::default().reserved_weight();
+        let mut miner = Miner::from_transactions(vec![
+            Transaction::new(PARENT, 1000, 1000, &[]),
+            Transaction::new(SINGLE, 2000, 1000, &[]),
+        ])
+        .with_limits(
+            BlockLimits::default()
+                .with_max_block_weight(1000 + reserved)
+                .with_max_block_sigop_cost(40_000),
+        );
+        miner.mine();
+
+        let templates = miner
+            .block_templates(&BlockAssembler::new(vec![0x51]))
+            .unwrap();
+        let json = serde_json::from_str::<serde_json::Value>(&templates[0].to_json()).unwrap();
+        assert_eq!(json["sigoplimit"], 40_000);
+        let assembled = miner.assemble(&BlockAssembler::new(vec![0x51])).unwrap();
+        assert_eq!(
+            templates[1].previous_blockhash(),
+            assembled[0].block_hash().to_string()
+        );
+
+        let csv = templates_csv(&templates);
+        let lines = csv.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("height,previousblockhash,transactions"));
+        assert_eq!(
+            lines[1],
+            format!("1,{},1,1000,0,2000,5000002000,8.00,8.00", "0".repeat(64))
+        );
+        assert!(lines[2].starts_with("2,"));
+        assert!(lines[2].ends_with(",1,1000,0,1000,5000001000,4.00,4.00"));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::Transaction;
+use std::collections::{BTreeSet, HashMap};
+
+/// The dependency graph of a mempool where every transaction
+/// is referred to by its index in the mempool slice
+#[derive(Debug)]
+pub struct MempoolGraph<'a> {
+    transactions: &'a [Transaction],
+    index: HashMap<&'a str, usize>,
+    parents: Vec<Vec<usize>>,
+    children: Vec<Vec<usize>>,
+    missing_parents: Vec<Vec<&'a str>>,
+}
+
+impl<'a> MempoolGraph<'a> {
+    pub fn new(transactions: &'a [Transaction]) -> Self {
+        let index = transactions
+            .iter()
+            .enumerate()
+            .map(|(position, tx)| (tx.txid(), position))
+            .collect::<HashMap<&str, usize>>();
+
+        let mut parents = vec![Vec::<usize>::new(); transactions.len()];
+        let mut children = vec![Vec::<usize>::new(); transactions.len()];
+        let mut missing_parents = vec![Vec::<&str>::new(); transactions.len()];
+
+        for (position, tx) in transactions.iter().enumerate() {
+            for parent_txid in tx.parent_txids() {
+                match index.get(parent_txid.as_str()) {
+                    Some(&parent) => {
+                        if !parents[position].contains(&parent) {
+                            parents[position].push(parent);
+                            children[parent].push(position);
+                        }
+                    }
+                    None => missing_parents[position].push(parent_txid.as_str()),
+                }
+            }
+        }
+
+        Self {
+            transactions,
+            index,
+            parents,
+            children,
+            missing_parents,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    pub fn transaction(&self, position: usize) -> &'a Transaction {
+        &self.transactions[position]
+    }
+
+    pub fn transactions(&self) -> &'a [Transaction] {
+        self.transactions
+    }
+
+    pub fn position(&self, txid: &str) -> Option<usize> {
+        self.index.get(txid).copied()
+    }
+
+    pub fn parents(&self, position: usize) -> &[usize] {
+        &self.parents[position]
+    }
+
+    pub fn children(&self, position: usize) -> &[usize] {
+        &self.children[position]
+    }
+
+    /// Parent txids that are not part of this mempool
+    pub fn missing_parents(&self, position: usize) -> &[&'a str] {
+        &self.missing_parents[position]
+    }
+
+    /// All in-mempool ancestors of a transaction, not including the transaction itself
+    /// unless the graph contains a cycle through it
+    pub fn ancestors(&self, position: usize) -> BTreeSet<usize> {
+        self.walk(position, &self.parents)
+    }
+
+    /// All in-mempool descendants of a transaction, not including the transaction itself
+    /// unless the graph contains a cycle through it
+    pub fn descendants(&self, position: usize) -> BTreeSet<usize> {
+        self.walk(position, &self.children)
+    }
+
+    /// Transactions that can never be mined from this mempool because
+    /// one of their ancestors is missing from it or they depend on themselves
+    pub fn unmineable(&self) -> Vec<bool> {
+        let mut unmineable = vec![false; self.len()];
+
+        for position in 0..self.len() {
+            if unmineable[position] {
+                continue;
+            }
+
+            let ancestors = self.ancestors(position);
+            if !self.missing_parents[position].is_empty()
+                || ancestors.contains(&position)
+                || ancestors
+                    .iter()
+                    .any(|&ancestor| !self.missing_parents[ancestor].is_empty())
+            {
+                unmineable[position] = true;
+                self.descendants(position)
+                    .into_iter()
+                    .for_each(|descendant| unmineable[descendant] = true);
+            }
+        }
+
+        unmineable
+    }
+
+    fn walk(&self, position: usize, edges: &[Vec<usize>]) -> BTreeSet<usize> {
+        let mut visited = BTreeSet::<usize>::new();
+        let mut pending = edges[position].clone();
+
+        while let Some(next) = pending.pop() {
+            if visited.insert(next) {
+                pending.extend_from_slice(&edges[next]);
+            }
+        }
+
+        visited
+    }
+}
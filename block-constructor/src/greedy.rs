@@ -0,0 +1,106 @@
+use crate::{MempoolGraph, Transaction};
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// Selects transactions into blocks by each transaction's own fee rate.
+/// A transaction becomes a candidate once all its in-mempool parents are
+/// included, either in a previous block or earlier in the current one,
+/// so children are considered as soon as their parents are mined instead
+/// of waiting for the next block.
+#[derive(Debug)]
+pub struct GreedySelector<'a> {
+    graph: &'a MempoolGraph<'a>,
+    // Number of parents of each transaction not yet included in a block
+    pending_parents: Vec<usize>,
+    included: Vec<bool>,
+    unmineable: Vec<bool>,
+}
+
+impl<'a> GreedySelector<'a> {
+    pub fn new(graph: &'a MempoolGraph<'a>) -> Self {
+        Self {
+            graph,
+            pending_parents: (0..graph.len())
+                .map(|position| graph.parents(position).len())
+                .collect(),
+            included: vec![false; graph.len()],
+            unmineable: graph.unmineable(),
+        }
+    }
+
+    /// Transactions that have not been selected into a block
+    /// and are not unmineable
+    pub fn remaining(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.graph.len())
+            .filter(|&position| !self.included[position] && !self.unmineable[position])
+    }
+
+    /// Fill the next block up to `max_weight` and `max_sigop_cost`
+    /// returning the positions of the selected transactions in topological
+    /// order. Returns `None` once no remaining transaction fits into an empty block.
+    pub fn next_block(&mut self, max_weight: u64, max_sigop_cost: u64) -> Option<Vec<usize>> {
+        let mut ready = self
+            .remaining()
+            .filter(|&position| self.pending_parents[position] == 0)
+            .map(|position| Candidate {
+                tx: self.graph.transaction(position),
+                position,
+            })
+            .collect::<BinaryHeap<Candidate>>();
+
+        let mut block = Vec::<usize>::new();
+        let mut block_weight = 0u64;
+        let mut block_sigop_cost = 0u64;
+
+        while let Some(candidate) = ready.pop() {
+            let weight = candidate.tx.weight() as u64;
+            let sigop_cost = candidate.tx.sigop_cost();
+            if block_weight + weight > max_weight || block_sigop_cost + sigop_cost > max_sigop_cost
+            {
+                continue;
+            }
+
+            self.included[candidate.position] = true;
+            block_weight += weight;
+            block_sigop_cost += sigop_cost;
+            block.push(candidate.position);
+
+            for &child in self.graph.children(candidate.position) {
+                self.pending_parents[child] -= 1;
+
+                if self.pending_parents[child] == 0 && !self.unmineable[child] {
+                    ready.push(Candidate {
+                        tx: self.graph.transaction(child),
+                        position: child,
+                    });
+                }
+            }
+        }
+
+        if block.is_empty() {
+            None
+        } else {
+            Some(block)
+        }
+    }
+}
+
+/// A transaction whose parents are all included, ordered by fee rate
+#[derive(Debug, PartialEq, Eq)]
+struct Candidate<'a> {
+    tx: &'a Transaction,
+    position: usize,
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.tx
+            .cmp(other.tx)
+            .then_with(|| other.position.cmp(&self.position))
+    }
+}
@@ -0,0 +1,627 @@
+use crate::Transaction;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet},
+};
+
+/// Packages that may fail to fit in a row before a nearly full block is
+/// closed, as in Bitcoin Core's block assembler
+pub const MAX_CONSECUTIVE_FAILURES: usize = 1_000;
+/// Free weight under which a block counts as nearly full
+pub const BLOCK_FULL_ENOUGH_WEIGHT_DELTA: u64 = 4_000;
+
+/// A mempool maintained incrementally as transactions arrive, are mined,
+/// evicted or have their fee changed, instead of being rebuilt into a
+/// `MempoolGraph` for every block. Txids are interned into ids on arrival,
+/// parents and children are kept as adjacency lists of ids and the
+/// transactions whose ancestors are all present are queued by ancestor
+/// fee rate, so adding, removing and rescoring a transaction takes
+/// logarithmic time in the size of the mempool for each affected relative.
+///
+/// A parent that is neither in the mempool nor mined from it is waited
+/// for, and the transaction is only queued once it arrives.
+#[derive(Debug, Default)]
+pub struct IndexedMempool {
+    // Indexed by id, ids are never reused
+    entries: Vec<Option<Entry>>,
+    index: HashMap<String, usize>,
+    // Transactions waiting for a parent txid to arrive
+    waiting: HashMap<String, Vec<usize>>,
+    mined: HashSet<String>,
+    queue: BTreeSet<Score>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    tx: Transaction,
+    // Fee the transaction is scored with, see `IndexedMempool::update_fee`
+    fee: u64,
+    parents: Vec<usize>,
+    children: Vec<usize>,
+    // Parents that have not arrived plus parents that are not ready,
+    // the transaction is ready and scored once it is zero
+    unready_parents: usize,
+    ancestor_fee: u64,
+    ancestor_weight: u64,
+    ancestor_sigop_cost: u64,
+    queued: bool,
+}
+
+impl IndexedMempool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a transaction returning `false` if its txid is already in the
+    /// mempool or was mined from it
+    pub fn add(&mut self, tx: Transaction) -> bool {
+        if self.index.contains_key(tx.txid()) || self.mined.contains(tx.txid()) {
+            return false;
+        }
+
+        let id = self.entries.len();
+        let mut parents = Vec::<usize>::new();
+        let mut unready_parents = 0usize;
+
+        let parent_txids = tx
+            .parent_txids()
+            .iter()
+            .map(|parent| parent.as_str())
+            .collect::<BTreeSet<&str>>();
+        for parent_txid in parent_txids {
+            match self.index.get(parent_txid) {
+                Some(&parent) => {
+                    parents.push(parent);
+                    if !self.entry(parent).is_ready() {
+                        unready_parents += 1;
+                    }
+                }
+                None if self.mined.contains(parent_txid) => {}
+                None => {
+                    self.waiting
+                        .entry(parent_txid.to_owned())
+                        .or_default()
+                        .push(id);
+                    unready_parents += 1;
+                }
+            }
+        }
+
+        for &parent in parents.iter() {
+            self.entry_mut(parent).children.push(id);
+        }
+
+        // Link the transactions that were waiting for this one, they
+        // already count it as an unready parent
+        let children = self
+            .waiting
+            .remove(tx.txid())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&child| self.entries[child].is_some())
+            .collect::<Vec<usize>>();
+        for &child in children.iter() {
+            self.entry_mut(child).parents.push(id);
+        }
+
+        self.index.insert(tx.txid().to_owned(), id);
+        self.entries.push(Some(Entry {
+            fee: tx.fee(),
+            tx,
+            parents,
+            children,
+            unready_parents,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            ancestor_sigop_cost: 0,
+            queued: false,
+        }));
+        self.len += 1;
+
+        if unready_parents == 0 {
+            self.make_ready(id);
+        }
+
+        true
+    }
+
+    /// Remove a transaction and all its descendants, which spend it and
+    /// are invalid without it, returning them parents first
+    pub fn remove(&mut self, txid: &str) -> Vec<Transaction> {
+        let Some(&id) = self.index.get(txid) else {
+            return Vec::new();
+        };
+
+        let mut removed = vec![id];
+        removed.extend(self.descendants(id));
+
+        removed
+            .into_iter()
+            .map(|id| {
+                self.dequeue(id);
+                let entry = self.entries[id].take().expect("descendants are alive");
+                for &parent in entry.parents.iter() {
+                    if let Some(parent) = self.entries[parent].as_mut() {
+                        parent.children.retain(|&child| child != id);
+                    }
+                }
+                self.index.remove(entry.tx.txid());
+                self.len -= 1;
+
+                entry.tx
+            })
+            .collect()
+    }
+
+    /// Score a transaction with `fee` instead of the fee it pays, like
+    /// Bitcoin Core's `prioritisetransaction`, rescoring its descendants
+    pub fn update_fee(&mut self, txid: &str, fee: u64) -> bool {
+        let Some(&id) = self.index.get(txid) else {
+            return false;
+        };
+
+        let old_fee = self.entry(id).fee;
+        self.entry_mut(id).fee = fee;
+
+        // Ancestor scores of transactions that are not ready yet are
+        // computed once they are
+        for member in std::iter::once(id).chain(self.descendants(id)) {
+            if self.entry(member).is_ready() {
+                self.rescore(member, |entry| {
+                    entry.ancestor_fee = entry.ancestor_fee + fee - old_fee
+                });
+            }
+        }
+
+        true
+    }
+
+    /// Mine the best packages by ancestor fee rate into a block of at most
+    /// `max_weight` and `max_sigop_cost`, removing them from the mempool.
+    /// The transactions are returned in topological order, `None` if nothing fits.
+    pub fn next_block(&mut self, max_weight: u64, max_sigop_cost: u64) -> Option<Vec<Transaction>> {
+        let mut block = Vec::<Transaction>::new();
+        let mut block_weight = 0u64;
+        let mut block_sigop_cost = 0u64;
+        let mut failed = Vec::<usize>::new();
+        let mut consecutive_failures = 0usize;
+
+        while let Some(score) = self.queue.pop_last() {
+            self.entry_mut(score.id).queued = false;
+
+            if block_weight + score.weight > max_weight
+                || block_sigop_cost + self.entry(score.id).ancestor_sigop_cost > max_sigop_cost
+            {
+                failed.push(score.id);
+                consecutive_failures += 1;
+
+                if consecutive_failures > MAX_CONSECUTIVE_FAILURES
+                    && block_weight + BLOCK_FULL_ENOUGH_WEIGHT_DELTA > max_weight
+                {
+                    break;
+                }
+                continue;
+            }
+
+            consecutive_failures = 0;
+            for member in self.package(score.id) {
+                self.dequeue(member);
+                let tx = self.mine(member);
+                block_weight += tx.weight() as u64;
+                block_sigop_cost += tx.sigop_cost();
+                block.push(tx);
+            }
+        }
+
+        // Packages that did not fit are candidates for the next block again
+        for id in failed {
+            if self.entries[id].is_some() && !self.entry(id).queued {
+                self.enqueue(id);
+            }
+        }
+
+        if block.is_empty() {
+            None
+        } else {
+            Some(block)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, txid: &str) -> bool {
+        self.index.contains_key(txid)
+    }
+
+    pub fn transaction(&self, txid: &str) -> Option<&Transaction> {
+        self.index.get(txid).map(|&id| &self.entry(id).tx)
+    }
+
+    /// Fee and weight of the transaction plus its ancestors still in the
+    /// mempool, `None` while a parent has not arrived
+    pub fn ancestor_score(&self, txid: &str) -> Option<(u64, u64)> {
+        let entry = self.entry(*self.index.get(txid)?);
+
+        entry
+            .is_ready()
+            .then_some((entry.ancestor_fee, entry.ancestor_weight))
+    }
+
+    /// The transaction with the best ancestor fee rate
+    pub fn best(&self) -> Option<&Transaction> {
+        self.queue.last().map(|score| &self.entry(score.id).tx)
+    }
+
+    fn entry(&self, id: usize) -> &Entry {
+        self.entries[id]
+            .as_ref()
+            .expect("ids refer to live entries")
+    }
+
+    fn entry_mut(&mut self, id: usize) -> &mut Entry {
+        self.entries[id]
+            .as_mut()
+            .expect("ids refer to live entries")
+    }
+
+    // Score the transaction and every descendant it was the last unready parent of
+    fn make_ready(&mut self, id: usize) {
+        let mut ready = vec![id];
+
+        while let Some(id) = ready.pop() {
+            let (fee, weight, sigop_cost) =
+                self.package(id)
+                    .iter()
+                    .fold((0, 0, 0), |(fee, weight, sigop_cost), &member| {
+                        let entry = self.entry(member);
+                        (
+                            fee + entry.fee,
+                            weight + entry.tx.weight() as u64,
+                            sigop_cost + entry.tx.sigop_cost(),
+                        )
+                    });
+
+            let entry = self.entry_mut(id);
+            entry.ancestor_fee = fee;
+            entry.ancestor_weight = weight;
+            entry.ancestor_sigop_cost = sigop_cost;
+            self.enqueue(id);
+
+            for child in self.entry(id).children.clone() {
+                let child_entry = self.entry_mut(child);
+                child_entry.unready_parents -= 1;
+                if child_entry.unready_parents == 0 {
+                    ready.push(child);
+                }
+            }
+        }
+    }
+
+    // Remove a transaction whose parents were all mined before it
+    fn mine(&mut self, id: usize) -> Transaction {
+        let (fee, weight, sigop_cost) = {
+            let entry = self.entry(id);
+            (entry.fee, entry.tx.weight() as u64, entry.tx.sigop_cost())
+        };
+
+        // Descendants still waiting for a parent are scored once it arrives
+        for descendant in self.descendants(id) {
+            if !self.entry(descendant).is_ready() {
+                continue;
+            }
+            self.rescore(descendant, |entry| {
+                entry.ancestor_fee -= fee;
+                entry.ancestor_weight -= weight;
+                entry.ancestor_sigop_cost -= sigop_cost;
+            });
+        }
+
+        let entry = self.entries[id].take().expect("mined entries are alive");
+        for &child in entry.children.iter() {
+            self.entry_mut(child).parents.retain(|&parent| parent != id);
+        }
+        self.index.remove(entry.tx.txid());
+        self.mined.insert(entry.tx.txid().to_owned());
+        self.len -= 1;
+
+        entry.tx
+    }
+
+    fn rescore(&mut self, id: usize, update: impl FnOnce(&mut Entry)) {
+        let queued = self.entry(id).queued;
+        if queued {
+            self.dequeue(id);
+        }
+        update(self.entry_mut(id));
+        if queued {
+            self.enqueue(id);
+        }
+    }
+
+    fn enqueue(&mut self, id: usize) {
+        let entry = self.entry_mut(id);
+        entry.queued = true;
+        let score = Score {
+            fee: entry.ancestor_fee,
+            weight: entry.ancestor_weight,
+            id,
+        };
+        self.queue.insert(score);
+    }
+
+    fn dequeue(&mut self, id: usize) {
+        let entry = self.entry_mut(id);
+        if entry.queued {
+            entry.queued = false;
+            let score = Score {
+                fee: entry.ancestor_fee,
+                weight: entry.ancestor_weight,
+                id,
+            };
+            self.queue.remove(&score);
+        }
+    }
+
+    // The transaction and its ancestors in the mempool, parents first
+    fn package(&self, id: usize) -> Vec<usize> {
+        let mut package = Vec::<usize>::new();
+        let mut visited = HashSet::<usize>::new();
+        let mut stack = vec![(id, false)];
+
+        while let Some((member, expanded)) = stack.pop() {
+            if expanded {
+                package.push(member);
+            } else if visited.insert(member) {
+                stack.push((member, true));
+                stack.extend(
+                    self.entry(member)
+                        .parents
+                        .iter()
+                        .filter(|parent| !visited.contains(parent))
+                        .map(|&parent| (parent, false)),
+                );
+            }
+        }
+
+        package
+    }
+
+    fn descendants(&self, id: usize) -> Vec<usize> {
+        let mut descendants = Vec::<usize>::new();
+        let mut visited = HashSet::from([id]);
+        let mut stack = self.entry(id).children.clone();
+
+        while let Some(child) = stack.pop() {
+            if visited.insert(child) {
+                descendants.push(child);
+                stack.extend_from_slice(&self.entry(child).children);
+            }
+        }
+
+        descendants
+    }
+}
+
+impl Entry {
+    fn is_ready(&self) -> bool {
+        self.unready_parents == 0
+    }
+}
+
+/// A queued transaction ordered by ancestor fee rate, the earlier arrival
+/// sorting last among equal fee rates
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Score {
+    fee: u64,
+    weight: u64,
+    id: usize,
+}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.fee as u128 * other.weight as u128)
+            .cmp(&(other.fee as u128 * self.weight as u128))
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+#[cfg(test)]
+mod indexed_mempool {
+    use crate::{
+        strategy, synthetic_mempool, BlockLimits, IndexedMempool, MempoolGraph, Miner, Projection,
+        Transaction, MAX_BLOCK_SIGOPS_COST,
+    };
+    use std::collections::HashSet;
+
+    fn txids(block: &[Transaction]) -> Vec<&str> {
+        block.iter().map(|tx| tx.txid()).collect()
+    }
+
+    #[test]
+    fn children_wait_for_their_parents() {
+        let mut mempool = IndexedMempool::new();
+        assert!(mempool.add(Transaction::new("child", 5000, 1000, &["parent"])));
+        assert!(mempool.add(Transaction::new("single", 2000, 1000, &[])));
+        assert_eq!(mempool.ancestor_score("child"), None);
+        assert_eq!(mempool.best().unwrap().txid(), "single");
+
+        assert!(mempool.add(Transaction::new("parent", 100, 1000, &[])));
+        assert!(!mempool.add(Transaction::new("parent", 100, 1000, &[])));
+        assert_eq!(mempool.ancestor_score("child"), Some((5100, 2000)));
+        assert_eq!(mempool.best().unwrap().txid(), "child");
+
+        let block = mempool.next_block(2000, MAX_BLOCK_SIGOPS_COST).unwrap();
+        assert_eq!(txids(&block), vec!["parent", "child"]);
+        assert_eq!(mempool.len(), 1);
+
+        // A child of a mined transaction is ready straight away
+        assert!(mempool.add(Transaction::new("grandchild", 100, 1000, &["child"])));
+        assert_eq!(mempool.ancestor_score("grandchild"), Some((100, 1000)));
+        assert!(!mempool.add(Transaction::new("child", 5000, 1000, &["parent"])));
+    }
+
+    #[test]
+    fn removing_and_updating_rescore_relatives() {
+        let mut mempool = IndexedMempool::new();
+        mempool.add(Transaction::new("parent", 100, 1000, &[]));
+        mempool.add(Transaction::new("child", 2000, 1000, &["parent"]));
+        mempool.add(Transaction::new("grandchild", 4000, 1000, &["child"]));
+        mempool.add(Transaction::new("single", 1500, 1000, &[]));
+
+        assert!(mempool.update_fee("parent", 3100));
+        assert_eq!(mempool.ancestor_score("grandchild"), Some((9100, 3000)));
+        assert_eq!(mempool.best().unwrap().txid(), "parent");
+
+        let removed = mempool.remove("child");
+        assert_eq!(txids(&removed), vec!["child", "grandchild"]);
+        assert_eq!(mempool.len(), 2);
+        assert!(!mempool.contains("grandchild"));
+
+        let block = mempool.next_block(1000, MAX_BLOCK_SIGOPS_COST).unwrap();
+        assert_eq!(txids(&block), vec!["parent"]);
+        assert_eq!(
+            txids(&mempool.next_block(1000, MAX_BLOCK_SIGOPS_COST).unwrap()),
+            vec!["single"]
+        );
+        assert!(mempool.next_block(1000, MAX_BLOCK_SIGOPS_COST).is_none());
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn packages_that_do_not_fit_are_retried_in_the_next_block() {
+        let mut mempool = IndexedMempool::new();
+        mempool.add(Transaction::new("heavy", 9000, 3000, &[]));
+        mempool.add(Transaction::new("light", 1000, 1000, &[]));
+
+        assert_eq!(
+            txids(&mempool.next_block(2000, MAX_BLOCK_SIGOPS_COST).unwrap()),
+            vec!["light"]
+        );
+        assert!(mempool.next_block(2000, MAX_BLOCK_SIGOPS_COST).is_none());
+        assert_eq!(
+            txids(&mempool.next_block(3000, MAX_BLOCK_SIGOPS_COST).unwrap()),
+            vec!["heavy"]
+        );
+    }
+
+    #[test]
+    fn mining_a_parent_leaves_children_waiting_for_another() {
+        let mut mempool = IndexedMempool::new();
+        mempool.add(Transaction::new("p", 100, 1000, &[]));
+        mempool.add(Transaction::new("c", 5000, 1000, &["p", "missing"]));
+
+        assert_eq!(
+            txids(&mempool.next_block(4000, MAX_BLOCK_SIGOPS_COST).unwrap()),
+            vec!["p"]
+        );
+        assert_eq!(mempool.ancestor_score("c"), None);
+
+        mempool.add(Transaction::new("missing", 100, 1000, &[]));
+        assert_eq!(mempool.ancestor_score("c"), Some((5100, 2000)));
+        assert_eq!(
+            txids(&mempool.next_block(4000, MAX_BLOCK_SIGOPS_COST).unwrap()),
+            vec!["missing", "c"]
+        );
+    }
+
+    #[test]
+    fn miner_mines_from_the_indexed_mempool() {
+        let mut miner = Miner::from_transactions(vec![
+            Transaction::new("parent", 100, 1000, &[]),
+            Transaction::new("child", 5000, 1000, &["parent"]),
+            Transaction::new("orphan", 9000, 1000, &["missing"]),
+        ]);
+        miner.mine();
+
+        assert_eq!(miner.finalized().len(), 1);
+        assert_eq!(txids(&miner.finalized()[0]), vec!["parent", "child"]);
+        assert_eq!(miner.unmineable().len(), 1);
+        assert_eq!(txids(miner.mempool()), vec!["orphan"]);
+    }
+
+    #[test]
+    fn mine_and_the_default_strategy_agree() {
+        let mut mined = Miner::load_mempool("mempool.csv").unwrap();
+        mined.mine();
+
+        // The command line mines with the strategy named `ancestor`
+        let mut selected = Miner::load_mempool("mempool.csv").unwrap();
+        selected.mine_with(strategy("ancestor").unwrap().as_ref());
+
+        assert_eq!(mined.finalized(), selected.finalized());
+        assert_eq!(mined.binding_limits(), selected.binding_limits());
+        assert_eq!(mined.mempool(), selected.mempool());
+    }
+
+    fn mine_all(transactions: &[Transaction], limits: BlockLimits) -> Vec<Vec<Transaction>> {
+        let mut mempool = IndexedMempool::new();
+        for tx in transactions {
+            mempool.add(tx.clone());
+        }
+
+        let mut blocks = Vec::<Vec<Transaction>>::new();
+        while let Some(block) =
+            mempool.next_block(limits.max_tx_weight(), limits.max_tx_sigop_cost())
+        {
+            blocks.push(block);
+        }
+        assert!(mempool.is_empty());
+
+        blocks
+    }
+
+    fn assert_parents_first(blocks: &[Vec<Transaction>]) {
+        let mut mined = HashSet::<&str>::new();
+        for tx in blocks.iter().flatten() {
+            assert!(tx
+                .parent_txids()
+                .iter()
+                .all(|parent| mined.contains(parent.as_str())));
+            mined.insert(tx.txid());
+        }
+    }
+
+    #[test]
+    fn bundled_mempool_matches_ancestor_packages() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let limits = BlockLimits::default();
+
+        let blocks = mine_all(miner.mempool(), limits);
+        assert_parents_first(&blocks);
+        assert_eq!(blocks.iter().map(|block| block.len()).sum::<usize>(), 5214);
+
+        let projection = Projection::ancestor_packages(&MempoolGraph::new(miner.mempool()), limits);
+        let fees = blocks
+            .iter()
+            .map(|block| block.iter().map(|tx| tx.fee()).sum::<u64>())
+            .collect::<Vec<u64>>();
+        assert_eq!(fees[0], projection.blocks()[0].total_fee());
+        assert_eq!(fees.iter().sum::<u64>(), projection.total_fee());
+    }
+
+    #[test]
+    fn synthetic_mempool_is_mined_completely() {
+        let mempool = synthetic_mempool(10_000, 1);
+        assert_eq!(mempool, synthetic_mempool(10_000, 1));
+
+        let blocks = mine_all(&mempool, BlockLimits::default());
+        assert_parents_first(&blocks);
+        assert_eq!(
+            blocks.iter().flatten().map(|tx| tx.fee()).sum::<u64>(),
+            mempool.iter().map(|tx| tx.fee()).sum::<u64>()
+        );
+    }
+}
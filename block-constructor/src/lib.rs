@@ -0,0 +1,68 @@
+mod assembler;
+pub use assembler::*;
+
+mod gbt;
+pub use gbt::*;
+
+mod error;
+pub use error::*;
+
+mod transaction;
+pub use transaction::*;
+
+mod rawmempool;
+pub use rawmempool::*;
+
+mod rawtx;
+pub use rawtx::*;
+
+mod graph;
+pub use graph::*;
+
+mod indexed;
+pub use indexed::*;
+
+mod ancestor;
+pub use ancestor::*;
+
+mod cluster;
+pub use cluster::*;
+
+mod greedy;
+pub use greedy::*;
+
+mod solver;
+pub use solver::*;
+
+mod strategy;
+pub use strategy::*;
+
+mod template;
+pub use template::*;
+
+mod policy;
+pub use policy::*;
+
+mod replacement;
+pub use replacement::*;
+
+mod eviction;
+pub use eviction::*;
+
+mod projection;
+pub use projection::*;
+
+mod visualization;
+pub use visualization::*;
+
+mod estimator;
+pub use estimator::*;
+
+mod simulation;
+pub use simulation::*;
+
+mod miner;
+pub use miner::*;
+
+mod validator;
+pub use validator::*;
@@ -1,153 +1,193 @@
-use std::{
-    collections::HashSet,
-    fs::File,
-    io::{prelude::*, BufReader},
-    path::Path,
+use bitcoin::consensus::encode::serialize_hex;
+use block_constructor::{
+    strategies, strategy, templates_csv, BlockAssembler, ComparisonReport, FeeEstimator,
+    MempoolGraph, MempoolSizeLimit, MempoolVisualization, Miner, PolicyLimits, SimulationConfig,
+    SimulationReport, SolverConfig, SolverReport, UtxoSet,
 };
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1).peekable();
+    // `compare` runs every strategy on the mempool and prints a report,
+    // `assemble` prints each mined block's hash, header and coinbase,
+    // `policy` lists the transactions Bitcoin Core's default limits refuse,
+    // `estimate` suggests fee rates for the next blocks, `simulate`
+    // mines the mempool as it fills up over time, `graph` prints the
+    // dependency graph with the block each transaction is projected into
+    // and `solve` prints the fee the solver gains over greedy in each block
+    let command = args.next_if(|arg| {
+        [
+            "compare", "assemble", "policy", "estimate", "simulate", "graph", "solve",
+        ]
+        .contains(&arg.as_str())
+    });
+
+    // Flags may come in any order around the mempool path
+    let mut json = false;
+    let mut strategy_name = "ancestor".to_owned();
+    let mut format = "txids".to_owned();
+    let mut max_mempool = None;
+    let mut utxos = UtxoSet::new();
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // `--json` prints the fee estimates or the graph as JSON instead
+            // of a table or Graphviz DOT
+            "--json" => json = true,
+            // `--strategy <name>` picks the strategy used to mine
+            "--strategy" => strategy_name = args.next().ok_or("--strategy needs a name")?,
+            // `--format <txids|gbt|csv>` prints the mined blocks as txids
+            // separated by blank lines, `getblocktemplate` JSON or a CSV line per block
+            "--format" => format = args.next().ok_or("--format needs txids, gbt or csv")?,
+            // `--max-mempool <vbytes>` evicts the cheapest packages until the mempool fits
+            "--max-mempool" => {
+                max_mempool = Some(
+                    args.next()
+                        .ok_or("--max-mempool needs a size in vbytes")?
+                        .parse::<u64>()?,
+                )
+            }
+            // `--utxos <file>` lists the values of the confirmed outputs raw transactions spend
+            "--utxos" => utxos = UtxoSet::load(args.next().ok_or("--utxos needs a file")?)?,
+            flag if flag.starts_with("--") => return Err(format!("unknown flag `{flag}`").into()),
+            other if path.is_none() => path = Some(other.to_owned()),
+            other => return Err(format!("unexpected argument `{other}`").into()),
+        }
+    }
 
-pub const MAX_BLOCK_TX_WEIGHT: u32 = 4_000_000;
+    let selection_strategy =
+        strategy(&strategy_name).ok_or(format!("unknown strategy `{strategy_name}`"))?;
+    if !["txids", "gbt", "csv"].contains(&format.as_str()) {
+        return Err(format!("unknown format `{format}`").into());
+    }
 
-fn main() {
-    let mut miner = Miner::load_mempool("mempool.csv");
-    miner.mine();
+    // A `.json` mempool is a snapshot saved from `bitcoin-cli getrawmempool true`,
+    // a directory or a `.hex` file holds serialized transactions, one per line
+    let path = path.unwrap_or("mempool.csv".to_owned());
+    let extension = Path::new(&path).extension();
+    let mut miner = if extension.is_some_and(|ext| ext == "json") {
+        Miner::load_raw_mempool(&path)?
+    } else if Path::new(&path).is_dir() || extension.is_some_and(|ext| ext == "hex") {
+        Miner::load_raw_transactions(&path, &utxos)?
+    } else {
+        Miner::load_mempool(&path)?
+    };
+
+    if let Some(max_size) = max_mempool {
+        eprint!(
+            "{}",
+            miner.trim_mempool(MempoolSizeLimit::default().with_max_size(max_size))
+        );
+    }
 
-    for block in miner.finalized.iter() {
-        for tx in block {
-            println!("{}", &tx.txid);
-        }
-        println!("\n\n",);
+    if command.as_deref() == Some("policy") {
+        print!(
+            "{}",
+            PolicyLimits::default().check(&MempoolGraph::new(miner.mempool()))
+        );
+
+        return Ok(());
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Default)]
-pub struct Miner {
-    mempool: Vec<Transaction>,
-    finalized_txids: HashSet<String>,
-    finalized: Vec<Vec<Transaction>>,
-}
+    if command.as_deref() == Some("estimate") {
+        let estimates = FeeEstimator::default()
+            .with_limits(*miner.limits())
+            .estimate(&MempoolGraph::new(miner.mempool()));
+        if json {
+            println!("{}", estimates.to_json());
+        } else {
+            print!("{estimates}");
+        }
 
-impl Miner {
-    pub fn load_mempool(path_to_file: impl AsRef<Path>) -> Self {
-        let file = File::open(path_to_file.as_ref()).unwrap();
-        let buffer = BufReader::new(file);
-        let mut init_miner = Miner::default();
+        return Ok(());
+    }
 
-        buffer.lines().for_each(|line| {
-            let line = line.unwrap();
-            let tx = Transaction::parser(line.trim());
+    if command.as_deref() == Some("simulate") {
+        let config = SimulationConfig::default().with_limits(*miner.limits());
+        print!(
+            "{}",
+            SimulationReport::run(miner.mempool(), selection_strategy.as_ref(), &config)
+        );
 
-            init_miner.mempool.push(tx);
-        });
+        return Ok(());
+    }
 
-        init_miner.mempool.sort();
+    if command.as_deref() == Some("graph") {
+        let graph = MempoolGraph::new(miner.mempool());
+        let visualization =
+            MempoolVisualization::new(&graph, &selection_strategy.project(&graph, *miner.limits()));
+        if json {
+            println!("{}", visualization.to_json());
+        } else {
+            print!("{}", visualization.to_dot());
+        }
 
-        init_miner
+        return Ok(());
     }
 
-    pub fn mine(&mut self) {
-        let mut current_block_weight = 0u32;
-        let mut current_block = Vec::<Transaction>::new();
-        let mut skipped = Vec::<Transaction>::new();
-
-        while let Some(mempool_tx) = self.mempool.pop() {
-            if current_block_weight + mempool_tx.weight > MAX_BLOCK_TX_WEIGHT
-                || self.mempool.is_empty()
-            {
-                for tx in current_block.iter() {
-                    self.finalized_txids.insert(tx.txid.clone());
-                }
-                self.finalized.push(current_block.clone());
-                current_block.clear();
-
-                current_block_weight = 0;
-
-                while let Some(skipped_tx) = skipped.pop() {
-                    self.mempool.push(skipped_tx);
-                    self.mempool.sort();
-                }
-            }
+    if command.as_deref() == Some("compare") {
+        print!(
+            "{}",
+            ComparisonReport::run(miner.mempool(), *miner.limits(), &strategies())
+        );
 
-            let has_no_parents = mempool_tx.parent_txids.is_empty();
-
-            if has_no_parents {
-                current_block_weight += mempool_tx.weight;
-                current_block.push(mempool_tx);
-            } else {
-                let mut all_parents_mined = Vec::<bool>::new();
-
-                for current_parent_txid in mempool_tx.parent_txids.iter() {
-                    let contains_tx = self.finalized_txids.contains(current_parent_txid);
-                    all_parents_mined.push(contains_tx);
-                }
-
-                let mut should_be_skipped = false;
-
-                all_parents_mined.iter().for_each(|element| {
-                    if !element {
-                        should_be_skipped = true;
-                    }
-                });
-
-                if !should_be_skipped {
-                    current_block_weight += mempool_tx.weight;
-                    current_block.push(mempool_tx);
-                } else {
-                    skipped.push(mempool_tx);
-                }
-            }
-        }
+        return Ok(());
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Default, Clone, Hash)]
-pub struct Transaction {
-    txid: String,
-    fee: u64,
-    weight: u32,
-    parent_txids: Vec<String>,
-}
+    if command.as_deref() == Some("solve") {
+        print!(
+            "{}",
+            SolverReport::run(
+                &MempoolGraph::new(miner.mempool()),
+                *miner.limits(),
+                SolverConfig::default()
+            )
+        );
+
+        return Ok(());
+    }
+
+    miner.mine_with(selection_strategy.as_ref());
 
-impl Transaction {
-    fn parser(value: &str) -> Self {
-        let mut outcome = Self::default();
-        let tx_data = value.split(',').collect::<Vec<&str>>();
-
-        let txid = tx_data.first().unwrap().trim();
-        let fee = tx_data.get(1).unwrap().trim();
-        let weight = tx_data.get(2).unwrap().trim();
-        let parents = tx_data.get(3);
-
-        outcome.txid = txid.trim().to_owned();
-        outcome.fee = fee.parse::<u64>().unwrap();
-        outcome.weight = weight.parse::<u32>().unwrap();
-
-        if let Some(parent_exists) = parents {
-            parent_exists.trim().split(';').for_each(|parent| {
-                if !parent.is_empty() {
-                    outcome.parent_txids.push(parent.trim().to_owned());
-                }
-            });
+    if command.as_deref() == Some("assemble") {
+        // Regtest blocks paying to an anyone-can-spend `OP_TRUE` script
+        for block in miner.assemble(&BlockAssembler::new(vec![0x51]))? {
+            println!("{}", block.block_hash());
+            println!("{}", serialize_hex(block.header()));
+            println!("{}", serialize_hex(block.coinbase()));
+            println!();
         }
 
-        // Reverse the parents order since ancestors of a transaction would need to be in
-        // the mempool for a UTXO to be valid
-        outcome.parent_txids.reverse();
+        return Ok(());
+    }
+
+    if format != "txids" {
+        // Regtest templates paying to an anyone-can-spend `OP_TRUE` script
+        let templates = miner.block_templates(&BlockAssembler::new(vec![0x51]))?;
+        if format == "gbt" {
+            println!("{}", serde_json::to_string_pretty(&templates)?);
+        } else {
+            print!("{}", templates_csv(&templates));
+        }
 
-        outcome
+        return Ok(());
     }
-}
 
-impl PartialOrd for Transaction {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    for block in miner.finalized().iter() {
+        for tx in block {
+            println!("{}", tx.txid());
+        }
+        println!("\n\n",);
     }
-}
 
-impl Ord for Transaction {
-    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
-        let self_fee_rate = self.fee / self.weight as u64;
-        let other_fee_rate = other.fee / other.weight as u64;
+    for unmineable in miner.unmineable() {
+        eprintln!("Unmineable: {}", unmineable);
+    }
 
-        other_fee_rate.cmp(&self_fee_rate)
+    for (index, limit) in miner.binding_limits().iter().enumerate() {
+        eprintln!("Block {} bound by {}", index + 1, limit);
     }
+
+    Ok(())
 }
@@ -0,0 +1,388 @@
+use crate::{
+    AncestorPackageStrategy, AssembledBlock, AssemblyError, BindingLimit, BlockAssembler,
+    BlockLimits, BlockTransaction, BranchAndBoundStrategy, ConflictResolution, Field,
+    GetBlockTemplate, GreedyStrategy, LineError, LineErrorKind, MempoolError, MempoolGraph,
+    MempoolSizeLimit, Projection, RawMempool, RawTransactions, ReplacementPolicy,
+    SelectionStrategy, SolverConfig, Transaction, TrimReport, Unmineable, UtxoSet,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{prelude::*, BufReader},
+    path::Path,
+};
+
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct Miner {
+    mempool: Vec<Transaction>,
+    finalized: Vec<Vec<Transaction>>,
+    binding_limits: Vec<BindingLimit>,
+    limits: BlockLimits,
+    unmineable: Vec<Unmineable>,
+    conflicts: ConflictResolution,
+}
+
+impl Miner {
+    /// Load a `txid,fee,weight,parents` mempool file failing on the first malformed line
+    pub fn load_mempool(path_to_file: impl AsRef<Path>) -> Result<Self, MempoolError> {
+        let (init_miner, _) = Self::load(path_to_file, false)?;
+
+        Ok(init_miner)
+    }
+
+    /// Load a mempool snapshot saved from Bitcoin Core's `getrawmempool true`
+    pub fn load_raw_mempool(path_to_file: impl AsRef<Path>) -> Result<Self, MempoolError> {
+        let raw_mempool = RawMempool::load(path_to_file)?;
+
+        Ok(Self::from_transactions(raw_mempool.transactions()))
+    }
+
+    /// Load hex encoded transactions from a file or a directory of files,
+    /// taking the values of the confirmed outputs they spend from `utxos`
+    pub fn load_raw_transactions(
+        path: impl AsRef<Path>,
+        utxos: &UtxoSet,
+    ) -> Result<Self, MempoolError> {
+        let transactions = RawTransactions::load(path)?.to_mempool(utxos)?;
+
+        Ok(Self::from_transactions(transactions))
+    }
+
+    /// Build a miner from transactions in the order they arrived,
+    /// resolving conflicting spends with the default replacement policy
+    pub fn from_transactions(transactions: Vec<Transaction>) -> Self {
+        let mut init_miner = Miner {
+            mempool: transactions,
+            ..Default::default()
+        };
+        init_miner.resolve_conflicts(ReplacementPolicy::default());
+
+        init_miner
+    }
+
+    /// Load a mempool file skipping malformed lines, duplicate txids
+    /// and zero weight records, returning them alongside the miner.
+    /// Only failing to read the file is an error.
+    pub fn load_mempool_lenient(
+        path_to_file: impl AsRef<Path>,
+    ) -> Result<(Self, Vec<LineError>), MempoolError> {
+        Self::load(path_to_file, true)
+    }
+
+    fn load(
+        path_to_file: impl AsRef<Path>,
+        lenient: bool,
+    ) -> Result<(Self, Vec<LineError>), MempoolError> {
+        let file = File::open(path_to_file.as_ref())?;
+        let buffer = BufReader::new(file);
+        let mut init_miner = Miner::default();
+        let mut skipped = Vec::<LineError>::new();
+        let mut first_seen = HashMap::<String, usize>::new();
+
+        for (index, line) in buffer.lines().enumerate() {
+            let line = line?;
+            let line_number = index + 1;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let parsed = Transaction::parser(line.trim(), line_number).and_then(|tx| {
+                match first_seen.get(tx.txid()) {
+                    Some(&first_line) => Err(LineError::new(
+                        line_number,
+                        Field::Txid,
+                        LineErrorKind::DuplicateTxid {
+                            txid: tx.txid().to_owned(),
+                            first_line,
+                        },
+                    )),
+                    None => Ok(tx),
+                }
+            });
+
+            match parsed {
+                Ok(tx) => {
+                    first_seen.insert(tx.txid().to_owned(), line_number);
+                    init_miner.mempool.push(tx);
+                }
+                Err(error) if lenient => skipped.push(error),
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        init_miner.resolve_conflicts(ReplacementPolicy::default());
+
+        Ok((init_miner, skipped))
+    }
+
+    // Keep the transactions surviving the replacement rules, in file order
+    // so that a later conflicting transaction is the replacement
+    fn resolve_conflicts(&mut self, policy: ReplacementPolicy) {
+        self.conflicts = policy.resolve(std::mem::take(&mut self.mempool));
+        self.mempool = self.conflicts.transactions().to_vec();
+        self.mempool.sort();
+    }
+
+    /// Evict the lowest descendant fee rate packages until the mempool
+    /// fits `limit`
+    pub fn trim_mempool(&mut self, limit: MempoolSizeLimit) -> TrimReport {
+        let (mempool, report) = limit.trim(std::mem::take(&mut self.mempool));
+        self.mempool = mempool;
+        self.mempool.sort();
+
+        report
+    }
+
+    /// Mine against custom block limits instead of the defaults
+    pub fn with_limits(mut self, limits: BlockLimits) -> Self {
+        self.limits = limits;
+
+        self
+    }
+
+    pub fn limits(&self) -> &BlockLimits {
+        &self.limits
+    }
+
+    pub fn mempool(&self) -> &[Transaction] {
+        self.mempool.as_slice()
+    }
+
+    /// Replacements, rejected replacements and dropped descendants
+    /// found while loading the mempool
+    pub fn conflicts(&self) -> &ConflictResolution {
+        &self.conflicts
+    }
+
+    pub fn finalized(&self) -> &[Vec<Transaction>] {
+        self.finalized.as_slice()
+    }
+
+    /// The limit that kept more transactions out of each finalized block
+    pub fn binding_limits(&self) -> &[BindingLimit] {
+        self.binding_limits.as_slice()
+    }
+
+    /// Transactions left in the mempool by the last call to `mine`
+    /// together with the reason they can never be mined
+    pub fn unmineable(&self) -> &[Unmineable] {
+        self.unmineable.as_slice()
+    }
+
+    /// Mine the mempool into blocks by ancestor fee rate so that
+    /// children can pay for their parents within the same block, keeping
+    /// the scores in an `IndexedMempool` updated as packages are mined.
+    /// Unmineable transactions are left in the mempool.
+    pub fn mine(&mut self) {
+        self.mine_with(&AncestorPackageStrategy);
+    }
+
+    /// Mine the mempool into blocks by each transaction's own fee rate,
+    /// adding children once their parents are mined.
+    /// Unmineable transactions are left in the mempool.
+    pub fn mine_greedy(&mut self) {
+        self.mine_with(&GreedyStrategy);
+    }
+
+    /// Mine the mempool by ancestor fee rate re-packing the end of each
+    /// block with the branch-and-bound solver for a higher total fee.
+    /// Unmineable transactions are left in the mempool.
+    pub fn mine_branch_and_bound(&mut self, config: SolverConfig) {
+        self.mine_with(&BranchAndBoundStrategy::new(config));
+    }
+
+    /// Mine the mempool into blocks with any selection strategy.
+    /// Unmineable transactions are left in the mempool.
+    pub fn mine_with(&mut self, strategy: &dyn SelectionStrategy) {
+        let projection = strategy.project(&MempoolGraph::new(&self.mempool), self.limits);
+
+        self.finalize(projection);
+    }
+
+    /// Assemble the mined blocks on top of each other starting from the
+    /// height and previous block hash of `assembler`, grinding each header
+    /// for at most the assembler's attempts. The coinbase reserves the
+    /// space of the miner's block limits.
+    pub fn assemble(
+        &self,
+        assembler: &BlockAssembler,
+    ) -> Result<Vec<AssembledBlock>, AssemblyError> {
+        let mut assembler = assembler.clone().with_limits(self.limits);
+        let mut blocks = Vec::<AssembledBlock>::with_capacity(self.finalized.len());
+
+        for finalized in self.finalized.iter() {
+            let transactions = finalized
+                .iter()
+                .map(BlockTransaction::from_transaction)
+                .collect::<Result<Vec<BlockTransaction>, AssemblyError>>()?;
+
+            let block = assembler.mine(transactions)?;
+            let height = assembler.height() + 1;
+            assembler = assembler
+                .with_prev_blockhash(block.block_hash())
+                .with_height(height);
+            blocks.push(block);
+        }
+
+        Ok(blocks)
+    }
+
+    /// Describe the mined blocks as `getblocktemplate` results, chained
+    /// the way `assemble` chains them
+    pub fn block_templates(
+        &self,
+        assembler: &BlockAssembler,
+    ) -> Result<Vec<GetBlockTemplate>, AssemblyError> {
+        let blocks = self.assemble(assembler)?;
+
+        Ok(self
+            .finalized
+            .iter()
+            .zip(blocks.iter())
+            .enumerate()
+            .map(|(index, (transactions, block))| {
+                GetBlockTemplate::new(
+                    transactions,
+                    block,
+                    assembler.height() + index as u32,
+                    &self.limits,
+                )
+            })
+            .collect())
+    }
+
+    fn finalize(&mut self, projection: Projection) {
+        let mut mempool = self.mempool.drain(..).map(Some).collect::<Vec<_>>();
+
+        for template in projection.blocks() {
+            let block = template
+                .positions()
+                .iter()
+                .filter_map(|&position| mempool[position].take())
+                .collect::<Vec<Transaction>>();
+
+            self.finalized.push(block);
+            self.binding_limits.push(template.binding_limit());
+        }
+
+        self.mempool = mempool.into_iter().flatten().collect();
+        // Positions refer to the mempool as it was before mining
+        self.unmineable = projection.unmineable().to_vec();
+    }
+}
+
+#[cfg(test)]
+mod mempool_loading {
+    use crate::{Field, LineErrorKind, MempoolError, Miner};
+    use std::{fs, path::PathBuf};
+
+    const FIRST: &str = "2e3da8fbc1eaca8ed9b7c2db9e6545d8ccac3c67deadee95db050e41c1eedfc0";
+    const SECOND: &str = "b0ef627c8dc2a706475d33d7712209ec779f7a8302aaeab86c64cf00316a3df8";
+
+    fn write_mempool(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "block-constructor-{}-{}.csv",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, contents).unwrap();
+
+        path
+    }
+
+    fn contents() -> String {
+        [
+            format!("{FIRST},452,1620,"),
+            format!("{SECOND},abc,900,"),
+            String::new(),
+            format!("{FIRST},100,400,"),
+            format!("{SECOND},226,0,"),
+            format!("{SECOND},226,900,{FIRST}"),
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn strict_loading_stops_at_the_first_bad_line() {
+        let path = write_mempool("strict", &contents());
+        let outcome = Miner::load_mempool(&path);
+        fs::remove_file(&path).unwrap();
+
+        match outcome {
+            Err(MempoolError::Line(error)) => {
+                assert_eq!(error.line(), 2);
+                assert_eq!(error.field(), Field::Fee);
+            }
+            _ => panic!("expected a line error"),
+        }
+
+        assert!(matches!(
+            Miner::load_mempool("does-not-exist.csv"),
+            Err(MempoolError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn lenient_loading_skips_and_reports_bad_lines() {
+        let path = write_mempool("lenient", &contents());
+        let (miner, skipped) = Miner::load_mempool_lenient(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let txids = miner
+            .mempool()
+            .iter()
+            .map(|tx| tx.txid())
+            .collect::<Vec<&str>>();
+        assert_eq!(txids.len(), 2);
+        assert!(txids.contains(&FIRST) && txids.contains(&SECOND));
+
+        let lines = skipped
+            .iter()
+            .map(|error| (error.line(), error.field()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![(2, Field::Fee), (4, Field::Txid), (5, Field::Weight)]
+        );
+        assert_eq!(
+            skipped[1].kind(),
+            &LineErrorKind::DuplicateTxid {
+                txid: FIRST.to_owned(),
+                first_line: 1,
+            }
+        );
+        assert_eq!(skipped[2].kind(), &LineErrorKind::ZeroWeight);
+    }
+
+    #[test]
+    fn bundled_mempool_is_well_formed() {
+        let (miner, skipped) = Miner::load_mempool_lenient("mempool.csv").unwrap();
+
+        assert!(skipped.is_empty());
+        assert_eq!(miner.mempool().len(), 5214);
+        assert!(miner.conflicts().replacements().is_empty());
+    }
+
+    #[test]
+    fn later_conflicting_lines_replace_earlier_ones() {
+        const COIN: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+        let path = write_mempool(
+            "replacement",
+            &[
+                format!("{FIRST},452,1620,,0,{COIN}:0"),
+                format!("{SECOND},2000,1620,,0,{COIN}:0"),
+            ]
+            .join("\n"),
+        );
+        let miner = Miner::load_mempool(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(miner.mempool().len(), 1);
+        assert_eq!(miner.mempool()[0].txid(), SECOND);
+        assert_eq!(
+            miner.conflicts().replacements()[0].replaced_txids(),
+            &[FIRST.to_owned()]
+        );
+    }
+}
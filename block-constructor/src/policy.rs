@@ -0,0 +1,489 @@
+use crate::{MempoolGraph, WITNESS_SCALE_FACTOR};
+use core::fmt;
+use std::collections::{BTreeSet, BinaryHeap};
+
+/// Bitcoin Core's `-limitancestorcount`, counting the transaction itself
+pub const DEFAULT_ANCESTOR_LIMIT: usize = 25;
+/// Bitcoin Core's `-limitancestorsize` of 101 kvB, in virtual bytes
+pub const DEFAULT_ANCESTOR_SIZE_LIMIT: u64 = 101_000;
+/// Bitcoin Core's `-limitdescendantcount`, counting the transaction itself
+pub const DEFAULT_DESCENDANT_LIMIT: usize = 25;
+/// Bitcoin Core's `-limitdescendantsize` of 101 kvB, in virtual bytes
+pub const DEFAULT_DESCENDANT_SIZE_LIMIT: u64 = 101_000;
+/// Bitcoin Core's `-bytespersigop`: the virtual size is
+/// `max(weight, sigop_cost * bytes_per_sigop) / 4`
+pub const DEFAULT_BYTES_PER_SIGOP: u64 = 20;
+
+/// Virtual size of a transaction whose weight is raised to
+/// `sigop_cost * bytes_per_sigop` when its sigops cost more than its bytes
+pub fn virtual_size(weight: u64, sigop_cost: u64, bytes_per_sigop: u64) -> u64 {
+    weight
+        .max(sigop_cost * bytes_per_sigop)
+        .div_ceil(WITNESS_SCALE_FACTOR)
+}
+
+/// Package limits a mempool applies before accepting a transaction
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PolicyLimits {
+    ancestor_count: usize,
+    ancestor_size: u64,
+    descendant_count: usize,
+    descendant_size: u64,
+    bytes_per_sigop: u64,
+}
+
+impl Default for PolicyLimits {
+    fn default() -> Self {
+        Self {
+            ancestor_count: DEFAULT_ANCESTOR_LIMIT,
+            ancestor_size: DEFAULT_ANCESTOR_SIZE_LIMIT,
+            descendant_count: DEFAULT_DESCENDANT_LIMIT,
+            descendant_size: DEFAULT_DESCENDANT_SIZE_LIMIT,
+            bytes_per_sigop: DEFAULT_BYTES_PER_SIGOP,
+        }
+    }
+}
+
+impl PolicyLimits {
+    pub fn with_ancestor_count(mut self, ancestor_count: usize) -> Self {
+        self.ancestor_count = ancestor_count;
+
+        self
+    }
+
+    /// Limit in virtual bytes
+    pub fn with_ancestor_size(mut self, ancestor_size: u64) -> Self {
+        self.ancestor_size = ancestor_size;
+
+        self
+    }
+
+    pub fn with_descendant_count(mut self, descendant_count: usize) -> Self {
+        self.descendant_count = descendant_count;
+
+        self
+    }
+
+    /// Limit in virtual bytes
+    pub fn with_descendant_size(mut self, descendant_size: u64) -> Self {
+        self.descendant_size = descendant_size;
+
+        self
+    }
+
+    /// Weight charged for each unit of sigop cost, zero to ignore sigops
+    pub fn with_bytes_per_sigop(mut self, bytes_per_sigop: u64) -> Self {
+        self.bytes_per_sigop = bytes_per_sigop;
+
+        self
+    }
+
+    pub fn ancestor_count(&self) -> usize {
+        self.ancestor_count
+    }
+
+    pub fn ancestor_size(&self) -> u64 {
+        self.ancestor_size
+    }
+
+    pub fn descendant_count(&self) -> usize {
+        self.descendant_count
+    }
+
+    pub fn descendant_size(&self) -> u64 {
+        self.descendant_size
+    }
+
+    pub fn bytes_per_sigop(&self) -> u64 {
+        self.bytes_per_sigop
+    }
+
+    /// Replay the mempool as if every transaction arrived after its parents,
+    /// in mempool order otherwise, rejecting the ones that break a limit and
+    /// evicting the descendants of rejected transactions.
+    /// Parents missing from the mempool are taken to be confirmed.
+    pub fn check(&self, graph: &MempoolGraph) -> PolicyReport {
+        let vsize = (0..graph.len())
+            .map(|position| {
+                let tx = graph.transaction(position);
+                virtual_size(tx.weight() as u64, tx.sigop_cost(), self.bytes_per_sigop)
+            })
+            .collect::<Vec<u64>>();
+
+        let mut report = PolicyReport::default();
+        let mut accepted = vec![false; graph.len()];
+        // Descendant count and size of accepted transactions, including themselves
+        let mut descendant_count = vec![0usize; graph.len()];
+        let mut descendant_size = vec![0u64; graph.len()];
+
+        let mut pending = (0..graph.len())
+            .map(|position| graph.parents(position).len())
+            .collect::<Vec<usize>>();
+        let mut ready = (0..graph.len())
+            .filter(|&position| pending[position] == 0)
+            .map(std::cmp::Reverse)
+            .collect::<BinaryHeap<_>>();
+        let mut reached = vec![false; graph.len()];
+
+        while let Some(std::cmp::Reverse(position)) = ready.pop() {
+            reached[position] = true;
+            for &child in graph.children(position) {
+                pending[child] -= 1;
+                if pending[child] == 0 {
+                    ready.push(std::cmp::Reverse(child));
+                }
+            }
+
+            let ancestors = graph.ancestors(position);
+            let violation = match ancestors.iter().find(|&&ancestor| !accepted[ancestor]) {
+                Some(&ancestor) => Some(PolicyViolation::RejectedAncestor {
+                    ancestor_txid: graph.transaction(ancestor).txid().to_owned(),
+                }),
+                None => self.violation(graph, position, &ancestors, &vsize, |ancestor| {
+                    (descendant_count[ancestor], descendant_size[ancestor])
+                }),
+            };
+
+            let txid = graph.transaction(position).txid().to_owned();
+            match violation {
+                Some(violation @ PolicyViolation::RejectedAncestor { .. }) => {
+                    report.evicted.push(PolicyRejection {
+                        position,
+                        txid,
+                        violation,
+                    })
+                }
+                Some(violation) => report.rejected.push(PolicyRejection {
+                    position,
+                    txid,
+                    violation,
+                }),
+                None => {
+                    accepted[position] = true;
+                    report.accepted += 1;
+                    descendant_count[position] = 1;
+                    descendant_size[position] = vsize[position];
+
+                    for &ancestor in ancestors.iter() {
+                        descendant_count[ancestor] += 1;
+                        descendant_size[ancestor] += vsize[position];
+                    }
+                }
+            }
+        }
+
+        // Transactions in a dependency cycle never have all their parents arrive
+        for position in (0..graph.len()).filter(|&position| !reached[position]) {
+            report.rejected.push(PolicyRejection {
+                position,
+                txid: graph.transaction(position).txid().to_owned(),
+                violation: PolicyViolation::DependencyCycle,
+            });
+        }
+
+        report
+    }
+
+    // The first limit accepting the transaction on top of its accepted ancestors would break
+    fn violation(
+        &self,
+        graph: &MempoolGraph,
+        position: usize,
+        ancestors: &BTreeSet<usize>,
+        vsize: &[u64],
+        descendants_of: impl Fn(usize) -> (usize, u64),
+    ) -> Option<PolicyViolation> {
+        let count = ancestors.len() + 1;
+        if count > self.ancestor_count {
+            return Some(PolicyViolation::TooManyAncestors {
+                count,
+                limit: self.ancestor_count,
+            });
+        }
+
+        let size = vsize[position]
+            + ancestors
+                .iter()
+                .map(|&ancestor| vsize[ancestor])
+                .sum::<u64>();
+        if size > self.ancestor_size {
+            return Some(PolicyViolation::AncestorSizeTooLarge {
+                vsize: size,
+                limit: self.ancestor_size,
+            });
+        }
+
+        for &ancestor in ancestors.iter() {
+            let (count, size) = descendants_of(ancestor);
+            let ancestor_txid = graph.transaction(ancestor).txid().to_owned();
+
+            if count + 1 > self.descendant_count {
+                return Some(PolicyViolation::TooManyDescendants {
+                    ancestor_txid,
+                    count: count + 1,
+                    limit: self.descendant_count,
+                });
+            }
+            if size + vsize[position] > self.descendant_size {
+                return Some(PolicyViolation::DescendantSizeTooLarge {
+                    ancestor_txid,
+                    vsize: size + vsize[position],
+                    limit: self.descendant_size,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// Outcome of replaying a mempool against the policy limits
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PolicyReport {
+    accepted: usize,
+    rejected: Vec<PolicyRejection>,
+    evicted: Vec<PolicyRejection>,
+}
+
+impl PolicyReport {
+    /// Number of transactions accepted
+    pub fn accepted(&self) -> usize {
+        self.accepted
+    }
+
+    /// Transactions breaking a limit themselves
+    pub fn rejected(&self) -> &[PolicyRejection] {
+        self.rejected.as_slice()
+    }
+
+    /// Transactions that cannot stay in the mempool because an ancestor was rejected
+    pub fn evicted(&self) -> &[PolicyRejection] {
+        self.evicted.as_slice()
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.rejected.is_empty() && self.evicted.is_empty()
+    }
+}
+
+impl fmt::Display for PolicyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} accepted, {} rejected, {} evicted",
+            self.accepted,
+            self.rejected.len(),
+            self.evicted.len()
+        )?;
+
+        for rejection in self.rejected.iter() {
+            writeln!(f, "rejected {rejection}")?;
+        }
+        for eviction in self.evicted.iter() {
+            writeln!(f, "evicted {eviction}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A transaction refused by the policy
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PolicyRejection {
+    position: usize,
+    txid: String,
+    violation: PolicyViolation,
+}
+
+impl PolicyRejection {
+    /// Position of the transaction in the mempool
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn violation(&self) -> &PolicyViolation {
+        &self.violation
+    }
+}
+
+impl fmt::Display for PolicyRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.txid, self.violation)
+    }
+}
+
+/// The policy limit a transaction breaks
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PolicyViolation {
+    /// The transaction and its ancestors are more than allowed
+    TooManyAncestors { count: usize, limit: usize },
+    /// The transaction and its ancestors are larger than allowed
+    AncestorSizeTooLarge { vsize: u64, limit: u64 },
+    /// An ancestor would have more descendants than allowed
+    TooManyDescendants {
+        ancestor_txid: String,
+        count: usize,
+        limit: usize,
+    },
+    /// The descendants of an ancestor would be larger than allowed
+    DescendantSizeTooLarge {
+        ancestor_txid: String,
+        vsize: u64,
+        limit: u64,
+    },
+    /// An ancestor was rejected so the transaction spends missing inputs
+    RejectedAncestor { ancestor_txid: String },
+    /// The transaction is its own ancestor
+    DependencyCycle,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyAncestors { count, limit } => {
+                write!(f, "has {count} ancestors including itself, the limit is {limit}")
+            }
+            Self::AncestorSizeTooLarge { vsize, limit } => write!(
+                f,
+                "has {vsize} vB of ancestors including itself, the limit is {limit}"
+            ),
+            Self::TooManyDescendants {
+                ancestor_txid,
+                count,
+                limit,
+            } => write!(
+                f,
+                "would give {ancestor_txid} {count} descendants including itself, the limit is {limit}"
+            ),
+            Self::DescendantSizeTooLarge {
+                ancestor_txid,
+                vsize,
+                limit,
+            } => write!(
+                f,
+                "would give {ancestor_txid} {vsize} vB of descendants including itself, the limit is {limit}"
+            ),
+            Self::RejectedAncestor { ancestor_txid } => {
+                write!(f, "descends from rejected {ancestor_txid}")
+            }
+            Self::DependencyCycle => write!(f, "depends on itself"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod policy_limits {
+    use crate::{virtual_size, MempoolGraph, Miner, PolicyLimits, PolicyViolation, Transaction};
+
+    fn chain(length: usize) -> Vec<Transaction> {
+        let txids = (0..length)
+            .map(|index| format!("tx-{index}"))
+            .collect::<Vec<_>>();
+
+        (0..length)
+            .map(|index| match index {
+                0 => Transaction::new(&txids[0], 1000, 800, &[]),
+                _ => Transaction::new(&txids[index], 1000, 800, &[&txids[index - 1]]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sigops_raise_the_virtual_size() {
+        assert_eq!(virtual_size(801, 0, 20), 201);
+        assert_eq!(virtual_size(800, 80, 20), 400);
+        assert_eq!(virtual_size(800, 80, 0), 200);
+    }
+
+    #[test]
+    fn long_chains_are_cut_at_the_ancestor_limit() {
+        let mempool = chain(30);
+        let graph = MempoolGraph::new(&mempool);
+        let report = PolicyLimits::default().check(&graph);
+
+        assert_eq!(report.accepted(), 25);
+        assert_eq!(report.rejected().len(), 1);
+        assert_eq!(report.rejected()[0].txid(), "tx-25");
+        assert_eq!(
+            report.rejected()[0].violation(),
+            &PolicyViolation::TooManyAncestors {
+                count: 26,
+                limit: 25
+            }
+        );
+        assert_eq!(
+            report
+                .evicted()
+                .iter()
+                .map(|eviction| eviction.txid())
+                .collect::<Vec<_>>(),
+            vec!["tx-26", "tx-27", "tx-28", "tx-29"]
+        );
+        assert_eq!(
+            report.evicted()[0].violation(),
+            &PolicyViolation::RejectedAncestor {
+                ancestor_txid: "tx-25".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn wide_fan_outs_hit_the_descendant_limit() {
+        let mut mempool = vec![Transaction::new("parent", 1000, 800, &[])];
+        for index in 0..25 {
+            mempool.push(Transaction::new(
+                &format!("child-{index:02}"),
+                1000,
+                800,
+                &["parent"],
+            ));
+        }
+        let graph = MempoolGraph::new(&mempool);
+        let report = PolicyLimits::default().check(&graph);
+
+        assert_eq!(report.accepted(), 25);
+        assert_eq!(report.rejected()[0].txid(), "child-24");
+        assert_eq!(
+            report.rejected()[0].violation(),
+            &PolicyViolation::TooManyDescendants {
+                ancestor_txid: "parent".to_owned(),
+                count: 26,
+                limit: 25
+            }
+        );
+    }
+
+    #[test]
+    fn package_sizes_include_sigops() {
+        let mempool = vec![
+            Transaction::new("parent", 1000, 400, &[]).with_sigop_cost(4000),
+            Transaction::new("child", 1000, 400, &["parent"]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        // max(400, 4000 sigop cost * 20) / 4 counts the parent as 20,000 vB
+        let limits = PolicyLimits::default().with_ancestor_size(20_000);
+        let report = limits.check(&graph);
+        assert_eq!(
+            report.rejected()[0].violation(),
+            &PolicyViolation::AncestorSizeTooLarge {
+                vsize: 20_100,
+                limit: 20_000
+            }
+        );
+        assert!(limits.with_bytes_per_sigop(0).check(&graph).is_compliant());
+    }
+
+    #[test]
+    fn bundled_mempool_follows_default_policy() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let report = PolicyLimits::default().check(&MempoolGraph::new(miner.mempool()));
+
+        assert!(report.is_compliant());
+        assert_eq!(report.accepted(), 5214);
+    }
+}
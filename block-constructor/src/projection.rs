@@ -0,0 +1,444 @@
+use crate::{
+    AncestorPackageSelector, BlockLimits, BlockTemplate, BranchAndBoundSelector, ChunkSelector,
+    GreedySelector, IndexedMempool, MempoolGraph, SolverConfig,
+};
+use core::fmt;
+
+/// The mempool projected into consecutive blocks.
+/// Every transaction of the mempool ends up either in exactly one
+/// projected block or in the list of unmineable transactions.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Projection {
+    blocks: Vec<BlockTemplate>,
+    unmineable: Vec<Unmineable>,
+}
+
+impl Projection {
+    /// Project the mempool by ancestor fee rate
+    pub fn ancestor_packages(graph: &MempoolGraph, limits: BlockLimits) -> Self {
+        let mut selector = AncestorPackageSelector::new(graph);
+
+        Self::project(graph, limits, |max_weight, max_sigop_cost| {
+            selector.next_block(max_weight, max_sigop_cost)
+        })
+    }
+
+    /// Project the mempool by ancestor fee rate with an `IndexedMempool`,
+    /// which only rescores the relatives of each mined transaction
+    pub fn indexed(graph: &MempoolGraph, limits: BlockLimits) -> Self {
+        let mut mempool = IndexedMempool::new();
+        for tx in graph.transactions() {
+            mempool.add(tx.clone());
+        }
+
+        Self::project(graph, limits, |max_weight, max_sigop_cost| {
+            mempool.next_block(max_weight, max_sigop_cost).map(|block| {
+                block
+                    .iter()
+                    .filter_map(|tx| graph.position(tx.txid()))
+                    .collect()
+            })
+        })
+    }
+
+    /// Project the mempool by each transaction's own fee rate
+    pub fn greedy(graph: &MempoolGraph, limits: BlockLimits) -> Self {
+        let mut selector = GreedySelector::new(graph);
+
+        Self::project(graph, limits, |max_weight, max_sigop_cost| {
+            selector.next_block(max_weight, max_sigop_cost)
+        })
+    }
+
+    /// Project the mempool by ancestor fee rate re-packing the end of
+    /// each block with the branch-and-bound solver
+    pub fn branch_and_bound(
+        graph: &MempoolGraph,
+        limits: BlockLimits,
+        config: SolverConfig,
+    ) -> Self {
+        let mut selector = BranchAndBoundSelector::new(graph, config);
+
+        Self::project(graph, limits, |max_weight, max_sigop_cost| {
+            selector.next_block(max_weight, max_sigop_cost)
+        })
+    }
+
+    /// Project the mempool by the fee rate of the chunks of each
+    /// linearized cluster
+    pub fn chunks(graph: &MempoolGraph, limits: BlockLimits) -> Self {
+        let mut selector = ChunkSelector::new(graph);
+
+        Self::project(graph, limits, |max_weight, max_sigop_cost| {
+            selector.next_block(max_weight, max_sigop_cost)
+        })
+    }
+
+    /// Build blocks with `next_block`, given the weight and sigop cost
+    /// available, until it returns `None` then explain why each
+    /// transaction that was never selected is unmineable. A transaction
+    /// selected again stays in the first block it was selected into, and
+    /// one `next_block` never selects is reported as `NotSelected`.
+    /// Projection also stops at a block with nothing new in it, since a
+    /// selector that makes no progress would otherwise never return `None`.
+    pub fn project(
+        graph: &MempoolGraph,
+        limits: BlockLimits,
+        mut next_block: impl FnMut(u64, u64) -> Option<Vec<usize>>,
+    ) -> Self {
+        let mut projection = Self::default();
+        let mut projected = vec![false; graph.len()];
+
+        while let Some(positions) = next_block(limits.max_tx_weight(), limits.max_tx_sigop_cost()) {
+            let positions = positions
+                .into_iter()
+                .filter(|&position| !std::mem::replace(&mut projected[position], true))
+                .collect::<Vec<usize>>();
+            if positions.is_empty() {
+                break;
+            }
+
+            projection
+                .blocks
+                .push(BlockTemplate::from_positions(graph, positions, &limits));
+        }
+
+        // Nothing mineable was left over for another block
+        if let Some(last) = projection.blocks.pop() {
+            projection.blocks.push(last.with_mempool_exhausted());
+        }
+
+        projection.unmineable = Self::explain_unmineable(graph, &projected, &limits);
+
+        projection
+    }
+
+    pub fn blocks(&self) -> &[BlockTemplate] {
+        self.blocks.as_slice()
+    }
+
+    pub fn unmineable(&self) -> &[Unmineable] {
+        self.unmineable.as_slice()
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.blocks.iter().map(|block| block.total_fee()).sum()
+    }
+
+    fn explain_unmineable(
+        graph: &MempoolGraph,
+        projected: &[bool],
+        limits: &BlockLimits,
+    ) -> Vec<Unmineable> {
+        let mut reasons = vec![None::<UnmineableReason>; graph.len()];
+
+        for position in (0..graph.len()).filter(|&position| !projected[position]) {
+            let tx = graph.transaction(position);
+
+            reasons[position] = if let Some(parent_txid) = graph.missing_parents(position).first() {
+                Some(UnmineableReason::MissingParent {
+                    parent_txid: parent_txid.to_string(),
+                })
+            } else if graph.ancestors(position).contains(&position) {
+                Some(UnmineableReason::DependencyCycle)
+            } else if tx.weight() as u64 > limits.max_tx_weight() {
+                Some(UnmineableReason::ExceedsBlockWeight {
+                    weight: tx.weight() as u64,
+                    max_weight: limits.max_tx_weight(),
+                })
+            } else if tx.sigop_cost() > limits.max_tx_sigop_cost() {
+                Some(UnmineableReason::ExceedsBlockSigopCost {
+                    sigop_cost: tx.sigop_cost(),
+                    max_sigop_cost: limits.max_tx_sigop_cost(),
+                })
+            } else {
+                None
+            };
+        }
+
+        let mut unmineable = Vec::<Unmineable>::new();
+
+        for position in (0..graph.len()).filter(|&position| !projected[position]) {
+            let reason = match reasons[position].clone() {
+                Some(reason) => reason,
+                None => {
+                    let ancestor = graph
+                        .ancestors(position)
+                        .into_iter()
+                        .find(|&ancestor| reasons[ancestor].is_some());
+
+                    match ancestor {
+                        Some(ancestor) => UnmineableReason::UnmineableAncestor {
+                            ancestor_txid: graph.transaction(ancestor).txid().to_owned(),
+                        },
+                        None => UnmineableReason::NotSelected,
+                    }
+                }
+            };
+
+            unmineable.push(Unmineable {
+                position,
+                txid: graph.transaction(position).txid().to_owned(),
+                reason,
+            });
+        }
+
+        unmineable
+    }
+}
+
+/// A transaction that cannot be included in any projected block
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Unmineable {
+    position: usize,
+    txid: String,
+    reason: UnmineableReason,
+}
+
+impl Unmineable {
+    /// Position of the transaction in the mempool
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn reason(&self) -> &UnmineableReason {
+        &self.reason
+    }
+}
+
+impl fmt::Display for Unmineable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.txid, self.reason)
+    }
+}
+
+/// Why a transaction cannot be mined
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum UnmineableReason {
+    /// A parent is neither in the mempool nor confirmed
+    MissingParent { parent_txid: String },
+    /// The transaction is its own ancestor
+    DependencyCycle,
+    /// The transaction alone is heavier than the weight available in a block
+    ExceedsBlockWeight { weight: u64, max_weight: u64 },
+    /// The transaction alone has more sigop cost than a block has available
+    ExceedsBlockSigopCost {
+        sigop_cost: u64,
+        max_sigop_cost: u64,
+    },
+    /// An ancestor can never be mined
+    UnmineableAncestor { ancestor_txid: String },
+    /// The transaction could be mined but the selector never picked it
+    NotSelected,
+}
+
+impl fmt::Display for UnmineableReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingParent { parent_txid } => {
+                write!(f, "spends {parent_txid} which is missing from the mempool")
+            }
+            Self::DependencyCycle => write!(f, "depends on itself"),
+            Self::ExceedsBlockWeight { weight, max_weight } => {
+                write!(
+                    f,
+                    "weighs {weight} which exceeds the block limit of {max_weight}"
+                )
+            }
+            Self::ExceedsBlockSigopCost {
+                sigop_cost,
+                max_sigop_cost,
+            } => {
+                write!(
+                    f,
+                    "has sigop cost {sigop_cost} which exceeds the block limit of {max_sigop_cost}"
+                )
+            }
+            Self::UnmineableAncestor { ancestor_txid } => {
+                write!(f, "descends from unmineable {ancestor_txid}")
+            }
+            Self::NotSelected => write!(f, "is mineable but was never selected"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod multi_block_projection {
+    use crate::{
+        BlockLimits, MempoolGraph, Miner, Projection, SolverConfig, Transaction, UnmineableReason,
+    };
+    use std::collections::HashMap;
+
+    // Each txid must appear exactly once across the blocks and the unmineable list
+    fn assert_conserved(mempool: &[Transaction], projection: &Projection) {
+        let mut seen = HashMap::<usize, usize>::new();
+
+        for block in projection.blocks() {
+            for &position in block.positions() {
+                *seen.entry(position).or_default() += 1;
+            }
+        }
+        for unmineable in projection.unmineable() {
+            *seen.entry(unmineable.position()).or_default() += 1;
+        }
+
+        assert_eq!(seen.len(), mempool.len());
+        assert!(seen.values().all(|&count| count == 1));
+    }
+
+    fn limits_for(tx_weight: u64) -> BlockLimits {
+        let reserved = BlockLimits::default()
+            .with_max_block_weight(tx_weight)
+            .reserved_weight();
+
+        BlockLimits::default().with_max_block_weight(tx_weight + reserved)
+    }
+
+    #[test]
+    fn the_last_transaction_is_not_lost() {
+        let mempool = vec![
+            Transaction::new("first", 3000, 1000, &[]),
+            Transaction::new("second", 2000, 1000, &[]),
+            Transaction::new("last", 1000, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        for projection in [
+            Projection::greedy(&graph, limits_for(2000)),
+            Projection::ancestor_packages(&graph, limits_for(2000)),
+        ] {
+            assert_conserved(&mempool, &projection);
+            assert_eq!(projection.blocks().len(), 2);
+            assert_eq!(projection.blocks()[1].positions(), &[2]);
+        }
+    }
+
+    #[test]
+    fn children_follow_parents_within_a_block() {
+        let mempool = vec![
+            Transaction::new("parent", 3000, 1000, &[]),
+            Transaction::new("child", 2000, 1000, &["parent"]),
+            Transaction::new("other", 100, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        let projection = Projection::greedy(&graph, limits_for(2000));
+        assert_conserved(&mempool, &projection);
+        assert_eq!(projection.blocks()[0].positions(), &[0, 1]);
+    }
+
+    #[test]
+    fn unmineable_transactions_are_reported() {
+        let mempool = vec![
+            Transaction::new("orphan", 3000, 1000, &["missing"]),
+            Transaction::new("orphan-child", 2000, 1000, &["orphan"]),
+            Transaction::new("heavy", 100_000, 5000, &[]),
+            Transaction::new("loop-a", 100, 1000, &["loop-b"]),
+            Transaction::new("loop-b", 100, 1000, &["loop-a"]),
+            Transaction::new("fine", 100, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        for projection in [
+            Projection::greedy(&graph, limits_for(2000)),
+            Projection::ancestor_packages(&graph, limits_for(2000)),
+        ] {
+            assert_conserved(&mempool, &projection);
+            assert_eq!(projection.blocks().len(), 1);
+
+            let reasons = projection
+                .unmineable()
+                .iter()
+                .map(|unmineable| (unmineable.txid(), unmineable.reason().clone()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                reasons,
+                vec![
+                    (
+                        "orphan",
+                        UnmineableReason::MissingParent {
+                            parent_txid: "missing".to_owned()
+                        }
+                    ),
+                    (
+                        "orphan-child",
+                        UnmineableReason::UnmineableAncestor {
+                            ancestor_txid: "orphan".to_owned()
+                        }
+                    ),
+                    (
+                        "heavy",
+                        UnmineableReason::ExceedsBlockWeight {
+                            weight: 5000,
+                            max_weight: 2000
+                        }
+                    ),
+                    ("loop-a", UnmineableReason::DependencyCycle),
+                    ("loop-b", UnmineableReason::DependencyCycle),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn selector_mistakes_do_not_lose_transactions() {
+        let mempool = vec![
+            Transaction::new("twice", 3000, 1000, &[]),
+            Transaction::new("never", 2000, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        // Selects the first transaction into two blocks and never the second
+        let mut blocks = vec![vec![0], vec![0]].into_iter();
+        let projection = Projection::project(&graph, limits_for(2000), |_, _| blocks.next());
+
+        assert_conserved(&mempool, &projection);
+        assert_eq!(projection.blocks().len(), 1);
+        assert_eq!(projection.blocks()[0].positions(), &[0]);
+        assert_eq!(projection.unmineable()[0].txid(), "never");
+        assert_eq!(
+            projection.unmineable()[0].reason(),
+            &UnmineableReason::NotSelected
+        );
+
+        // A selector repeating itself forever stops at its first repeat
+        let projection = Projection::project(&graph, limits_for(2000), |_, _| Some(vec![0]));
+        assert_conserved(&mempool, &projection);
+        assert_eq!(projection.blocks().len(), 1);
+    }
+
+    #[test]
+    fn bundled_mempool_is_conserved() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let graph = MempoolGraph::new(miner.mempool());
+
+        for projection in [
+            Projection::greedy(&graph, BlockLimits::default()),
+            Projection::ancestor_packages(&graph, BlockLimits::default()),
+            Projection::branch_and_bound(
+                &graph,
+                BlockLimits::default(),
+                SolverConfig::default().with_max_nodes(1_000),
+            ),
+        ] {
+            assert_conserved(miner.mempool(), &projection);
+            assert!(projection.unmineable().is_empty());
+        }
+
+        let mut greedy = Miner::load_mempool("mempool.csv").unwrap();
+        greedy.mine_greedy();
+        assert_eq!(
+            greedy
+                .finalized()
+                .iter()
+                .map(|block| block.len())
+                .sum::<usize>(),
+            5214
+        );
+        assert!(greedy.mempool().is_empty());
+    }
+}
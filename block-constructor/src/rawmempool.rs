@@ -0,0 +1,410 @@
+use crate::{
+    is_txid, EntryError, Field, LineErrorKind, MempoolError, MempoolGraph, Transaction,
+    WITNESS_SCALE_FACTOR,
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Number of satoshis in one BTC
+pub const SATS_PER_BTC: f64 = 100_000_000.0;
+
+/// A mempool snapshot saved from Bitcoin Core's verbose
+/// `getrawmempool true` RPC, a JSON object mapping each txid to its
+/// `getmempoolentry` result. `getmempoolentry` outputs can be combined
+/// into the same shape by keying each one by its txid.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RawMempool {
+    entries: Vec<MempoolEntry>,
+}
+
+impl RawMempool {
+    pub fn load(path_to_file: impl AsRef<Path>) -> Result<Self, MempoolError> {
+        let contents = fs::read_to_string(path_to_file.as_ref())?;
+
+        Self::from_json_str(&contents)
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self, MempoolError> {
+        let snapshot = serde_json::from_str::<BTreeMap<String, EntryJson>>(json)?;
+
+        let entries = snapshot
+            .into_iter()
+            .map(|(txid, entry)| entry.into_entry(txid))
+            .collect::<Result<Vec<MempoolEntry>, EntryError>>()?;
+
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[MempoolEntry] {
+        self.entries.as_slice()
+    }
+
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.entries
+            .iter()
+            .map(|entry| entry.transaction.clone())
+            .collect()
+    }
+
+    /// Entries whose reported `ancestorcount` does not match the ancestors
+    /// found in the snapshot, which happens when `depends` refers to
+    /// transactions that are missing from it
+    pub fn ancestor_mismatches(&self) -> Vec<AncestorMismatch> {
+        let transactions = self.transactions();
+        let graph = MempoolGraph::new(&transactions);
+
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(position, entry)| {
+                let reported = entry.ancestors?.count;
+                // Core counts the transaction itself as one of its ancestors
+                let found = graph.ancestors(position).len() as u64 + 1;
+
+                (reported != found).then(|| AncestorMismatch {
+                    txid: entry.transaction.txid().to_owned(),
+                    reported,
+                    found,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A transaction from the snapshot with the statistics Core reported for it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MempoolEntry {
+    transaction: Transaction,
+    vsize: u64,
+    modified_fee: Option<u64>,
+    spent_by: Vec<String>,
+    ancestors: Option<PackageStats>,
+    descendants: Option<PackageStats>,
+}
+
+impl MempoolEntry {
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn vsize(&self) -> u64 {
+        self.vsize
+    }
+
+    /// The fee after `prioritisetransaction` adjustments
+    pub fn modified_fee(&self) -> Option<u64> {
+        self.modified_fee
+    }
+
+    /// Txids of the in-mempool transactions spending this one
+    pub fn spent_by(&self) -> &[String] {
+        self.spent_by.as_slice()
+    }
+
+    /// The `ancestorcount`, `ancestorsize` and `fees.ancestor` reported by Core
+    pub fn ancestors(&self) -> Option<&PackageStats> {
+        self.ancestors.as_ref()
+    }
+
+    /// The `descendantcount`, `descendantsize` and `fees.descendant` reported by Core
+    pub fn descendants(&self) -> Option<&PackageStats> {
+        self.descendants.as_ref()
+    }
+}
+
+/// Size of a transaction together with its ancestors or descendants.
+/// The count and size include the transaction itself.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PackageStats {
+    count: u64,
+    vsize: u64,
+    fee: Option<u64>,
+}
+
+impl PackageStats {
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn vsize(&self) -> u64 {
+        self.vsize
+    }
+
+    pub fn fee(&self) -> Option<u64> {
+        self.fee
+    }
+}
+
+/// An entry whose reported ancestor count differs from the snapshot
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AncestorMismatch {
+    txid: String,
+    reported: u64,
+    found: u64,
+}
+
+impl AncestorMismatch {
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn reported(&self) -> u64 {
+        self.reported
+    }
+
+    pub fn found(&self) -> u64 {
+        self.found
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EntryJson {
+    vsize: Option<u64>,
+    weight: Option<u64>,
+    // Removed in Core 0.22 in favour of `fees.base`
+    fee: Option<f64>,
+    fees: Option<FeesJson>,
+    #[serde(default)]
+    depends: Vec<String>,
+    #[serde(default)]
+    spentby: Vec<String>,
+    ancestorcount: Option<u64>,
+    ancestorsize: Option<u64>,
+    descendantcount: Option<u64>,
+    descendantsize: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeesJson {
+    base: f64,
+    modified: Option<f64>,
+    ancestor: Option<f64>,
+    descendant: Option<f64>,
+}
+
+impl EntryJson {
+    fn into_entry(self, txid: String) -> Result<MempoolEntry, EntryError> {
+        let error = |field: Field, kind: LineErrorKind| EntryError::new(&txid, field, kind);
+
+        if !is_txid(&txid) {
+            return Err(error(
+                Field::Txid,
+                LineErrorKind::InvalidTxid {
+                    value: txid.clone(),
+                },
+            ));
+        }
+
+        for parent in self.depends.iter() {
+            if !is_txid(parent) {
+                return Err(error(
+                    Field::Parents,
+                    LineErrorKind::InvalidTxid {
+                        value: parent.clone(),
+                    },
+                ));
+            }
+        }
+
+        // Older versions of Core only report the virtual size
+        let weight = match (self.weight, self.vsize) {
+            (Some(weight), _) => weight,
+            (None, Some(vsize)) => vsize.checked_mul(WITNESS_SCALE_FACTOR).ok_or_else(|| {
+                error(
+                    Field::Weight,
+                    LineErrorKind::IntegerOutOfRange {
+                        value: vsize.to_string(),
+                    },
+                )
+            })?,
+            (None, None) => return Err(error(Field::Weight, LineErrorKind::Missing)),
+        };
+        if weight == 0 {
+            return Err(error(Field::Weight, LineErrorKind::ZeroWeight));
+        }
+        let weight = u32::try_from(weight).map_err(|_| {
+            error(
+                Field::Weight,
+                LineErrorKind::IntegerOutOfRange {
+                    value: weight.to_string(),
+                },
+            )
+        })?;
+
+        let amount = |btc: f64| to_sats(btc).ok_or_else(|| error(Field::Fee, invalid_amount(btc)));
+
+        let base_fee = match (&self.fees, self.fee) {
+            (Some(fees), _) => fees.base,
+            (None, Some(fee)) => fee,
+            (None, None) => return Err(error(Field::Fee, LineErrorKind::Missing)),
+        };
+        let fee = amount(base_fee)?;
+
+        let fees = self.fees.as_ref();
+        let modified_fee = fees
+            .and_then(|fees| fees.modified)
+            .map(amount)
+            .transpose()?;
+        let ancestor_fee = fees
+            .and_then(|fees| fees.ancestor)
+            .map(amount)
+            .transpose()?;
+        let descendant_fee = fees
+            .and_then(|fees| fees.descendant)
+            .map(amount)
+            .transpose()?;
+
+        let parents = self
+            .depends
+            .iter()
+            .map(|parent| parent.as_str())
+            .collect::<Vec<&str>>();
+
+        Ok(MempoolEntry {
+            transaction: Transaction::new(&txid, fee, weight, &parents),
+            vsize: self
+                .vsize
+                .unwrap_or((weight as u64).div_ceil(WITNESS_SCALE_FACTOR)),
+            modified_fee,
+            spent_by: self.spentby,
+            ancestors: self.ancestorcount.map(|count| PackageStats {
+                count,
+                vsize: self.ancestorsize.unwrap_or_default(),
+                fee: ancestor_fee,
+            }),
+            descendants: self.descendantcount.map(|count| PackageStats {
+                count,
+                vsize: self.descendantsize.unwrap_or_default(),
+                fee: descendant_fee,
+            }),
+        })
+    }
+}
+
+/// Convert a BTC amount as printed by Core (8 decimal places) to satoshis
+pub fn to_sats(btc: f64) -> Option<u64> {
+    let sats = (btc * SATS_PER_BTC).round();
+
+    (sats.is_finite() && sats >= 0.0 && sats <= u64::MAX as f64).then_some(sats as u64)
+}
+
+fn invalid_amount(btc: f64) -> LineErrorKind {
+    LineErrorKind::InvalidAmount {
+        value: btc.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod raw_mempool_import {
+    use crate::{Field, LineErrorKind, MempoolError, Miner, RawMempool};
+
+    const PARENT: &str = "2e3da8fbc1eaca8ed9b7c2db9e6545d8ccac3c67deadee95db050e41c1eedfc0";
+    const CHILD: &str = "79c51c9d4124c5cbb37a85263748dcf44e182dff83561fa3087f0e9e43f41c33";
+    const ABSENT: &str = "6eb38fad135e38a93cb47a15a5f953cbc0563fd84bf1abdec578c2af302e10bf";
+
+    fn snapshot() -> String {
+        format!(
+            r#"{{
+                "{PARENT}": {{
+                    "vsize": 141, "weight": 561, "time": 1707000000, "height": 830000,
+                    "descendantcount": 2, "descendantsize": 251,
+                    "ancestorcount": 1, "ancestorsize": 141,
+                    "wtxid": "{PARENT}",
+                    "fees": {{ "base": 0.00000141, "modified": 0.00000141,
+                               "ancestor": 0.00000141, "descendant": 0.00010141 }},
+                    "depends": [], "spentby": ["{CHILD}"],
+                    "bip125-replaceable": false, "unbroadcast": false
+                }},
+                "{CHILD}": {{
+                    "vsize": 110, "time": 1707000005, "height": 830000,
+                    "descendantcount": 1, "descendantsize": 110,
+                    "ancestorcount": 3, "ancestorsize": 251,
+                    "fees": {{ "base": 0.0001, "modified": 0.0001,
+                               "ancestor": 0.00010141, "descendant": 0.0001 }},
+                    "depends": ["{PARENT}", "{ABSENT}"], "spentby": []
+                }}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn imports_verbose_getrawmempool() {
+        let mempool = RawMempool::from_json_str(&snapshot()).unwrap();
+        let entries = mempool.entries();
+
+        assert_eq!(entries.len(), 2);
+
+        let parent = &entries[0];
+        assert_eq!(parent.transaction().txid(), PARENT);
+        assert_eq!(parent.transaction().fee(), 141);
+        assert_eq!(parent.transaction().weight(), 561);
+        assert_eq!(parent.spent_by(), &[CHILD.to_owned()]);
+        assert_eq!(parent.descendants().unwrap().count(), 2);
+        assert_eq!(parent.descendants().unwrap().fee(), Some(10_141));
+
+        // No weight reported so it falls back to the virtual size
+        let child = &entries[1];
+        assert_eq!(child.transaction().fee(), 10_000);
+        assert_eq!(child.transaction().weight(), 440);
+        assert_eq!(child.transaction().parent_txids().len(), 2);
+
+        let mismatches = mempool.ancestor_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].txid(), CHILD);
+        assert_eq!((mismatches[0].reported(), mismatches[0].found()), (3, 2));
+
+        // The child depends on a transaction missing from the snapshot
+        let mut miner = Miner::from_transactions(mempool.transactions());
+        miner.mine();
+        assert_eq!(miner.finalized().len(), 1);
+        assert_eq!(miner.finalized()[0][0].txid(), PARENT);
+        assert_eq!(miner.mempool()[0].txid(), CHILD);
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        let missing_fee = format!(r#"{{ "{PARENT}": {{ "vsize": 141 }} }}"#);
+        match RawMempool::from_json_str(&missing_fee) {
+            Err(MempoolError::Entry(error)) => {
+                assert_eq!(error.txid(), PARENT);
+                assert_eq!(error.field(), Field::Fee);
+                assert_eq!(error.kind(), &LineErrorKind::Missing);
+            }
+            _ => panic!("expected an entry error"),
+        }
+
+        let negative_fee = format!(r#"{{ "{PARENT}": {{ "weight": 400, "fee": -0.1 }} }}"#);
+        assert!(matches!(
+            RawMempool::from_json_str(&negative_fee),
+            Err(MempoolError::Entry(_))
+        ));
+
+        let heavy = format!(r#"{{ "{PARENT}": {{ "weight": 4294967296, "fee": 0.1 }} }}"#);
+        // A virtual size whose weight overflows a u64
+        let huge_vsize =
+            format!(r#"{{ "{PARENT}": {{ "vsize": 18446744073709551615, "fee": 0.1 }} }}"#);
+        for json in [heavy, huge_vsize] {
+            match RawMempool::from_json_str(&json) {
+                Err(MempoolError::Entry(error)) => {
+                    assert_eq!(error.field(), Field::Weight);
+                    assert!(matches!(
+                        error.kind(),
+                        LineErrorKind::IntegerOutOfRange { .. }
+                    ));
+                }
+                _ => panic!("expected an entry error"),
+            }
+        }
+
+        let bad_txid = r#"{ "abcd": { "weight": 400, "fees": { "base": 0.1 } } }"#;
+        assert!(matches!(
+            RawMempool::from_json_str(bad_txid),
+            Err(MempoolError::Entry(_))
+        ));
+
+        assert!(matches!(
+            RawMempool::from_json_str("[1, 2]"),
+            Err(MempoolError::Json(_))
+        ));
+    }
+}
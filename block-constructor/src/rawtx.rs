@@ -0,0 +1,447 @@
+use crate::{
+    Field, LineError, LineErrorKind, MempoolError, OutPoint, RawTransactionError, Transaction,
+};
+use bitcoin::{
+    consensus::encode::{deserialize, serialize},
+    Amount, ScriptBuf, TxOut,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+/// Values and scripts of the confirmed outputs the mempool spends, read
+/// from `txid:vout,value` lines with the value in sats, optionally
+/// followed by a `,script_pubkey` field in hex. The script is only needed
+/// to count the sigops of P2SH and segwit inputs.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct UtxoSet {
+    outputs: HashMap<OutPoint, TxOut>,
+}
+
+impl UtxoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path_to_file: impl AsRef<Path>) -> Result<Self, MempoolError> {
+        let contents = fs::read_to_string(path_to_file.as_ref())?;
+
+        Ok(Self::parse(&contents)?)
+    }
+
+    /// Parse `txid:vout,value[,script_pubkey]` lines skipping blank ones
+    pub fn parse(contents: &str) -> Result<Self, LineError> {
+        let mut utxos = Self::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut fields = line.trim().split(',');
+            let outpoint = Transaction::parse_outpoint(
+                fields.next().unwrap_or_default().trim(),
+                line_number,
+                Field::Inputs,
+            )?;
+
+            let value = fields
+                .next()
+                .ok_or_else(|| LineError::new(line_number, Field::Value, LineErrorKind::Missing))?
+                .trim();
+            let value = value.parse::<u64>().map_err(|error| {
+                LineError::new(
+                    line_number,
+                    Field::Value,
+                    LineErrorKind::InvalidInteger {
+                        value: value.to_owned(),
+                        error,
+                    },
+                )
+            })?;
+
+            let script_pubkey = match fields.next().map(str::trim) {
+                Some(script) => hex::decode(script).map_err(|_| {
+                    LineError::new(
+                        line_number,
+                        Field::ScriptPubkey,
+                        LineErrorKind::InvalidHex {
+                            value: script.to_owned(),
+                        },
+                    )
+                })?,
+                None => Vec::new(),
+            };
+
+            utxos.insert(
+                outpoint,
+                TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey: ScriptBuf::from_bytes(script_pubkey),
+                },
+            );
+        }
+
+        Ok(utxos)
+    }
+
+    pub fn insert(&mut self, outpoint: OutPoint, output: TxOut) {
+        self.outputs.insert(outpoint, output);
+    }
+
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOut> {
+        self.outputs.get(outpoint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+/// Serialized transactions, one hex encoded transaction per line of a
+/// file or of every file in a directory. Unlike the CSV mempool the
+/// txid, weight, parents and sigop cost are derived from the transactions
+/// themselves and the fee from the values of the outputs they spend.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RawTransactions {
+    transactions: Vec<bitcoin::Transaction>,
+}
+
+impl RawTransactions {
+    pub fn new(transactions: Vec<bitcoin::Transaction>) -> Self {
+        Self { transactions }
+    }
+
+    /// Load a file of hex transactions, or every file in a directory in
+    /// the order of their names
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MempoolError> {
+        let path = path.as_ref();
+        let mut files = if path.is_dir() {
+            fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .filter(|file| file.is_file())
+                .collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.sort();
+
+        let mut transactions = Vec::<bitcoin::Transaction>::new();
+        for file in files {
+            let contents = fs::read_to_string(&file)?;
+
+            for (index, line) in contents.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let decode_error = |error: String| RawTransactionError::Decode {
+                    path: file.clone(),
+                    line: index + 1,
+                    error,
+                };
+                let raw =
+                    hex::decode(line.trim()).map_err(|error| decode_error(error.to_string()))?;
+                transactions
+                    .push(deserialize(&raw).map_err(|error| decode_error(error.to_string()))?);
+            }
+        }
+
+        Ok(Self { transactions })
+    }
+
+    pub fn transactions(&self) -> &[bitcoin::Transaction] {
+        self.transactions.as_slice()
+    }
+
+    /// Turn the transactions into mempool transactions in the order they
+    /// were loaded. Inputs spend the outputs of the other transactions,
+    /// which become their parents, or the outputs in `utxos`.
+    pub fn to_mempool(&self, utxos: &UtxoSet) -> Result<Vec<Transaction>, RawTransactionError> {
+        let mut created = HashMap::<OutPoint, TxOut>::new();
+        let mut txids = HashSet::<String>::new();
+
+        for tx in self.transactions.iter() {
+            let txid = tx.txid().to_string();
+
+            if tx.is_coinbase() {
+                return Err(RawTransactionError::Coinbase { txid });
+            }
+            if !txids.insert(txid.clone()) {
+                return Err(RawTransactionError::DuplicateTxid { txid });
+            }
+
+            for (vout, output) in tx.output.iter().enumerate() {
+                created.insert(OutPoint::new(&txid, vout as u32), output.clone());
+            }
+        }
+
+        let spent = |outpoint: &OutPoint| created.get(outpoint).or_else(|| utxos.get(outpoint));
+
+        self.transactions
+            .iter()
+            .map(|tx| {
+                let txid = tx.txid().to_string();
+                let inputs = tx
+                    .input
+                    .iter()
+                    .map(|input| {
+                        OutPoint::new(
+                            &input.previous_output.txid.to_string(),
+                            input.previous_output.vout,
+                        )
+                    })
+                    .collect::<Vec<OutPoint>>();
+
+                let mut input_value = 0u64;
+                let mut parents = Vec::<&str>::new();
+                for outpoint in inputs.iter() {
+                    let output =
+                        spent(outpoint).ok_or_else(|| RawTransactionError::MissingPrevout {
+                            txid: txid.clone(),
+                            outpoint: outpoint.clone(),
+                        })?;
+                    input_value = input_value
+                        .checked_add(output.value.to_sat())
+                        .ok_or_else(|| RawTransactionError::ValueOverflow { txid: txid.clone() })?;
+
+                    if txids.contains(outpoint.txid()) && !parents.contains(&outpoint.txid()) {
+                        parents.push(outpoint.txid());
+                    }
+                }
+
+                let output_value = tx
+                    .output
+                    .iter()
+                    .try_fold(0u64, |total, output| {
+                        total.checked_add(output.value.to_sat())
+                    })
+                    .ok_or_else(|| RawTransactionError::ValueOverflow { txid: txid.clone() })?;
+                let fee = input_value.checked_sub(output_value).ok_or_else(|| {
+                    RawTransactionError::NegativeFee {
+                        txid: txid.clone(),
+                        input_value,
+                        output_value,
+                    }
+                })?;
+
+                let sigop_cost = tx.total_sigop_cost(|outpoint| {
+                    spent(&OutPoint::new(&outpoint.txid.to_string(), outpoint.vout)).cloned()
+                });
+
+                let weight = u32::try_from(tx.weight().to_wu()).map_err(|_| {
+                    RawTransactionError::WeightOutOfRange {
+                        txid: txid.clone(),
+                        weight: tx.weight().to_wu(),
+                    }
+                })?;
+
+                Ok(Transaction::new(&txid, fee, weight, &parents)
+                    .with_sigop_cost(sigop_cost as u64)
+                    .with_inputs(inputs.clone())
+                    .with_raw(serialize(tx)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod raw_transaction_loading {
+    use crate::{BlockAssembler, Miner, OutPoint, RawTransactionError, RawTransactions, UtxoSet};
+    use bitcoin::{
+        absolute::LockTime, consensus::encode::serialize_hex, hashes::Hash, transaction::Version,
+        Amount, ScriptBuf, Sequence, TxIn, TxOut, Txid, Witness,
+    };
+    use std::fs;
+
+    const FUNDING: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    fn spend(previous: (Txid, u32), values: &[u64], witness: bool) -> bitcoin::Transaction {
+        bitcoin::Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: bitcoin::OutPoint::new(previous.0, previous.1),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: if witness {
+                    Witness::from_slice(&[vec![0x30; 72], vec![0x02; 33]])
+                } else {
+                    Witness::new()
+                },
+            }],
+            output: values
+                .iter()
+                .map(|&value| TxOut {
+                    value: Amount::from_sat(value),
+                    // `OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG`
+                    script_pubkey: ScriptBuf::from_bytes(
+                        [&[0x76, 0xa9, 0x14][..], &[0u8; 20], &[0x88, 0xac]].concat(),
+                    ),
+                })
+                .collect(),
+        }
+    }
+
+    // A parent spending a confirmed output and a segwit child spending the parent
+    fn family() -> (bitcoin::Transaction, bitcoin::Transaction, UtxoSet) {
+        let funding = FUNDING.parse::<Txid>().unwrap();
+        let parent = spend((funding, 0), &[60_000, 39_000], false);
+        let child = spend((parent.txid(), 0), &[58_500], true);
+        let utxos = UtxoSet::parse(&format!("{FUNDING}:0,100000\n")).unwrap();
+
+        (parent, child, utxos)
+    }
+
+    #[test]
+    fn fees_weights_and_parents_come_from_the_transactions() {
+        let (parent, child, utxos) = family();
+        let mempool = RawTransactions::new(vec![child.clone(), parent.clone()])
+            .to_mempool(&utxos)
+            .unwrap();
+
+        assert_eq!(mempool[0].txid(), child.txid().to_string());
+        assert_eq!(mempool[0].fee(), 1_500);
+        assert_eq!(mempool[0].weight() as u64, child.weight().to_wu());
+        assert_eq!(mempool[0].parent_txids(), &[parent.txid().to_string()]);
+        assert_eq!(
+            mempool[0].inputs(),
+            &[OutPoint::new(&parent.txid().to_string(), 0)]
+        );
+        // One legacy sigop in the P2PKH output, four times over
+        assert_eq!(mempool[0].sigop_cost(), 4);
+
+        assert_eq!(mempool[1].fee(), 1_000);
+        assert!(mempool[1].parent_txids().is_empty());
+        assert_eq!(mempool[1].sigop_cost(), 8);
+    }
+
+    #[test]
+    fn mined_raw_transactions_serialize_into_a_valid_block() {
+        let (parent, child, utxos) = family();
+        let transactions = RawTransactions::new(vec![parent.clone(), child.clone()])
+            .to_mempool(&utxos)
+            .unwrap();
+        let mut miner = Miner::from_transactions(transactions);
+        miner.mine();
+
+        let blocks = miner.assemble(&BlockAssembler::new(vec![0x51])).unwrap();
+        let decoded =
+            bitcoin::consensus::deserialize::<bitcoin::Block>(&blocks[0].serialize().unwrap())
+                .unwrap();
+        assert!(decoded.check_merkle_root());
+        assert!(decoded.check_witness_commitment());
+        assert_eq!(decoded.txdata[1..], [parent, child]);
+        assert_eq!(blocks[0].coinbase_value(), 50 * 100_000_000 + 2_500);
+    }
+
+    #[test]
+    fn fees_need_the_spent_outputs() {
+        let (parent, child, utxos) = family();
+
+        assert_eq!(
+            RawTransactions::new(vec![child.clone()]).to_mempool(&utxos),
+            Err(RawTransactionError::MissingPrevout {
+                txid: child.txid().to_string(),
+                outpoint: OutPoint::new(&parent.txid().to_string(), 0),
+            })
+        );
+
+        let greedy = spend((parent.txid(), 1), &[40_000], false);
+        assert_eq!(
+            RawTransactions::new(vec![parent.clone(), greedy.clone()]).to_mempool(&utxos),
+            Err(RawTransactionError::NegativeFee {
+                txid: greedy.txid().to_string(),
+                input_value: 39_000,
+                output_value: 40_000,
+            })
+        );
+
+        let overflowing = spend((parent.txid(), 1), &[u64::MAX, 1], false);
+        assert_eq!(
+            RawTransactions::new(vec![parent.clone(), overflowing.clone()]).to_mempool(&utxos),
+            Err(RawTransactionError::ValueOverflow {
+                txid: overflowing.txid().to_string(),
+            })
+        );
+
+        // Two confirmed outputs worth more than a `u64` together
+        let funding = FUNDING.parse::<Txid>().unwrap();
+        let mut rich = spend((funding, 0), &[1], false);
+        rich.input.push(rich.input[0].clone());
+        rich.input[1].previous_output.vout = 1;
+        let rich_utxos =
+            UtxoSet::parse(&format!("{FUNDING}:0,{}\n{FUNDING}:1,1\n", u64::MAX)).unwrap();
+        assert_eq!(
+            RawTransactions::new(vec![rich.clone()]).to_mempool(&rich_utxos),
+            Err(RawTransactionError::ValueOverflow {
+                txid: rich.txid().to_string(),
+            })
+        );
+
+        assert_eq!(
+            RawTransactions::new(vec![parent.clone(), parent.clone()]).to_mempool(&utxos),
+            Err(RawTransactionError::DuplicateTxid {
+                txid: parent.txid().to_string()
+            })
+        );
+        assert!(
+            RawTransactions::new(vec![spend((Txid::all_zeros(), u32::MAX), &[1], false)])
+                .to_mempool(&utxos)
+                .unwrap_err()
+                .to_string()
+                .contains("coinbase")
+        );
+    }
+
+    #[test]
+    fn miner_loads_a_directory_of_hex_transactions() {
+        let (parent, child, _) = family();
+        let directory =
+            std::env::temp_dir().join(format!("block-constructor-raw-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("1-parent.hex"), serialize_hex(&parent)).unwrap();
+        fs::write(
+            directory.join("2-child.hex"),
+            format!("\n{}\n", serialize_hex(&child)),
+        )
+        .unwrap();
+        let utxo_file = std::env::temp_dir().join(format!(
+            "block-constructor-utxos-{}.csv",
+            std::process::id()
+        ));
+        fs::write(
+            &utxo_file,
+            format!("{FUNDING}:0,100000,76a914{}88ac\n", "00".repeat(20)),
+        )
+        .unwrap();
+
+        let utxos = UtxoSet::load(&utxo_file).unwrap();
+        let mut miner = Miner::load_raw_transactions(&directory, &utxos).unwrap();
+        miner.mine();
+        fs::remove_dir_all(&directory).unwrap();
+        fs::remove_file(&utxo_file).unwrap();
+
+        assert_eq!(miner.finalized().len(), 1);
+        assert_eq!(
+            miner.finalized()[0]
+                .iter()
+                .map(|tx| tx.fee())
+                .collect::<Vec<u64>>(),
+            vec![1_000, 1_500]
+        );
+        assert!(UtxoSet::parse(&format!("{FUNDING}:0,abc"))
+            .unwrap_err()
+            .to_string()
+            .contains("field `value`"));
+    }
+}
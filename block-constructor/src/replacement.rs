@@ -0,0 +1,636 @@
+use crate::{OutPoint, Transaction, WITNESS_SCALE_FACTOR};
+use core::fmt;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap},
+};
+
+/// Fee rate in sat/kvB a replacement must pay on top of what it replaces
+pub const DEFAULT_INCREMENTAL_RELAY_FEE: u64 = 1_000;
+/// Most transactions a single replacement may evict (BIP125 rule 5)
+pub const MAX_REPLACEMENT_CANDIDATES: usize = 100;
+
+/// The BIP125 rules deciding whether a transaction replaces the mempool
+/// transactions spending the same outputs. Every transaction is taken to
+/// be replaceable (full RBF, the default since Bitcoin Core 28) since the
+/// mempool format does not carry sequence numbers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ReplacementPolicy {
+    incremental_relay_fee: u64,
+    max_replacements: usize,
+}
+
+impl Default for ReplacementPolicy {
+    fn default() -> Self {
+        Self {
+            incremental_relay_fee: DEFAULT_INCREMENTAL_RELAY_FEE,
+            max_replacements: MAX_REPLACEMENT_CANDIDATES,
+        }
+    }
+}
+
+impl ReplacementPolicy {
+    /// Fee rate in sat/kvB
+    pub fn with_incremental_relay_fee(mut self, incremental_relay_fee: u64) -> Self {
+        self.incremental_relay_fee = incremental_relay_fee;
+
+        self
+    }
+
+    pub fn with_max_replacements(mut self, max_replacements: usize) -> Self {
+        self.max_replacements = max_replacements;
+
+        self
+    }
+
+    pub fn incremental_relay_fee(&self) -> u64 {
+        self.incremental_relay_fee
+    }
+
+    pub fn max_replacements(&self) -> usize {
+        self.max_replacements
+    }
+
+    /// Replay the transactions in order, parents before children, keeping
+    /// the first spender of every output unless a later conflicting
+    /// transaction replaces it and its descendants under the rules.
+    /// Transactions spending a rejected or replaced transaction are dropped.
+    pub fn resolve(&self, transactions: Vec<Transaction>) -> ConflictResolution {
+        let mut resolution = ConflictResolution::default();
+        let mut pool = Pool::default();
+
+        for tx in arrival_order(transactions) {
+            if let Some(parent_txid) = tx
+                .parent_txids()
+                .iter()
+                .find(|parent| pool.removed.contains(parent.as_str()))
+            {
+                resolution.dropped.push(DroppedTransaction {
+                    txid: tx.txid().to_owned(),
+                    parent_txid: parent_txid.clone(),
+                });
+                pool.removed.insert(tx.txid().to_owned());
+                continue;
+            }
+
+            let conflicts = pool.conflicts(&tx);
+            if conflicts.is_empty() {
+                pool.insert(tx);
+                continue;
+            }
+
+            match self.check(&pool, &tx, &conflicts) {
+                Ok(replaced) => {
+                    let replaced_txids = replaced
+                        .iter()
+                        .map(|&index| pool.remove(index))
+                        .collect::<Vec<String>>();
+
+                    resolution.replacements.push(Replacement {
+                        txid: tx.txid().to_owned(),
+                        replaced_txids,
+                    });
+                    pool.insert(tx);
+                }
+                Err(reason) => {
+                    resolution.rejected.push(RejectedReplacement {
+                        txid: tx.txid().to_owned(),
+                        conflicting_txids: conflicts
+                            .iter()
+                            .map(|&index| pool.txid(index).to_owned())
+                            .collect(),
+                        reason,
+                    });
+                    pool.removed.insert(tx.txid().to_owned());
+                }
+            }
+        }
+
+        resolution.transactions = pool.transactions.into_iter().flatten().collect();
+
+        resolution
+    }
+
+    // Returns what the replacement evicts: its direct conflicts and their descendants
+    fn check(
+        &self,
+        pool: &Pool,
+        tx: &Transaction,
+        conflicts: &BTreeSet<usize>,
+    ) -> Result<BTreeSet<usize>, ReplacementRejection> {
+        let replaced = pool.with_descendants(conflicts);
+
+        if let Some(&spent) = replaced.iter().find(|&&index| {
+            tx.parent_txids()
+                .iter()
+                .any(|parent| parent == pool.txid(index))
+        }) {
+            return Err(ReplacementRejection::SpendsConflictingTransaction {
+                conflicting_txid: pool.txid(spent).to_owned(),
+            });
+        }
+
+        // Rule 6: pay a higher fee rate than every direct conflict
+        for &index in conflicts.iter() {
+            let conflict = pool.transaction(index);
+            if tx.fee() as u128 * conflict.weight() as u128
+                <= conflict.fee() as u128 * tx.weight() as u128
+            {
+                return Err(ReplacementRejection::LowerFeeRate {
+                    conflicting_txid: conflict.txid().to_owned(),
+                });
+            }
+        }
+
+        // Rule 5: evict at most `max_replacements` transactions
+        if replaced.len() > self.max_replacements {
+            return Err(ReplacementRejection::TooManyReplacements {
+                count: replaced.len(),
+                max: self.max_replacements,
+            });
+        }
+
+        // Rule 2: only spend unconfirmed outputs the conflicts already spent
+        let conflict_parents = conflicts
+            .iter()
+            .flat_map(|&index| pool.transaction(index).parent_txids())
+            .collect::<BTreeSet<&String>>();
+        if let Some(parent_txid) = tx
+            .parent_txids()
+            .iter()
+            .find(|parent| pool.contains(parent) && !conflict_parents.contains(parent))
+        {
+            return Err(ReplacementRejection::NewUnconfirmedInput {
+                parent_txid: parent_txid.clone(),
+            });
+        }
+
+        // Rule 3: pay at least the fees of everything replaced
+        let replaced_fee = replaced
+            .iter()
+            .map(|&index| pool.transaction(index).fee())
+            .sum::<u64>();
+        if tx.fee() < replaced_fee {
+            return Err(ReplacementRejection::InsufficientAbsoluteFee {
+                fee: tx.fee(),
+                replaced_fee,
+            });
+        }
+
+        // Rule 4: pay for its own relay at the incremental relay fee rate
+        let vsize = (tx.weight() as u64).div_ceil(WITNESS_SCALE_FACTOR);
+        let required = (vsize * self.incremental_relay_fee).div_ceil(1000);
+        if tx.fee() - replaced_fee < required {
+            return Err(ReplacementRejection::InsufficientIncrementalFee {
+                additional_fee: tx.fee() - replaced_fee,
+                required,
+            });
+        }
+
+        Ok(replaced)
+    }
+}
+
+/// The transactions left after resolving conflicts and what happened to the others
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ConflictResolution {
+    transactions: Vec<Transaction>,
+    replacements: Vec<Replacement>,
+    rejected: Vec<RejectedReplacement>,
+    dropped: Vec<DroppedTransaction>,
+}
+
+impl ConflictResolution {
+    /// The surviving transactions, none of which spend the same output
+    pub fn transactions(&self) -> &[Transaction] {
+        self.transactions.as_slice()
+    }
+
+    pub fn into_transactions(self) -> Vec<Transaction> {
+        self.transactions
+    }
+
+    pub fn replacements(&self) -> &[Replacement] {
+        self.replacements.as_slice()
+    }
+
+    pub fn rejected(&self) -> &[RejectedReplacement] {
+        self.rejected.as_slice()
+    }
+
+    pub fn dropped(&self) -> &[DroppedTransaction] {
+        self.dropped.as_slice()
+    }
+}
+
+impl fmt::Display for ConflictResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for replacement in self.replacements.iter() {
+            writeln!(f, "{replacement}")?;
+        }
+        for rejected in self.rejected.iter() {
+            writeln!(f, "{rejected}")?;
+        }
+        for dropped in self.dropped.iter() {
+            writeln!(f, "{dropped}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A transaction that replaced conflicting transactions and their descendants
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Replacement {
+    txid: String,
+    replaced_txids: Vec<String>,
+}
+
+impl Replacement {
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn replaced_txids(&self) -> &[String] {
+        self.replaced_txids.as_slice()
+    }
+}
+
+impl fmt::Display for Replacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} replaced {}",
+            self.txid,
+            self.replaced_txids.join(", ")
+        )
+    }
+}
+
+/// A conflicting transaction that failed the replacement rules
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RejectedReplacement {
+    txid: String,
+    conflicting_txids: Vec<String>,
+    reason: ReplacementRejection,
+}
+
+impl RejectedReplacement {
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    /// The mempool transactions spending the same outputs, which were kept
+    pub fn conflicting_txids(&self) -> &[String] {
+        self.conflicting_txids.as_slice()
+    }
+
+    pub fn reason(&self) -> &ReplacementRejection {
+        &self.reason
+    }
+}
+
+impl fmt::Display for RejectedReplacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} cannot replace {}: {}",
+            self.txid,
+            self.conflicting_txids.join(", "),
+            self.reason
+        )
+    }
+}
+
+/// A transaction spending a rejected or replaced transaction
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DroppedTransaction {
+    txid: String,
+    parent_txid: String,
+}
+
+impl DroppedTransaction {
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn parent_txid(&self) -> &str {
+        self.parent_txid.as_str()
+    }
+}
+
+impl fmt::Display for DroppedTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} spends removed {}", self.txid, self.parent_txid)
+    }
+}
+
+/// The BIP125 rule a replacement breaks
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ReplacementRejection {
+    /// The replacement spends an output of a transaction it would replace
+    SpendsConflictingTransaction { conflicting_txid: String },
+    /// Rule 2: it spends an unconfirmed output none of the conflicts spent
+    NewUnconfirmedInput { parent_txid: String },
+    /// Rule 3: it pays less than the transactions it replaces
+    InsufficientAbsoluteFee { fee: u64, replaced_fee: u64 },
+    /// Rule 4: the extra fee does not pay for its relay
+    InsufficientIncrementalFee { additional_fee: u64, required: u64 },
+    /// Rule 5: it would evict too many transactions
+    TooManyReplacements { count: usize, max: usize },
+    /// Rule 6: its fee rate is not higher than a direct conflict's
+    LowerFeeRate { conflicting_txid: String },
+}
+
+impl fmt::Display for ReplacementRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SpendsConflictingTransaction { conflicting_txid } => {
+                write!(f, "spends conflicting {conflicting_txid}")
+            }
+            Self::NewUnconfirmedInput { parent_txid } => {
+                write!(f, "adds the unconfirmed input {parent_txid}")
+            }
+            Self::InsufficientAbsoluteFee { fee, replaced_fee } => {
+                write!(f, "pays {fee} sats, less than the {replaced_fee} replaced")
+            }
+            Self::InsufficientIncrementalFee {
+                additional_fee,
+                required,
+            } => write!(
+                f,
+                "pays {additional_fee} sats more than replaced, {required} are required"
+            ),
+            Self::TooManyReplacements { count, max } => {
+                write!(
+                    f,
+                    "would replace {count} transactions, at most {max} allowed"
+                )
+            }
+            Self::LowerFeeRate { conflicting_txid } => {
+                write!(f, "does not pay a higher fee rate than {conflicting_txid}")
+            }
+        }
+    }
+}
+
+/// The mempool as transactions arrive, indexed by txid and spent outpoint
+#[derive(Debug, Default)]
+struct Pool {
+    transactions: Vec<Option<Transaction>>,
+    index: HashMap<String, usize>,
+    spenders: HashMap<OutPoint, usize>,
+    children: HashMap<String, Vec<usize>>,
+    // Txids whose descendants have to be dropped
+    removed: BTreeSet<String>,
+}
+
+impl Pool {
+    fn insert(&mut self, tx: Transaction) {
+        let index = self.transactions.len();
+
+        for input in tx.inputs() {
+            self.spenders.insert(input.clone(), index);
+        }
+        for parent in tx.parent_txids() {
+            self.children.entry(parent.clone()).or_default().push(index);
+        }
+        self.index.insert(tx.txid().to_owned(), index);
+        self.transactions.push(Some(tx));
+    }
+
+    fn remove(&mut self, index: usize) -> String {
+        let tx = self.transactions[index]
+            .take()
+            .expect("only transactions in the pool are removed");
+
+        for input in tx.inputs() {
+            self.spenders.remove(input);
+        }
+        self.index.remove(tx.txid());
+        self.removed.insert(tx.txid().to_owned());
+
+        tx.txid().to_owned()
+    }
+
+    fn contains(&self, txid: &str) -> bool {
+        self.index.contains_key(txid)
+    }
+
+    fn transaction(&self, index: usize) -> &Transaction {
+        self.transactions[index]
+            .as_ref()
+            .expect("indexes refer to transactions in the pool")
+    }
+
+    fn txid(&self, index: usize) -> &str {
+        self.transaction(index).txid()
+    }
+
+    // Pool transactions spending an output the transaction spends
+    fn conflicts(&self, tx: &Transaction) -> BTreeSet<usize> {
+        tx.inputs()
+            .iter()
+            .filter_map(|input| self.spenders.get(input).copied())
+            .collect()
+    }
+
+    fn with_descendants(&self, transactions: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut descendants = transactions.clone();
+        let mut stack = transactions.iter().copied().collect::<Vec<usize>>();
+
+        while let Some(index) = stack.pop() {
+            for &child in self.children.get(self.txid(index)).into_iter().flatten() {
+                if self.transactions[child].is_some() && descendants.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+
+        descendants
+    }
+}
+
+// Parents before children, in the given order otherwise.
+// Transactions in a dependency cycle come last.
+fn arrival_order(transactions: Vec<Transaction>) -> Vec<Transaction> {
+    let index = transactions
+        .iter()
+        .enumerate()
+        .map(|(position, tx)| (tx.txid(), position))
+        .collect::<HashMap<&str, usize>>();
+
+    let mut pending = vec![0usize; transactions.len()];
+    let mut children = vec![Vec::<usize>::new(); transactions.len()];
+    for (position, tx) in transactions.iter().enumerate() {
+        for parent in tx.parent_txids() {
+            if let Some(&parent) = index.get(parent.as_str()) {
+                pending[position] += 1;
+                children[parent].push(position);
+            }
+        }
+    }
+
+    let mut ready = (0..transactions.len())
+        .filter(|&position| pending[position] == 0)
+        .map(Reverse)
+        .collect::<BinaryHeap<_>>();
+    let mut order = Vec::<usize>::with_capacity(transactions.len());
+    let mut placed = vec![false; transactions.len()];
+
+    while let Some(Reverse(position)) = ready.pop() {
+        order.push(position);
+        placed[position] = true;
+
+        for &child in children[position].iter() {
+            pending[child] -= 1;
+            if pending[child] == 0 {
+                ready.push(Reverse(child));
+            }
+        }
+    }
+    order.extend((0..transactions.len()).filter(|&position| !placed[position]));
+
+    let mut transactions = transactions.into_iter().map(Some).collect::<Vec<_>>();
+    order
+        .into_iter()
+        .filter_map(|position| transactions[position].take())
+        .collect()
+}
+
+#[cfg(test)]
+mod replace_by_fee {
+    use crate::{OutPoint, ReplacementPolicy, ReplacementRejection, Transaction};
+
+    fn spending(
+        txid: &str,
+        fee: u64,
+        weight: u32,
+        parents: &[&str],
+        outpoints: &[(&str, u32)],
+    ) -> Transaction {
+        Transaction::new(txid, fee, weight, parents).with_inputs(
+            outpoints
+                .iter()
+                .map(|&(txid, vout)| OutPoint::new(txid, vout))
+                .collect(),
+        )
+    }
+
+    fn txids(transactions: &[Transaction]) -> Vec<&str> {
+        transactions.iter().map(|tx| tx.txid()).collect()
+    }
+
+    #[test]
+    fn higher_fee_replacement_evicts_the_original_and_its_children() {
+        let mempool = vec![
+            spending("original", 1000, 800, &[], &[("coin", 0)]),
+            spending("child", 500, 800, &["original"], &[("original", 0)]),
+            spending("replacement", 2000, 800, &[], &[("coin", 0)]),
+            spending("late-child", 500, 800, &["child"], &[("child", 0)]),
+        ];
+
+        let resolution = ReplacementPolicy::default().resolve(mempool);
+        assert_eq!(txids(resolution.transactions()), vec!["replacement"]);
+        assert_eq!(resolution.replacements()[0].txid(), "replacement");
+        assert_eq!(
+            resolution.replacements()[0].replaced_txids(),
+            &["original".to_owned(), "child".to_owned()]
+        );
+        assert_eq!(resolution.dropped()[0].txid(), "late-child");
+    }
+
+    #[test]
+    fn replacements_breaking_a_rule_are_rejected() {
+        let original = spending("original", 1000, 800, &[], &[("coin", 0)]);
+        let cases = [
+            (
+                spending("cheaper", 900, 400, &[], &[("coin", 0)]),
+                ReplacementRejection::InsufficientAbsoluteFee {
+                    fee: 900,
+                    replaced_fee: 1000,
+                },
+            ),
+            (
+                spending("same-rate", 2000, 1600, &[], &[("coin", 0)]),
+                ReplacementRejection::LowerFeeRate {
+                    conflicting_txid: "original".to_owned(),
+                },
+            ),
+            (
+                // 200 vB at 1 sat/vB needs 200 sats more than the original
+                spending("stingy", 1100, 800, &[], &[("coin", 0)]),
+                ReplacementRejection::InsufficientIncrementalFee {
+                    additional_fee: 100,
+                    required: 200,
+                },
+            ),
+        ];
+
+        for (replacement, reason) in cases {
+            let txid = replacement.txid().to_owned();
+            let resolution =
+                ReplacementPolicy::default().resolve(vec![original.clone(), replacement]);
+
+            assert_eq!(txids(resolution.transactions()), vec!["original"]);
+            assert_eq!(resolution.rejected()[0].txid(), txid);
+            assert_eq!(resolution.rejected()[0].reason(), &reason);
+        }
+    }
+
+    #[test]
+    fn replacements_cannot_add_unconfirmed_inputs() {
+        let mempool = vec![
+            spending("unrelated", 1000, 800, &[], &[("other", 0)]),
+            spending("original", 1000, 800, &[], &[("coin", 0)]),
+            spending(
+                "replacement",
+                5000,
+                800,
+                &["unrelated"],
+                &[("coin", 0), ("unrelated", 0)],
+            ),
+        ];
+
+        let resolution = ReplacementPolicy::default().resolve(mempool);
+        assert_eq!(
+            resolution.rejected()[0].reason(),
+            &ReplacementRejection::NewUnconfirmedInput {
+                parent_txid: "unrelated".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn at_most_max_replacements_are_evicted() {
+        let mut mempool = vec![spending("original", 1000, 800, &[], &[("coin", 0)])];
+        for index in 0..3 {
+            let parent = if index == 0 {
+                "original".to_owned()
+            } else {
+                format!("child-{}", index - 1)
+            };
+            mempool.push(spending(
+                &format!("child-{index}"),
+                100,
+                800,
+                &[&parent],
+                &[(&parent, 0)],
+            ));
+        }
+        mempool.push(spending("replacement", 10_000, 800, &[], &[("coin", 0)]));
+
+        let resolution = ReplacementPolicy::default()
+            .with_max_replacements(3)
+            .resolve(mempool.clone());
+        assert_eq!(
+            resolution.rejected()[0].reason(),
+            &ReplacementRejection::TooManyReplacements { count: 4, max: 3 }
+        );
+
+        let resolution = ReplacementPolicy::default()
+            .with_max_replacements(4)
+            .resolve(mempool);
+        assert_eq!(txids(resolution.transactions()), vec!["replacement"]);
+    }
+}
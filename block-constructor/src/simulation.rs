@@ -0,0 +1,679 @@
+use crate::{
+    BlockLimits, MempoolGraph, SelectionStrategy, Transaction, DEFAULT_ANCESTOR_LIMIT,
+    WITNESS_SCALE_FACTOR,
+};
+use core::fmt;
+use std::collections::HashSet;
+
+/// Mean time between blocks in seconds
+pub const DEFAULT_BLOCK_INTERVAL: f64 = 600.0;
+/// Mean number of transactions arriving per second when the mempool
+/// does not carry arrival times
+pub const DEFAULT_ARRIVAL_RATE: f64 = 7.0;
+/// Lower bounds in sat/vB of the fee rate buckets confirmation delays are grouped by
+pub const DEFAULT_FEE_RATE_BUCKETS: [f64; 9] = [0.0, 1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+
+/// How transactions arrive and blocks are found in a simulation
+#[derive(Debug, PartialEq, Clone)]
+pub struct SimulationConfig {
+    limits: BlockLimits,
+    block_interval: f64,
+    arrival_rate: f64,
+    seed: u64,
+    fee_rate_buckets: Vec<f64>,
+    max_blocks: usize,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            limits: BlockLimits::default(),
+            block_interval: DEFAULT_BLOCK_INTERVAL,
+            arrival_rate: DEFAULT_ARRIVAL_RATE,
+            seed: 0,
+            fee_rate_buckets: DEFAULT_FEE_RATE_BUCKETS.to_vec(),
+            max_blocks: 1_000,
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn with_limits(mut self, limits: BlockLimits) -> Self {
+        self.limits = limits;
+
+        self
+    }
+
+    /// Mean of the exponentially distributed time between blocks in seconds
+    pub fn with_block_interval(mut self, block_interval: f64) -> Self {
+        self.block_interval = block_interval;
+
+        self
+    }
+
+    /// Rate in transactions per second of the Poisson process generating
+    /// arrival times for transactions without one
+    pub fn with_arrival_rate(mut self, arrival_rate: f64) -> Self {
+        self.arrival_rate = arrival_rate;
+
+        self
+    }
+
+    /// Seed of the block interval and arrival time generator
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+
+        self
+    }
+
+    /// Lower bounds of the fee rate buckets in sat/vB, ascending
+    pub fn with_fee_rate_buckets(mut self, fee_rate_buckets: Vec<f64>) -> Self {
+        self.fee_rate_buckets = fee_rate_buckets;
+
+        self
+    }
+
+    /// Blocks after which the simulation stops even if transactions are left
+    pub fn with_max_blocks(mut self, max_blocks: usize) -> Self {
+        self.max_blocks = max_blocks;
+
+        self
+    }
+
+    pub fn limits(&self) -> &BlockLimits {
+        &self.limits
+    }
+
+    pub fn block_interval(&self) -> f64 {
+        self.block_interval
+    }
+
+    pub fn arrival_rate(&self) -> f64 {
+        self.arrival_rate
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn fee_rate_buckets(&self) -> &[f64] {
+        self.fee_rate_buckets.as_slice()
+    }
+
+    pub fn max_blocks(&self) -> usize {
+        self.max_blocks
+    }
+}
+
+/// A discrete-event simulation of a mempool over time. Transactions enter
+/// the mempool at their arrival time, never before their parents, and each
+/// time a block is found the strategy builds a template from the mempool
+/// at that moment and its first block is mined.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SimulationReport {
+    blocks: Vec<SimulatedBlock>,
+    confirmations: Vec<Confirmation>,
+    buckets: Vec<DelayStats>,
+}
+
+impl SimulationReport {
+    pub fn run(
+        transactions: &[Transaction],
+        strategy: &dyn SelectionStrategy,
+        config: &SimulationConfig,
+    ) -> Self {
+        let mut rng = SplitMix64::new(config.seed);
+        let arrival_times = arrival_times(transactions, config.arrival_rate, &mut rng);
+
+        let mut arrivals = (0..transactions.len()).collect::<Vec<usize>>();
+        arrivals.sort_by(|&a, &b| {
+            arrival_times[a]
+                .total_cmp(&arrival_times[b])
+                .then(a.cmp(&b))
+        });
+        let mut arrivals = arrivals.into_iter().peekable();
+
+        let mut confirmations = transactions
+            .iter()
+            .zip(arrival_times.iter())
+            .map(|(tx, &arrival_time)| Confirmation {
+                txid: tx.txid().to_owned(),
+                fee_rate: fee_rate(tx),
+                arrival_time,
+                arrival_height: 0,
+                block: None,
+            })
+            .collect::<Vec<Confirmation>>();
+
+        let mut blocks = Vec::<SimulatedBlock>::new();
+        let mut pending = Vec::<usize>::new();
+        let mut confirmed_txids = HashSet::<&str>::new();
+        let mut time = 0f64;
+
+        while blocks.len() < config.max_blocks && (arrivals.peek().is_some() || !pending.is_empty())
+        {
+            time += rng.exponential(config.block_interval);
+            let height = blocks.len() + 1;
+
+            while let Some(position) = arrivals.next_if(|&position| arrival_times[position] <= time)
+            {
+                confirmations[position].arrival_height = height - 1;
+                pending.push(position);
+            }
+
+            // Parents confirmed in earlier blocks are no longer in the mempool
+            let mempool = pending
+                .iter()
+                .map(|&position| {
+                    let tx = &transactions[position];
+                    let parents = tx
+                        .parent_txids()
+                        .iter()
+                        .map(|parent| parent.as_str())
+                        .filter(|parent| !confirmed_txids.contains(parent))
+                        .collect::<Vec<&str>>();
+
+                    Transaction::new(tx.txid(), tx.fee(), tx.weight(), &parents)
+                        .with_sigop_cost(tx.sigop_cost())
+                        .with_inputs(tx.inputs().to_vec())
+                })
+                .collect::<Vec<Transaction>>();
+
+            let projection = strategy.project(&MempoolGraph::new(&mempool), config.limits);
+            let selected = projection
+                .blocks()
+                .first()
+                .map_or(&[][..], |block| block.positions());
+
+            let mut total_fee = 0u64;
+            let mut confirmed = vec![false; pending.len()];
+            for &index in selected {
+                let position = pending[index];
+                confirmed[index] = true;
+                confirmed_txids.insert(transactions[position].txid());
+                confirmations[position].block = Some((height, time));
+                total_fee += transactions[position].fee();
+            }
+
+            let mut index = 0;
+            pending.retain(|_| {
+                index += 1;
+                !confirmed[index - 1]
+            });
+
+            blocks.push(SimulatedBlock {
+                height,
+                time,
+                tx_count: selected.len(),
+                total_fee,
+                mempool_size: pending.len(),
+            });
+
+            // Whatever is left can never be mined
+            if selected.is_empty() && arrivals.peek().is_none() {
+                break;
+            }
+        }
+
+        let buckets = DelayStats::by_fee_rate(&confirmations, &config.fee_rate_buckets);
+
+        Self {
+            blocks,
+            confirmations,
+            buckets,
+        }
+    }
+
+    pub fn blocks(&self) -> &[SimulatedBlock] {
+        self.blocks.as_slice()
+    }
+
+    /// What happened to each transaction, in mempool order
+    pub fn confirmations(&self) -> &[Confirmation] {
+        self.confirmations.as_slice()
+    }
+
+    /// Confirmation delays grouped by fee rate
+    pub fn buckets(&self) -> &[DelayStats] {
+        self.buckets.as_slice()
+    }
+
+    /// Transactions still in the mempool when the simulation stopped
+    pub fn unconfirmed(&self) -> usize {
+        self.confirmations
+            .iter()
+            .filter(|confirmation| confirmation.block.is_none())
+            .count()
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>7} {:>9} {:>10} {:>10} {:>10} {:>10} {:>7}",
+            "sat/vB", "txs", "confirmed", "mean (s)", "median (s)", "p90 (s)", "max (s)", "blocks"
+        )?;
+
+        for bucket in self.buckets.iter() {
+            let range = match bucket.max_fee_rate {
+                Some(max_fee_rate) => format!("{}-{}", bucket.min_fee_rate, max_fee_rate),
+                None => format!("{}+", bucket.min_fee_rate),
+            };
+            writeln!(
+                f,
+                "{:<10} {:>7} {:>9} {:>10.0} {:>10.0} {:>10.0} {:>10.0} {:>7.2}",
+                range,
+                bucket.count,
+                bucket.confirmed,
+                bucket.mean_delay,
+                bucket.median_delay,
+                bucket.p90_delay,
+                bucket.max_delay,
+                bucket.mean_blocks
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{} blocks in {:.0} s, {} transactions unconfirmed",
+            self.blocks.len(),
+            self.blocks.last().map_or(0.0, |block| block.time),
+            self.unconfirmed()
+        )
+    }
+}
+
+/// A block found during the simulation
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SimulatedBlock {
+    height: usize,
+    time: f64,
+    tx_count: usize,
+    total_fee: u64,
+    mempool_size: usize,
+}
+
+impl SimulatedBlock {
+    /// Blocks found since the start of the simulation, starting at 1
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Seconds since the start of the simulation
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn tx_count(&self) -> usize {
+        self.tx_count
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.total_fee
+    }
+
+    /// Transactions left in the mempool after the block
+    pub fn mempool_size(&self) -> usize {
+        self.mempool_size
+    }
+}
+
+/// When a transaction arrived and the block that confirmed it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Confirmation {
+    txid: String,
+    fee_rate: f64,
+    arrival_time: f64,
+    arrival_height: usize,
+    block: Option<(usize, f64)>,
+}
+
+impl Confirmation {
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    /// The transaction's own fee rate in sat/vB
+    pub fn fee_rate(&self) -> f64 {
+        self.fee_rate
+    }
+
+    /// Seconds since the start of the simulation
+    pub fn arrival_time(&self) -> f64 {
+        self.arrival_time
+    }
+
+    /// Height of the block confirming the transaction
+    pub fn height(&self) -> Option<usize> {
+        self.block.map(|(height, _)| height)
+    }
+
+    /// Seconds from arrival to confirmation
+    pub fn delay(&self) -> Option<f64> {
+        self.block.map(|(_, time)| time - self.arrival_time)
+    }
+
+    /// Blocks found from arrival up to and including the confirming one
+    pub fn blocks_waited(&self) -> Option<usize> {
+        self.block.map(|(height, _)| height - self.arrival_height)
+    }
+}
+
+/// Confirmation delays of the transactions within a fee rate range
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DelayStats {
+    min_fee_rate: f64,
+    max_fee_rate: Option<f64>,
+    count: usize,
+    confirmed: usize,
+    mean_delay: f64,
+    median_delay: f64,
+    p90_delay: f64,
+    max_delay: f64,
+    mean_blocks: f64,
+}
+
+impl DelayStats {
+    fn by_fee_rate(confirmations: &[Confirmation], bounds: &[f64]) -> Vec<Self> {
+        bounds
+            .iter()
+            .enumerate()
+            .map(|(index, &min_fee_rate)| {
+                let max_fee_rate = bounds.get(index + 1).copied();
+                let in_bucket = confirmations
+                    .iter()
+                    .filter(|confirmation| {
+                        confirmation.fee_rate >= min_fee_rate
+                            && max_fee_rate.is_none_or(|max| confirmation.fee_rate < max)
+                    })
+                    .collect::<Vec<&Confirmation>>();
+
+                let mut delays = in_bucket
+                    .iter()
+                    .filter_map(|confirmation| confirmation.delay())
+                    .collect::<Vec<f64>>();
+                delays.sort_by(f64::total_cmp);
+                let blocks = in_bucket
+                    .iter()
+                    .filter_map(|confirmation| confirmation.blocks_waited())
+                    .sum::<usize>();
+
+                Self {
+                    min_fee_rate,
+                    max_fee_rate,
+                    count: in_bucket.len(),
+                    confirmed: delays.len(),
+                    mean_delay: mean(delays.iter().sum::<f64>(), delays.len()),
+                    median_delay: percentile(&delays, 50),
+                    p90_delay: percentile(&delays, 90),
+                    max_delay: delays.last().copied().unwrap_or_default(),
+                    mean_blocks: mean(blocks as f64, delays.len()),
+                }
+            })
+            .collect()
+    }
+
+    /// Lowest fee rate in the bucket in sat/vB
+    pub fn min_fee_rate(&self) -> f64 {
+        self.min_fee_rate
+    }
+
+    /// Fee rate the next bucket starts at, `None` for the last bucket
+    pub fn max_fee_rate(&self) -> Option<f64> {
+        self.max_fee_rate
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn confirmed(&self) -> usize {
+        self.confirmed
+    }
+
+    /// Delays are in seconds and only cover confirmed transactions
+    pub fn mean_delay(&self) -> f64 {
+        self.mean_delay
+    }
+
+    pub fn median_delay(&self) -> f64 {
+        self.median_delay
+    }
+
+    pub fn p90_delay(&self) -> f64 {
+        self.p90_delay
+    }
+
+    pub fn max_delay(&self) -> f64 {
+        self.max_delay
+    }
+
+    /// Mean number of blocks found from arrival to confirmation
+    pub fn mean_blocks(&self) -> f64 {
+        self.mean_blocks
+    }
+}
+
+/// A reproducible random mempool of `size` transactions for benchmarks.
+/// Fee rates are exponentially distributed above 1 sat/vB and about a
+/// third of the transactions spend one or two of the previous thousand,
+/// keeping every transaction within Bitcoin Core's 25 ancestor limit.
+pub fn synthetic_mempool(size: usize, seed: u64) -> Vec<Transaction> {
+    let mut rng = SplitMix64::new(seed);
+    let mut transactions = Vec::<Transaction>::with_capacity(size);
+    // Upper bound on the ancestors of each transaction including itself
+    let mut ancestor_counts = Vec::<usize>::with_capacity(size);
+
+    for index in 0..size {
+        let txid = format!("{:016x}{:048x}", rng.next_u64(), index);
+        let weight = 400 + (rng.next_u64() % 3_600) as u32;
+        let fee_rate = 1.0 + rng.exponential(10.0);
+        let fee = (fee_rate * weight as f64 / WITNESS_SCALE_FACTOR as f64) as u64;
+
+        let mut parents = Vec::<usize>::new();
+        let mut ancestor_count = 1usize;
+        if index > 0 && rng.next_f64() < 0.3 {
+            for _ in 0..1 + rng.next_u64() % 2 {
+                let parent = index - 1 - rng.next_u64() as usize % index.min(1_000);
+                if !parents.contains(&parent)
+                    && ancestor_count + ancestor_counts[parent] <= DEFAULT_ANCESTOR_LIMIT
+                {
+                    ancestor_count += ancestor_counts[parent];
+                    parents.push(parent);
+                }
+            }
+        }
+
+        let parent_txids = parents
+            .iter()
+            .map(|&parent| transactions[parent].txid())
+            .collect::<Vec<&str>>();
+        let tx = Transaction::new(&txid, fee, weight, &parent_txids);
+        ancestor_counts.push(ancestor_count);
+        transactions.push(tx);
+    }
+
+    transactions
+}
+
+/// SplitMix64, a small seeded generator so simulations are reproducible
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub(crate) fn exponential(&mut self, mean: f64) -> f64 {
+        -mean * (1.0 - self.next_f64()).ln()
+    }
+}
+
+// Transactions without an arrival time arrive in a random order as a
+// Poisson process, since a loaded mempool is sorted by fee rate, and no
+// transaction arrives before its parents
+fn arrival_times(
+    transactions: &[Transaction],
+    arrival_rate: f64,
+    rng: &mut SplitMix64,
+) -> Vec<f64> {
+    let mut arrival_times = transactions
+        .iter()
+        .map(|tx| tx.arrival_time().map_or(f64::NAN, |time| time as f64))
+        .collect::<Vec<f64>>();
+
+    let mut unknown = (0..transactions.len())
+        .filter(|&position| arrival_times[position].is_nan())
+        .collect::<Vec<usize>>();
+    for index in (1..unknown.len()).rev() {
+        unknown.swap(index, rng.next_u64() as usize % (index + 1));
+    }
+
+    let mut clock = 0f64;
+    for position in unknown {
+        clock += rng.exponential(1.0 / arrival_rate);
+        arrival_times[position] = clock;
+    }
+
+    let graph = MempoolGraph::new(transactions);
+    (0..transactions.len())
+        .map(|position| {
+            graph
+                .ancestors(position)
+                .into_iter()
+                .map(|ancestor| arrival_times[ancestor])
+                .fold(arrival_times[position], f64::max)
+        })
+        .collect()
+}
+
+fn fee_rate(tx: &Transaction) -> f64 {
+    (tx.fee() * WITNESS_SCALE_FACTOR) as f64 / tx.weight() as f64
+}
+
+fn mean(total: f64, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total / count as f64
+    }
+}
+
+// `values` are sorted
+fn percentile(values: &[f64], percentile: usize) -> f64 {
+    match values.len() {
+        0 => 0.0,
+        len => values[((len * percentile).div_ceil(100)).clamp(1, len) - 1],
+    }
+}
+
+#[cfg(test)]
+mod mempool_simulation {
+    use crate::{
+        AncestorPackageStrategy, BlockLimits, Miner, SimulationConfig, SimulationReport,
+        Transaction,
+    };
+
+    #[test]
+    fn higher_fee_rates_confirm_first() {
+        // One 1000 weight unit transaction per block
+        let reserved = BlockLimits::default().reserved_weight();
+        let config = SimulationConfig::default()
+            .with_limits(BlockLimits::default().with_max_block_weight(1000 + reserved))
+            .with_fee_rate_buckets(vec![0.0, 5.0, 10.0]);
+        let mempool = vec![
+            Transaction::new("low", 1000, 1000, &[]).with_arrival_time(0),
+            Transaction::new("high", 4000, 1000, &[]).with_arrival_time(0),
+            Transaction::new("medium", 2000, 1000, &[]).with_arrival_time(0),
+        ];
+
+        let report = SimulationReport::run(&mempool, &AncestorPackageStrategy, &config);
+        let heights = report
+            .confirmations()
+            .iter()
+            .map(|confirmation| (confirmation.txid(), confirmation.height().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(heights, vec![("low", 3), ("high", 1), ("medium", 2)]);
+        assert_eq!(report.blocks().len(), 3);
+        assert_eq!(report.unconfirmed(), 0);
+
+        let buckets = report.buckets();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!((buckets[0].count(), buckets[0].mean_blocks()), (1, 3.0));
+        assert_eq!((buckets[1].count(), buckets[1].mean_blocks()), (1, 2.0));
+        assert_eq!((buckets[2].count(), buckets[2].mean_blocks()), (1, 1.0));
+        assert!(buckets[0].mean_delay() > buckets[2].mean_delay());
+    }
+
+    #[test]
+    fn children_arrive_with_their_parents() {
+        let mempool = vec![
+            Transaction::new("child", 5000, 1000, &["parent"]).with_arrival_time(0),
+            Transaction::new("parent", 100, 1000, &[]).with_arrival_time(900),
+            Transaction::new("orphan", 100, 1000, &["missing"]).with_arrival_time(0),
+        ];
+
+        let report = SimulationReport::run(
+            &mempool,
+            &AncestorPackageStrategy,
+            &SimulationConfig::default(),
+        );
+        let confirmations = report.confirmations();
+        assert_eq!(confirmations[0].arrival_time(), 900.0);
+        assert_eq!(confirmations[0].height(), confirmations[1].height());
+        assert_eq!(confirmations[2].height(), None);
+        assert_eq!(report.unconfirmed(), 1);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_simulation() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let config = SimulationConfig::default().with_seed(7);
+
+        let report = SimulationReport::run(miner.mempool(), &AncestorPackageStrategy, &config);
+        assert_eq!(report.unconfirmed(), 0);
+        assert_eq!(
+            report
+                .buckets()
+                .iter()
+                .map(|bucket| bucket.count())
+                .sum::<usize>(),
+            5214
+        );
+        assert!(report
+            .confirmations()
+            .iter()
+            .all(|confirmation| confirmation.delay().unwrap() >= 0.0));
+
+        assert_eq!(
+            SimulationReport::run(miner.mempool(), &AncestorPackageStrategy, &config),
+            report
+        );
+        assert_ne!(
+            SimulationReport::run(
+                miner.mempool(),
+                &AncestorPackageStrategy,
+                &config.with_seed(8)
+            ),
+            report
+        );
+    }
+}
@@ -0,0 +1,671 @@
+use crate::{AncestorPackageSelector, BlockLimits, MempoolGraph, Projection, Transaction};
+use core::fmt;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+    time::{Duration, Instant},
+};
+
+/// Limits of the branch-and-bound search run at the end of each block
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SolverConfig {
+    tail_weight: u64,
+    tail_sigop_cost: u64,
+    max_candidates: usize,
+    time_budget: Duration,
+    max_nodes: u64,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            tail_weight: 100_000,
+            tail_sigop_cost: 2_000,
+            max_candidates: 64,
+            time_budget: Duration::from_millis(500),
+            max_nodes: 200_000,
+        }
+    }
+}
+
+impl SolverConfig {
+    /// Weight at the end of the block that is re-packed by the solver
+    /// instead of being filled by ancestor fee rate
+    pub fn with_tail_weight(mut self, tail_weight: u64) -> Self {
+        self.tail_weight = tail_weight;
+
+        self
+    }
+
+    /// Sigop cost at the end of the block that is re-packed by the solver,
+    /// the tail starts where either this or the tail weight is reached
+    pub fn with_tail_sigop_cost(mut self, tail_sigop_cost: u64) -> Self {
+        self.tail_sigop_cost = tail_sigop_cost;
+
+        self
+    }
+
+    /// Number of packages, besides those greedy picked for the tail,
+    /// that the solver considers for the tail
+    pub fn with_max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates;
+
+        self
+    }
+
+    /// Wall clock time the search may take for each block
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = time_budget;
+
+        self
+    }
+
+    /// Number of search nodes visited for each block, which unlike the
+    /// time budget keeps the result independent of the machine
+    pub fn with_max_nodes(mut self, max_nodes: u64) -> Self {
+        self.max_nodes = max_nodes;
+
+        self
+    }
+
+    pub fn tail_weight(&self) -> u64 {
+        self.tail_weight
+    }
+
+    pub fn tail_sigop_cost(&self) -> u64 {
+        self.tail_sigop_cost
+    }
+
+    pub fn max_candidates(&self) -> usize {
+        self.max_candidates
+    }
+
+    pub fn time_budget(&self) -> Duration {
+        self.time_budget
+    }
+
+    pub fn max_nodes(&self) -> u64 {
+        self.max_nodes
+    }
+}
+
+/// Outcome of the search for one block
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SolverStats {
+    greedy_fee: u64,
+    fee: u64,
+    upper_bound: u64,
+    candidates: usize,
+    nodes: u64,
+    optimal: bool,
+}
+
+impl SolverStats {
+    /// Fee of the block ancestor fee rate selection would have built
+    pub fn greedy_fee(&self) -> u64 {
+        self.greedy_fee
+    }
+
+    /// Fee of the block built by the solver, never below `greedy_fee`
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn improvement(&self) -> u64 {
+        self.fee - self.greedy_fee
+    }
+
+    /// No block can pay more than this: the prefix plus the fractional
+    /// knapsack over the candidates, ignoring their dependencies
+    pub fn upper_bound(&self) -> u64 {
+        self.upper_bound
+    }
+
+    /// Number of transactions the tail was chosen from
+    pub fn candidates(&self) -> usize {
+        self.candidates
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// The search finished within its budget, so no better tail
+    /// exists among the candidates
+    pub fn optimal(&self) -> bool {
+        self.optimal
+    }
+}
+
+/// Selects transactions into blocks by ancestor fee rate and then re-packs
+/// the last `tail_weight` weight units of each block as a knapsack whose
+/// items depend on each other: a transaction may only be picked together
+/// with its parents. The knapsack is solved by branch-and-bound, starting
+/// from the tail greedy picked so a block is never worse than greedy's.
+#[derive(Debug, Clone)]
+pub struct BranchAndBoundSelector<'a> {
+    graph: &'a MempoolGraph<'a>,
+    selector: AncestorPackageSelector<'a>,
+    config: SolverConfig,
+    stats: Vec<SolverStats>,
+}
+
+impl<'a> BranchAndBoundSelector<'a> {
+    pub fn new(graph: &'a MempoolGraph<'a>, config: SolverConfig) -> Self {
+        Self {
+            graph,
+            selector: AncestorPackageSelector::new(graph),
+            config,
+            stats: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &SolverConfig {
+        &self.config
+    }
+
+    /// Search outcome of every block built so far
+    pub fn stats(&self) -> &[SolverStats] {
+        self.stats.as_slice()
+    }
+
+    /// Fill the next block up to `max_weight` and `max_sigop_cost`
+    /// returning the positions of the selected transactions in topological
+    /// order. Returns `None` once no remaining transaction fits into an empty block.
+    pub fn next_block(&mut self, max_weight: u64, max_sigop_cost: u64) -> Option<Vec<usize>> {
+        let greedy = self
+            .selector
+            .clone()
+            .next_block(max_weight, max_sigop_cost)?;
+
+        // Packages are added with their ancestors first so any prefix of
+        // the greedy block is a valid block on its own
+        let prefix_limit = max_weight.saturating_sub(self.config.tail_weight);
+        let prefix_sigop_limit = max_sigop_cost.saturating_sub(self.config.tail_sigop_cost);
+        let mut prefix_weight = 0u64;
+        let mut prefix_sigop_cost = 0u64;
+        let prefix_len = greedy
+            .iter()
+            .take_while(|&&position| {
+                prefix_weight += self.graph.transaction(position).weight() as u64;
+                prefix_sigop_cost += self.graph.transaction(position).sigop_cost();
+                prefix_weight <= prefix_limit && prefix_sigop_cost <= prefix_sigop_limit
+            })
+            .count();
+        let (prefix, tail) = greedy.split_at(prefix_len);
+        let prefix_weight = self.weight_of(prefix);
+
+        let mut working = self.selector.clone();
+        working.include(prefix);
+
+        let capacity = max_weight - prefix_weight;
+        let sigop_capacity = max_sigop_cost - self.sigop_cost_of(prefix);
+        let items = self.candidate_pool(&working, tail, capacity, sigop_capacity);
+        let greedy_tail = items
+            .iter()
+            .map(|item| tail.contains(&item.position))
+            .collect::<Vec<bool>>();
+
+        let mut search = Search::new(
+            &items,
+            (capacity, sigop_capacity),
+            greedy_tail,
+            &self.config,
+        );
+        let upper_bound = self.fee_of(prefix) + search.bound(0, capacity);
+        search.run();
+
+        let mut block = prefix.to_vec();
+        block.extend(
+            items
+                .iter()
+                .zip(search.best.iter())
+                .filter(|(_, &chosen)| chosen)
+                .map(|(item, _)| item.position),
+        );
+        self.selector.include(&block);
+
+        self.stats.push(SolverStats {
+            greedy_fee: self.fee_of(&greedy),
+            fee: self.fee_of(&block),
+            upper_bound,
+            candidates: items.len(),
+            nodes: search.nodes,
+            optimal: !search.aborted,
+        });
+
+        Some(block)
+    }
+
+    // The greedy tail plus the packages with the best ancestor fee rate
+    // that fit the tail, in topological order
+    fn candidate_pool(
+        &self,
+        working: &AncestorPackageSelector,
+        tail: &[usize],
+        capacity: u64,
+        sigop_capacity: u64,
+    ) -> Vec<Item> {
+        let mut packages = working
+            .remaining()
+            .map(|position| {
+                let ancestors = working.ancestors(position);
+                let fee = self.fee_of(ancestors) + self.graph.transaction(position).fee();
+                let weight =
+                    self.weight_of(ancestors) + self.graph.transaction(position).weight() as u64;
+                let sigop_cost =
+                    self.sigop_cost_of(ancestors) + self.graph.transaction(position).sigop_cost();
+
+                (position, fee, weight, sigop_cost)
+            })
+            .filter(|&(_, _, weight, sigop_cost)| {
+                weight <= capacity && sigop_cost <= sigop_capacity
+            })
+            .map(|(position, fee, weight, _)| (position, fee, weight))
+            .collect::<Vec<(usize, u64, u64)>>();
+        packages.sort_by(|a, b| {
+            (b.1 as u128 * a.2 as u128)
+                .cmp(&(a.1 as u128 * b.2 as u128))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+
+        let mut pool = tail.iter().copied().collect::<BTreeSet<usize>>();
+        for &(position, _, _) in packages.iter().take(self.config.max_candidates) {
+            pool.insert(position);
+            pool.extend(working.ancestors(position).iter().copied());
+        }
+
+        // Kahn's algorithm picking the best fee rate among the transactions
+        // whose parents are already ordered, so the search tries the most
+        // profitable transactions first
+        let in_pool_parents = |position: usize| {
+            self.graph
+                .parents(position)
+                .iter()
+                .copied()
+                .filter(|parent| pool.contains(parent))
+                .collect::<Vec<usize>>()
+        };
+        let mut pending = pool
+            .iter()
+            .map(|&position| (position, in_pool_parents(position).len()))
+            .collect::<BTreeMap<usize, usize>>();
+        let mut ready = pending
+            .iter()
+            .filter(|(_, &count)| count == 0)
+            .map(|(&position, _)| (self.graph.transaction(position), Reverse(position)))
+            .collect::<BinaryHeap<(&Transaction, Reverse<usize>)>>();
+
+        let mut index = BTreeMap::<usize, usize>::new();
+        let mut items = Vec::<Item>::with_capacity(pool.len());
+
+        while let Some((tx, Reverse(position))) = ready.pop() {
+            index.insert(position, items.len());
+            items.push(Item {
+                position,
+                fee: tx.fee(),
+                weight: tx.weight() as u64,
+                sigop_cost: tx.sigop_cost(),
+                parents: in_pool_parents(position)
+                    .iter()
+                    .map(|parent| index[parent])
+                    .collect(),
+            });
+
+            for &child in self.graph.children(position) {
+                if let Some(count) = pending.get_mut(&child) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push((self.graph.transaction(child), Reverse(child)));
+                    }
+                }
+            }
+        }
+
+        items
+    }
+
+    fn fee_of<'b>(&self, positions: impl IntoIterator<Item = &'b usize>) -> u64 {
+        positions
+            .into_iter()
+            .map(|&position| self.graph.transaction(position).fee())
+            .sum()
+    }
+
+    fn weight_of<'b>(&self, positions: impl IntoIterator<Item = &'b usize>) -> u64 {
+        positions
+            .into_iter()
+            .map(|&position| self.graph.transaction(position).weight() as u64)
+            .sum()
+    }
+
+    fn sigop_cost_of<'b>(&self, positions: impl IntoIterator<Item = &'b usize>) -> u64 {
+        positions
+            .into_iter()
+            .map(|&position| self.graph.transaction(position).sigop_cost())
+            .sum()
+    }
+}
+
+/// A transaction that may be packed into the tail.
+/// `parents` are indexes of earlier items
+#[derive(Debug)]
+struct Item {
+    position: usize,
+    fee: u64,
+    weight: u64,
+    sigop_cost: u64,
+    parents: Vec<usize>,
+}
+
+/// Depth first branch-and-bound over the items in order, deciding for each
+/// one whether it is in the tail. A branch is pruned once the fee of the
+/// fractional knapsack over the undecided items, skipping those whose
+/// parent was left out, cannot beat the best tail found so far. The bound
+/// only looks at weight, dropping the sigop cost limit only loosens it.
+struct Search<'b> {
+    items: &'b [Item],
+    // Item indexes from the best to the worst fee rate, used by the bound
+    by_fee_rate: Vec<usize>,
+    capacity: u64,
+    sigop_capacity: u64,
+    decided: Vec<Option<bool>>,
+    best: Vec<bool>,
+    best_fee: u64,
+    nodes: u64,
+    max_nodes: u64,
+    deadline: Instant,
+    aborted: bool,
+}
+
+impl<'b> Search<'b> {
+    // `capacity` is the weight and sigop cost left for the tail
+    fn new(
+        items: &'b [Item],
+        (capacity, sigop_capacity): (u64, u64),
+        initial: Vec<bool>,
+        config: &SolverConfig,
+    ) -> Self {
+        let mut by_fee_rate = (0..items.len()).collect::<Vec<usize>>();
+        by_fee_rate.sort_by(|&a, &b| {
+            (items[b].fee as u128 * items[a].weight as u128)
+                .cmp(&(items[a].fee as u128 * items[b].weight as u128))
+                .then_with(|| a.cmp(&b))
+        });
+
+        let best_fee = items
+            .iter()
+            .zip(initial.iter())
+            .filter(|(_, &chosen)| chosen)
+            .map(|(item, _)| item.fee)
+            .sum();
+
+        Self {
+            items,
+            by_fee_rate,
+            capacity,
+            sigop_capacity,
+            decided: vec![None; items.len()],
+            best: initial,
+            best_fee,
+            nodes: 0,
+            max_nodes: config.max_nodes,
+            deadline: Instant::now() + config.time_budget,
+            aborted: false,
+        }
+    }
+
+    fn run(&mut self) {
+        self.branch(0, 0, 0, 0);
+    }
+
+    fn branch(&mut self, depth: usize, fee: u64, weight: u64, sigop_cost: u64) {
+        if self.aborted {
+            return;
+        }
+
+        self.nodes += 1;
+        if self.nodes > self.max_nodes
+            || (self.nodes.is_multiple_of(1024) && Instant::now() >= self.deadline)
+        {
+            self.aborted = true;
+            return;
+        }
+
+        if fee > self.best_fee {
+            self.best_fee = fee;
+            self.best = self
+                .decided
+                .iter()
+                .map(|decision| *decision == Some(true))
+                .collect();
+        }
+
+        if depth == self.items.len()
+            || fee + self.bound(depth, self.capacity - weight) <= self.best_fee
+        {
+            return;
+        }
+
+        let item = &self.items[depth];
+        let parents_chosen = item
+            .parents
+            .iter()
+            .all(|&parent| self.decided[parent] == Some(true));
+
+        if parents_chosen
+            && weight + item.weight <= self.capacity
+            && sigop_cost + item.sigop_cost <= self.sigop_capacity
+        {
+            self.decided[depth] = Some(true);
+            self.branch(
+                depth + 1,
+                fee + item.fee,
+                weight + item.weight,
+                sigop_cost + item.sigop_cost,
+            );
+        }
+
+        self.decided[depth] = Some(false);
+        self.branch(depth + 1, fee, weight, sigop_cost);
+        self.decided[depth] = None;
+    }
+
+    // Upper bound on the fee the undecided items can still add
+    fn bound(&self, depth: usize, mut capacity: u64) -> u64 {
+        let mut fee = 0u64;
+
+        for &index in self.by_fee_rate.iter() {
+            let item = &self.items[index];
+            if index < depth
+                || item
+                    .parents
+                    .iter()
+                    .any(|&parent| self.decided[parent] == Some(false))
+            {
+                continue;
+            }
+
+            if item.weight <= capacity {
+                fee += item.fee;
+                capacity -= item.weight;
+            } else {
+                // Round up so the bound never underestimates
+                fee += (item.fee as u128 * capacity as u128).div_ceil(item.weight as u128) as u64;
+                break;
+            }
+        }
+
+        fee
+    }
+}
+
+/// The fee the solver gained over ancestor fee rate selection in each
+/// block of a mempool
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SolverReport {
+    stats: Vec<SolverStats>,
+}
+
+impl SolverReport {
+    /// Project the whole mempool with the solver, keeping its outcome
+    /// for every block
+    pub fn run(graph: &MempoolGraph, limits: BlockLimits, config: SolverConfig) -> Self {
+        let mut selector = BranchAndBoundSelector::new(graph, config);
+        Projection::project(graph, limits, |max_weight, max_sigop_cost| {
+            selector.next_block(max_weight, max_sigop_cost)
+        });
+
+        Self {
+            stats: selector.stats().to_vec(),
+        }
+    }
+
+    pub fn stats(&self) -> &[SolverStats] {
+        self.stats.as_slice()
+    }
+
+    pub fn greedy_fee(&self) -> u64 {
+        self.stats.iter().map(|stats| stats.greedy_fee()).sum()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.stats.iter().map(|stats| stats.fee()).sum()
+    }
+
+    /// Fee gained over ancestor fee rate selection across all blocks
+    pub fn improvement(&self) -> u64 {
+        self.stats.iter().map(|stats| stats.improvement()).sum()
+    }
+}
+
+impl fmt::Display for SolverReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<7} {:>14} {:>14} {:>12} {:>14} {:>10} {:>8}",
+            "block", "greedy fees", "solver fees", "improvement", "upper bound", "nodes", "optimal"
+        )?;
+
+        for (index, stats) in self.stats.iter().enumerate() {
+            writeln!(
+                f,
+                "{:<7} {:>14} {:>14} {:>12} {:>14} {:>10} {:>8}",
+                index + 1,
+                stats.greedy_fee,
+                stats.fee,
+                stats.improvement(),
+                stats.upper_bound,
+                stats.nodes,
+                stats.optimal
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{:<7} {:>14} {:>14} {:>12}",
+            "total",
+            self.greedy_fee(),
+            self.fee(),
+            self.improvement()
+        )
+    }
+}
+
+#[cfg(test)]
+mod branch_and_bound {
+    use crate::{
+        AncestorPackageSelector, BlockLimits, BranchAndBoundSelector, MempoolGraph, Miner,
+        Projection, SolverConfig, SolverReport, Transaction, MAX_BLOCK_SIGOPS_COST,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn beats_greedy_at_the_block_boundary() {
+        // Greedy takes `dense` first after which neither `filler` nor the
+        // `parent` and `child` package fit, while those three fill the block exactly
+        let mempool = vec![
+            Transaction::new("dense", 1300, 1200, &[]),
+            Transaction::new("filler", 1000, 1000, &[]),
+            Transaction::new("parent", 100, 400, &[]),
+            Transaction::new("child", 950, 600, &["parent"]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        let mut greedy = AncestorPackageSelector::new(&graph);
+        assert_eq!(
+            greedy.next_block(2000, MAX_BLOCK_SIGOPS_COST),
+            Some(vec![0, 2])
+        );
+
+        let mut solver = BranchAndBoundSelector::new(&graph, SolverConfig::default());
+        assert_eq!(
+            solver.next_block(2000, MAX_BLOCK_SIGOPS_COST),
+            Some(vec![1, 2, 3])
+        );
+
+        let stats = solver.stats()[0];
+        assert_eq!(stats.greedy_fee(), 1400);
+        assert_eq!(stats.fee(), 2050);
+        assert_eq!(stats.improvement(), 650);
+        assert!(stats.optimal());
+    }
+
+    #[test]
+    fn children_are_only_picked_with_their_parents() {
+        let mempool = vec![
+            Transaction::new("parent", 10, 1000, &[]),
+            Transaction::new("child", 5000, 1000, &["parent"]),
+            Transaction::new("single", 3000, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        let mut solver = BranchAndBoundSelector::new(&graph, SolverConfig::default());
+        assert_eq!(
+            solver.next_block(1500, MAX_BLOCK_SIGOPS_COST),
+            Some(vec![2])
+        );
+        assert_eq!(
+            solver.next_block(2000, MAX_BLOCK_SIGOPS_COST),
+            Some(vec![0, 1])
+        );
+        assert_eq!(solver.next_block(2000, MAX_BLOCK_SIGOPS_COST), None);
+    }
+
+    #[test]
+    fn bundled_mempool_against_greedy() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let graph = MempoolGraph::new(miner.mempool());
+        let limits = BlockLimits::default();
+        // Bounded by nodes rather than time so the result is reproducible
+        let config = SolverConfig::default()
+            .with_max_nodes(20_000)
+            .with_time_budget(Duration::from_secs(600));
+
+        let report = SolverReport::run(&graph, limits, config);
+        for stats in report.stats() {
+            assert!(stats.fee() >= stats.greedy_fee());
+            assert!(stats.fee() <= stats.upper_bound());
+        }
+        assert_eq!(report.fee(), report.greedy_fee() + report.improvement());
+        // Only the second block has a better tail within the node budget
+        assert_eq!(
+            report
+                .stats()
+                .iter()
+                .map(|stats| stats.improvement())
+                .collect::<Vec<u64>>(),
+            vec![0, 6, 0]
+        );
+
+        let greedy = Projection::ancestor_packages(&graph, limits);
+        assert!(report.fee() >= greedy.total_fee());
+        assert!(report.stats()[0].fee() >= greedy.blocks()[0].total_fee());
+        assert!(report
+            .to_string()
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with("total"));
+    }
+}
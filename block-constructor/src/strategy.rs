@@ -0,0 +1,301 @@
+use crate::{BlockLimits, MempoolGraph, Projection, SolverConfig, Transaction};
+use core::fmt;
+use std::time::{Duration, Instant};
+
+/// An algorithm that projects a mempool into consecutive blocks
+pub trait SelectionStrategy {
+    /// Short name used to pick the strategy and label reports
+    fn name(&self) -> &str;
+
+    fn project(&self, graph: &MempoolGraph, limits: BlockLimits) -> Projection;
+}
+
+/// Selection by each transaction's own fee rate, see `GreedySelector`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct GreedyStrategy;
+
+impl SelectionStrategy for GreedyStrategy {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn project(&self, graph: &MempoolGraph, limits: BlockLimits) -> Projection {
+        Projection::greedy(graph, limits)
+    }
+}
+
+/// Selection by ancestor fee rate, kept up to date in an
+/// `IndexedMempool` as packages are mined
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct AncestorPackageStrategy;
+
+impl SelectionStrategy for AncestorPackageStrategy {
+    fn name(&self) -> &str {
+        "ancestor"
+    }
+
+    fn project(&self, graph: &MempoolGraph, limits: BlockLimits) -> Projection {
+        Projection::indexed(graph, limits)
+    }
+}
+
+/// Selection by ancestor fee rate with the end of each block re-packed
+/// by the solver, see `BranchAndBoundSelector`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct BranchAndBoundStrategy {
+    config: SolverConfig,
+}
+
+impl BranchAndBoundStrategy {
+    pub fn new(config: SolverConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &SolverConfig {
+        &self.config
+    }
+}
+
+impl SelectionStrategy for BranchAndBoundStrategy {
+    fn name(&self) -> &str {
+        "solver"
+    }
+
+    fn project(&self, graph: &MempoolGraph, limits: BlockLimits) -> Projection {
+        Projection::branch_and_bound(graph, limits, self.config)
+    }
+}
+
+/// Selection of whole chunks of linearized clusters, see `ChunkSelector`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ClusterStrategy;
+
+impl SelectionStrategy for ClusterStrategy {
+    fn name(&self) -> &str {
+        "cluster"
+    }
+
+    fn project(&self, graph: &MempoolGraph, limits: BlockLimits) -> Projection {
+        Projection::chunks(graph, limits)
+    }
+}
+
+/// Every built-in strategy with its default configuration
+pub fn strategies() -> Vec<Box<dyn SelectionStrategy>> {
+    vec![
+        Box::new(GreedyStrategy),
+        Box::new(AncestorPackageStrategy),
+        Box::new(BranchAndBoundStrategy::default()),
+        Box::new(ClusterStrategy),
+    ]
+}
+
+/// Look up a built-in strategy by its name
+pub fn strategy(name: &str) -> Option<Box<dyn SelectionStrategy>> {
+    strategies()
+        .into_iter()
+        .find(|strategy| strategy.name() == name)
+}
+
+/// How a strategy did on a mempool
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StrategyReport {
+    name: String,
+    blocks: usize,
+    first_block_fee: u64,
+    total_fee: u64,
+    total_weight: u64,
+    tx_count: usize,
+    unmineable: usize,
+    runtime: Duration,
+}
+
+impl StrategyReport {
+    /// Run the strategy on the mempool timing how long the projection takes
+    pub fn run(
+        strategy: &dyn SelectionStrategy,
+        graph: &MempoolGraph,
+        limits: BlockLimits,
+    ) -> Self {
+        let start = Instant::now();
+        let projection = strategy.project(graph, limits);
+        let runtime = start.elapsed();
+
+        let blocks = projection.blocks();
+
+        Self {
+            name: strategy.name().to_owned(),
+            blocks: blocks.len(),
+            first_block_fee: blocks.first().map_or(0, |block| block.total_fee()),
+            total_fee: projection.total_fee(),
+            total_weight: blocks.iter().map(|block| block.weight()).sum(),
+            tx_count: blocks.iter().map(|block| block.positions().len()).sum(),
+            unmineable: projection.unmineable().len(),
+            runtime,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Fee of the next block, where the strategies differ the most
+    /// since every mineable transaction is mined eventually
+    pub fn first_block_fee(&self) -> u64 {
+        self.first_block_fee
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.total_fee
+    }
+
+    /// Weight of all blocks including the header and coinbase reservation
+    pub fn total_weight(&self) -> u64 {
+        self.total_weight
+    }
+
+    pub fn tx_count(&self) -> usize {
+        self.tx_count
+    }
+
+    pub fn unmineable(&self) -> usize {
+        self.unmineable
+    }
+
+    pub fn runtime(&self) -> Duration {
+        self.runtime
+    }
+}
+
+/// Strategies run side by side on the same mempool
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ComparisonReport {
+    reports: Vec<StrategyReport>,
+}
+
+impl ComparisonReport {
+    pub fn run(
+        mempool: &[Transaction],
+        limits: BlockLimits,
+        strategies: &[Box<dyn SelectionStrategy>],
+    ) -> Self {
+        let graph = MempoolGraph::new(mempool);
+
+        Self {
+            reports: strategies
+                .iter()
+                .map(|strategy| StrategyReport::run(strategy.as_ref(), &graph, limits))
+                .collect(),
+        }
+    }
+
+    pub fn reports(&self) -> &[StrategyReport] {
+        self.reports.as_slice()
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>6} {:>14} {:>14} {:>14} {:>8} {:>10} {:>12}",
+            "strategy",
+            "blocks",
+            "block 1 fees",
+            "fees (sats)",
+            "weight (wu)",
+            "txs",
+            "unmineable",
+            "runtime (ms)"
+        )?;
+
+        for report in self.reports.iter() {
+            writeln!(
+                f,
+                "{:<10} {:>6} {:>14} {:>14} {:>14} {:>8} {:>10} {:>12.3}",
+                report.name,
+                report.blocks,
+                report.first_block_fee,
+                report.total_fee,
+                report.total_weight,
+                report.tx_count,
+                report.unmineable,
+                report.runtime.as_secs_f64() * 1000.0
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod strategy_comparison {
+    use crate::{
+        strategies, strategy, BlockLimits, ComparisonReport, MempoolGraph, Miner, Transaction,
+    };
+
+    #[test]
+    fn strategies_are_found_by_name() {
+        for name in ["greedy", "ancestor", "solver", "cluster"] {
+            assert_eq!(strategy(name).unwrap().name(), name);
+        }
+        assert!(strategy("random").is_none());
+    }
+
+    #[test]
+    fn strategies_are_interchangeable() {
+        let mempool = vec![
+            Transaction::new("parent", 10, 1000, &[]),
+            Transaction::new("child", 5000, 1000, &["parent"]),
+            Transaction::new("single", 1500, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let reserved = BlockLimits::default().reserved_weight();
+        let limits = BlockLimits::default().with_max_block_weight(2000 + reserved);
+
+        let first_block_fees = strategies()
+            .iter()
+            .map(|strategy| {
+                let projection = strategy.project(&graph, limits);
+                (
+                    strategy.name().to_owned(),
+                    projection.blocks()[0].total_fee(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            first_block_fees,
+            vec![
+                ("greedy".to_owned(), 1510),
+                ("ancestor".to_owned(), 5010),
+                ("solver".to_owned(), 5010),
+                ("cluster".to_owned(), 5010)
+            ]
+        );
+
+        // Every strategy mines everything eventually
+        let report = ComparisonReport::run(&mempool, limits, &strategies());
+        for strategy in report.reports() {
+            assert_eq!(strategy.total_fee(), 6510);
+            assert!(strategy.first_block_fee() >= 1510);
+            assert_eq!(strategy.tx_count(), 3);
+            assert_eq!(strategy.blocks(), 2);
+        }
+    }
+
+    #[test]
+    fn bundled_mempool_report() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+        let report = ComparisonReport::run(miner.mempool(), BlockLimits::default(), &strategies());
+
+        for strategy in report.reports() {
+            assert_eq!(strategy.tx_count(), 5214);
+            assert_eq!(strategy.unmineable(), 0);
+        }
+        assert_eq!(report.to_string().lines().count(), 5);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::{AncestorPackageSelector, MempoolGraph};
+use core::fmt;
+
+/// Consensus limit on the weight of a block including its header and coinbase
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+/// Most signature operation cost a block may contain
+pub const MAX_BLOCK_SIGOPS_COST: u64 = 80_000;
+/// Sigop cost Bitcoin Core's block assembler reserves for the coinbase
+pub const COINBASE_SIGOPS_COST_RESERVED: u64 = 400;
+/// Size in bytes of a serialized block header
+pub const BLOCK_HEADER_SIZE: u64 = 80;
+/// The smallest weight a transaction can have (60 non-witness bytes)
+pub const MIN_TRANSACTION_WEIGHT: u64 = 4 * 60;
+/// Non-witness bytes multiply by this factor when computing weight
+pub const WITNESS_SCALE_FACTOR: u64 = 4;
+/// The largest coinbase scriptSig allowed by consensus
+pub const MAX_COINBASE_SCRIPT_SIG_SIZE: u64 = 100;
+/// Size of the `OP_RETURN OP_PUSHBYTES_36 0xaa21a9ed <32 byte commitment>` script
+pub const WITNESS_COMMITMENT_SCRIPT_SIZE: u64 = 38;
+
+/// The limits a block template is built against.
+/// The weight available to mempool transactions is what is left of
+/// `max_block_weight` after reserving the 80 byte header, the transaction
+/// count and the coinbase transaction, and the sigop cost available is
+/// what is left of `max_block_sigop_cost` after the coinbase's.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BlockLimits {
+    max_block_weight: u64,
+    max_block_sigop_cost: u64,
+    coinbase_script_sig_size: u64,
+    coinbase_payout_script_size: u64,
+    witness_commitment: bool,
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        Self {
+            max_block_weight: MAX_BLOCK_WEIGHT,
+            max_block_sigop_cost: MAX_BLOCK_SIGOPS_COST,
+            coinbase_script_sig_size: MAX_COINBASE_SCRIPT_SIG_SIZE,
+            // Large enough for P2WSH and P2TR payouts
+            coinbase_payout_script_size: 34,
+            witness_commitment: true,
+        }
+    }
+}
+
+impl BlockLimits {
+    pub fn with_max_block_weight(mut self, max_block_weight: u64) -> Self {
+        self.max_block_weight = max_block_weight;
+
+        self
+    }
+
+    pub fn with_max_block_sigop_cost(mut self, max_block_sigop_cost: u64) -> Self {
+        self.max_block_sigop_cost = max_block_sigop_cost;
+
+        self
+    }
+
+    /// Size of the coinbase scriptSig to reserve space for,
+    /// (BIP34 height, extra nonce and pool tag)
+    pub fn with_coinbase_script_sig_size(mut self, size: u64) -> Self {
+        self.coinbase_script_sig_size = size;
+
+        self
+    }
+
+    /// Size of the script the coinbase pays the block reward to
+    pub fn with_coinbase_payout_script_size(mut self, size: u64) -> Self {
+        self.coinbase_payout_script_size = size;
+
+        self
+    }
+
+    /// Whether the coinbase carries the BIP141 witness commitment output
+    /// and the witness reserved value
+    pub fn with_witness_commitment(mut self, witness_commitment: bool) -> Self {
+        self.witness_commitment = witness_commitment;
+
+        self
+    }
+
+    pub fn max_block_weight(&self) -> u64 {
+        self.max_block_weight
+    }
+
+    pub fn max_block_sigop_cost(&self) -> u64 {
+        self.max_block_sigop_cost
+    }
+
+    pub fn coinbase_script_sig_size(&self) -> u64 {
+        self.coinbase_script_sig_size
+    }
+
+    pub fn coinbase_payout_script_size(&self) -> u64 {
+        self.coinbase_payout_script_size
+    }
+
+    pub fn witness_commitment(&self) -> bool {
+        self.witness_commitment
+    }
+
+    pub fn header_weight(&self) -> u64 {
+        BLOCK_HEADER_SIZE * WITNESS_SCALE_FACTOR
+    }
+
+    /// Weight of the transaction count varint sized for the
+    /// largest number of transactions that can fit into the block
+    pub fn tx_count_weight(&self) -> u64 {
+        varint_size(self.max_block_weight / MIN_TRANSACTION_WEIGHT) * WITNESS_SCALE_FACTOR
+    }
+
+    pub fn coinbase_weight(&self) -> u64 {
+        let payout = output_size(self.coinbase_payout_script_size);
+        let (outputs, commitment) = if self.witness_commitment {
+            (2, output_size(WITNESS_COMMITMENT_SCRIPT_SIZE))
+        } else {
+            (1, 0)
+        };
+
+        let non_witness_size = 4 // version
+            + varint_size(1) // input count
+            + 36 // null prevout
+            + varint_size(self.coinbase_script_sig_size)
+            + self.coinbase_script_sig_size
+            + 4 // sequence
+            + varint_size(outputs)
+            + payout
+            + commitment
+            + 4; // locktime
+
+        // marker, flag and a single 32 byte witness reserved value
+        let witness_size = if self.witness_commitment {
+            2 + varint_size(1) + varint_size(32) + 32
+        } else {
+            0
+        };
+
+        non_witness_size * WITNESS_SCALE_FACTOR + witness_size
+    }
+
+    /// Weight reserved for everything in a block except the mempool transactions
+    pub fn reserved_weight(&self) -> u64 {
+        self.header_weight() + self.tx_count_weight() + self.coinbase_weight()
+    }
+
+    /// Weight available to mempool transactions
+    pub fn max_tx_weight(&self) -> u64 {
+        self.max_block_weight.saturating_sub(self.reserved_weight())
+    }
+
+    /// Sigop cost available to mempool transactions
+    pub fn max_tx_sigop_cost(&self) -> u64 {
+        self.max_block_sigop_cost
+            .saturating_sub(COINBASE_SIGOPS_COST_RESERVED)
+    }
+}
+
+/// Builds consecutive block templates from a mempool
+/// by ancestor fee rate within the configured [BlockLimits]
+#[derive(Debug)]
+pub struct BlockTemplateBuilder<'a> {
+    graph: &'a MempoolGraph<'a>,
+    selector: AncestorPackageSelector<'a>,
+    limits: BlockLimits,
+}
+
+impl<'a> BlockTemplateBuilder<'a> {
+    pub fn new(graph: &'a MempoolGraph<'a>, limits: BlockLimits) -> Self {
+        Self {
+            graph,
+            selector: AncestorPackageSelector::new(graph),
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> &BlockLimits {
+        &self.limits
+    }
+
+    pub fn selector(&self) -> &AncestorPackageSelector<'a> {
+        &self.selector
+    }
+
+    /// Build the next template from the transactions not selected yet.
+    /// Returns `None` once no remaining transaction fits.
+    pub fn next_template(&mut self) -> Option<BlockTemplate> {
+        let positions = self
+            .selector
+            .next_block(self.limits.max_tx_weight(), self.limits.max_tx_sigop_cost())?;
+
+        Some(BlockTemplate::from_positions(
+            self.graph,
+            positions,
+            &self.limits,
+        ))
+    }
+}
+
+/// The transactions selected for a block, in topological order,
+/// referred to by their position in the mempool
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BlockTemplate {
+    positions: Vec<usize>,
+    tx_weight: u64,
+    tx_sigop_cost: u64,
+    total_fee: u64,
+    reserved_weight: u64,
+    binding_limit: BindingLimit,
+}
+
+impl BlockTemplate {
+    pub fn from_positions(
+        graph: &MempoolGraph,
+        positions: Vec<usize>,
+        limits: &BlockLimits,
+    ) -> Self {
+        let transactions = positions
+            .iter()
+            .map(|&position| graph.transaction(position));
+
+        let tx_weight = transactions
+            .clone()
+            .map(|tx| tx.weight() as u64)
+            .sum::<u64>();
+        let tx_sigop_cost = transactions.clone().map(|tx| tx.sigop_cost()).sum::<u64>();
+
+        // The limit the block uses the larger share of stopped it
+        let binding_limit = if tx_sigop_cost as u128 * limits.max_tx_weight() as u128
+            > tx_weight as u128 * limits.max_tx_sigop_cost() as u128
+        {
+            BindingLimit::SigopCost
+        } else {
+            BindingLimit::Weight
+        };
+
+        Self {
+            tx_weight,
+            tx_sigop_cost,
+            total_fee: transactions.map(|tx| tx.fee()).sum::<u64>(),
+            reserved_weight: limits.reserved_weight(),
+            binding_limit,
+            positions,
+        }
+    }
+
+    /// Record that the block holds every mineable transaction left
+    pub(crate) fn with_mempool_exhausted(mut self) -> Self {
+        self.binding_limit = BindingLimit::Mempool;
+
+        self
+    }
+
+    pub fn positions(&self) -> &[usize] {
+        self.positions.as_slice()
+    }
+
+    /// Weight of the selected mempool transactions
+    pub fn tx_weight(&self) -> u64 {
+        self.tx_weight
+    }
+
+    /// Sigop cost of the selected mempool transactions
+    pub fn tx_sigop_cost(&self) -> u64 {
+        self.tx_sigop_cost
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.total_fee
+    }
+
+    /// Weight of the whole block including the header and coinbase reservation
+    pub fn weight(&self) -> u64 {
+        self.tx_weight + self.reserved_weight
+    }
+
+    pub fn binding_limit(&self) -> BindingLimit {
+        self.binding_limit
+    }
+}
+
+/// The limit that kept more transactions out of a block
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BindingLimit {
+    /// The block is fuller by weight than by sigop cost
+    Weight,
+    /// The block is fuller by sigop cost than by weight
+    SigopCost,
+    /// Every mineable transaction left fit into the block
+    Mempool,
+}
+
+impl fmt::Display for BindingLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Weight => write!(f, "weight"),
+            Self::SigopCost => write!(f, "sigop cost"),
+            Self::Mempool => write!(f, "mempool"),
+        }
+    }
+}
+
+/// Size of a Bitcoin `CompactSize` integer
+pub fn varint_size(value: u64) -> u64 {
+    match value {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+fn output_size(script_size: u64) -> u64 {
+    8 + varint_size(script_size) + script_size
+}
+
+#[cfg(test)]
+mod block_limits {
+    use crate::{
+        strategies, BindingLimit, BlockLimits, BlockTemplateBuilder, MempoolGraph, Miner,
+        Transaction, UnmineableReason,
+    };
+
+    #[test]
+    fn default_reservation() {
+        let limits = BlockLimits::default();
+
+        assert_eq!(limits.header_weight(), 320);
+        // Up to 16,666 transactions of 240 weight units need a 3 byte varint
+        assert_eq!(limits.tx_count_weight(), 12);
+        // 241 non-witness bytes and 36 witness bytes
+        assert_eq!(limits.coinbase_weight(), 1000);
+        assert_eq!(limits.max_tx_weight(), 4_000_000 - 1332);
+        assert_eq!(limits.max_tx_sigop_cost(), 80_000 - 400);
+
+        let legacy = limits.with_witness_commitment(false);
+        assert_eq!(legacy.coinbase_weight(), (241 - 47) * 4);
+    }
+
+    #[test]
+    fn templates_respect_the_reservation() {
+        let mempool = vec![
+            Transaction::new("first", 3000, 1000, &[]),
+            Transaction::new("second", 2000, 1000, &[]),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+        let limits = BlockLimits::default().with_max_block_weight(1500 + 1332);
+        let mut builder = BlockTemplateBuilder::new(&graph, limits);
+
+        let template = builder.next_template().unwrap();
+        assert_eq!(template.positions(), &[0]);
+        assert_eq!(template.weight(), 1000 + limits.reserved_weight());
+
+        let template = builder.next_template().unwrap();
+        assert_eq!(template.positions(), &[1]);
+        assert_eq!(builder.next_template(), None);
+    }
+
+    #[test]
+    fn sigop_cost_limits_blocks() {
+        // 100 sigop cost left for transactions, far below the weight limit
+        let limits = BlockLimits::default().with_max_block_sigop_cost(500);
+        let mempool = vec![
+            Transaction::new("dense", 5000, 1000, &[]).with_sigop_cost(90),
+            Transaction::new("first", 3000, 1000, &[]).with_sigop_cost(40),
+            Transaction::new("second", 2000, 1000, &[]).with_sigop_cost(40),
+            Transaction::new("child", 1000, 1000, &["second"]).with_sigop_cost(10),
+            Transaction::new("too-many", 9000, 1000, &[]).with_sigop_cost(101),
+        ];
+        let graph = MempoolGraph::new(&mempool);
+
+        for strategy in strategies() {
+            let projection = strategy.project(&graph, limits);
+            let blocks = projection
+                .blocks()
+                .iter()
+                .map(|block| {
+                    (
+                        block.positions().to_vec(),
+                        block.total_fee(),
+                        block.binding_limit(),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            // Only the solver sees that the three smaller transactions
+            // pay more for the sigop cost `dense` takes up
+            let expected = if strategy.name() == "solver" {
+                vec![
+                    (vec![1, 2, 3], 6000, BindingLimit::SigopCost),
+                    (vec![0], 5000, BindingLimit::Mempool),
+                ]
+            } else {
+                vec![
+                    (vec![0], 5000, BindingLimit::SigopCost),
+                    (vec![1, 2, 3], 6000, BindingLimit::Mempool),
+                ]
+            };
+            assert_eq!(blocks, expected, "{}", strategy.name());
+
+            assert_eq!(
+                projection.unmineable()[0].reason(),
+                &UnmineableReason::ExceedsBlockSigopCost {
+                    sigop_cost: 101,
+                    max_sigop_cost: 100
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn bundled_mempool_fits_with_the_reservation() {
+        let limits = BlockLimits::default();
+        let mut miner = Miner::load_mempool("mempool.csv")
+            .unwrap()
+            .with_limits(limits);
+        miner.mine();
+
+        for block in miner.finalized() {
+            let weight = block.iter().map(|tx| tx.weight() as u64).sum::<u64>();
+            assert!(weight <= limits.max_tx_weight());
+        }
+    }
+}
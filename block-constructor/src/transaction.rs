@@ -0,0 +1,509 @@
+use crate::{Field, LineError, LineErrorKind};
+use core::fmt;
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Hash)]
+pub struct Transaction {
+    txid: String,
+    fee: u64,
+    weight: u32,
+    parent_txids: Vec<String>,
+    sigop_cost: u64,
+    inputs: Vec<OutPoint>,
+    arrival_time: Option<u64>,
+    raw: Option<Vec<u8>>,
+}
+
+/// An output spent by a transaction input
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub struct OutPoint {
+    txid: String,
+    vout: u32,
+}
+
+impl OutPoint {
+    pub fn new(txid: &str, vout: u32) -> Self {
+        Self {
+            txid: txid.to_owned(),
+            vout,
+        }
+    }
+
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn vout(&self) -> u32 {
+        self.vout
+    }
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.txid, self.vout)
+    }
+}
+
+impl Transaction {
+    pub fn new(txid: &str, fee: u64, weight: u32, parent_txids: &[&str]) -> Self {
+        Self {
+            txid: txid.to_owned(),
+            fee,
+            weight,
+            parent_txids: parent_txids
+                .iter()
+                .map(|parent| parent.to_string())
+                .collect(),
+            sigop_cost: 0,
+            inputs: Vec::new(),
+            arrival_time: None,
+            raw: None,
+        }
+    }
+
+    /// Outputs spent by the transaction, empty when unknown
+    pub fn with_inputs(mut self, inputs: Vec<OutPoint>) -> Self {
+        self.inputs = inputs;
+
+        self
+    }
+
+    /// Signature operation cost (legacy sigops count four times),
+    /// zero when unknown
+    pub fn with_sigop_cost(mut self, sigop_cost: u64) -> Self {
+        self.sigop_cost = sigop_cost;
+
+        self
+    }
+
+    /// Seconds after the start of a simulation at which the transaction
+    /// enters the mempool
+    pub fn with_arrival_time(mut self, arrival_time: u64) -> Self {
+        self.arrival_time = Some(arrival_time);
+
+        self
+    }
+
+    /// The serialized transaction including its witness, when it was
+    /// loaded from raw hex rather than a mempool summary
+    pub fn with_raw(mut self, raw: Vec<u8>) -> Self {
+        self.raw = Some(raw);
+
+        self
+    }
+
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn parent_txids(&self) -> &[String] {
+        self.parent_txids.as_slice()
+    }
+
+    pub fn sigop_cost(&self) -> u64 {
+        self.sigop_cost
+    }
+
+    pub fn inputs(&self) -> &[OutPoint] {
+        self.inputs.as_slice()
+    }
+
+    pub fn arrival_time(&self) -> Option<u64> {
+        self.arrival_time
+    }
+
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    /// Parse a `txid,fee,weight,parent;parent` record read from `line`
+    /// of the mempool file, optionally followed by a `,sigop_cost` field,
+    /// a `,txid:vout;txid:vout` field listing the outputs it spends and an
+    /// `,arrival_time` field in seconds
+    pub fn parser(value: &str, line: usize) -> Result<Self, LineError> {
+        let mut outcome = Self::default();
+        let tx_data = value.split(',').collect::<Vec<&str>>();
+
+        let field = |position: usize, field: Field| {
+            tx_data
+                .get(position)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .ok_or(LineError::new(line, field, LineErrorKind::Missing))
+        };
+
+        let txid = field(0, Field::Txid)?;
+        let fee = field(1, Field::Fee)?;
+        let weight = field(2, Field::Weight)?;
+        let parents = tx_data.get(3);
+        let sigop_cost = tx_data
+            .get(4)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty());
+        let inputs = tx_data.get(5);
+        let arrival_time = tx_data
+            .get(6)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty());
+
+        outcome.txid = Self::parse_txid(txid, line, Field::Txid)?;
+        outcome.fee = fee.parse::<u64>().map_err(|error| {
+            LineError::new(
+                line,
+                Field::Fee,
+                LineErrorKind::InvalidInteger {
+                    value: fee.to_owned(),
+                    error,
+                },
+            )
+        })?;
+        outcome.weight = weight.parse::<u32>().map_err(|error| {
+            LineError::new(
+                line,
+                Field::Weight,
+                LineErrorKind::InvalidInteger {
+                    value: weight.to_owned(),
+                    error,
+                },
+            )
+        })?;
+
+        if let Some(sigop_cost) = sigop_cost {
+            outcome.sigop_cost = sigop_cost.parse::<u64>().map_err(|error| {
+                LineError::new(
+                    line,
+                    Field::SigopCost,
+                    LineErrorKind::InvalidInteger {
+                        value: sigop_cost.to_owned(),
+                        error,
+                    },
+                )
+            })?;
+        }
+
+        if let Some(arrival_time) = arrival_time {
+            outcome.arrival_time = Some(arrival_time.parse::<u64>().map_err(|error| {
+                LineError::new(
+                    line,
+                    Field::ArrivalTime,
+                    LineErrorKind::InvalidInteger {
+                        value: arrival_time.to_owned(),
+                        error,
+                    },
+                )
+            })?);
+        }
+
+        if outcome.weight == 0 {
+            return Err(LineError::new(
+                line,
+                Field::Weight,
+                LineErrorKind::ZeroWeight,
+            ));
+        }
+
+        if let Some(parent_exists) = parents {
+            for parent in parent_exists.trim().split(';') {
+                if !parent.trim().is_empty() {
+                    let parent = Self::parse_txid(parent.trim(), line, Field::Parents)?;
+                    outcome.parent_txids.push(parent);
+                }
+            }
+        }
+
+        if let Some(inputs) = inputs {
+            for input in inputs.trim().split(';') {
+                if !input.trim().is_empty() {
+                    outcome
+                        .inputs
+                        .push(Self::parse_outpoint(input.trim(), line, Field::Inputs)?);
+                }
+            }
+        }
+
+        // Reverse the parents order since ancestors of a transaction would need to be in
+        // the mempool for a UTXO to be valid
+        outcome.parent_txids.reverse();
+
+        Ok(outcome)
+    }
+
+    pub(crate) fn parse_outpoint(
+        value: &str,
+        line: usize,
+        field: Field,
+    ) -> Result<OutPoint, LineError> {
+        let invalid = || {
+            LineError::new(
+                line,
+                field,
+                LineErrorKind::InvalidOutpoint {
+                    value: value.to_owned(),
+                },
+            )
+        };
+
+        let (txid, vout) = value.split_once(':').ok_or_else(invalid)?;
+        let vout = vout.parse::<u32>().map_err(|_| invalid())?;
+
+        Ok(OutPoint::new(&Self::parse_txid(txid, line, field)?, vout))
+    }
+
+    fn parse_txid(value: &str, line: usize, field: Field) -> Result<String, LineError> {
+        if is_txid(value) {
+            Ok(value.to_owned())
+        } else {
+            Err(LineError::new(
+                line,
+                field,
+                LineErrorKind::InvalidTxid {
+                    value: value.to_owned(),
+                },
+            ))
+        }
+    }
+}
+
+/// Whether the value is a 64 character hex encoded txid
+pub fn is_txid(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+impl PartialOrd for Transaction {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Transaction {
+    /// Orders transactions by fee rate `(fee / weight)` so that the most
+    /// profitable transaction sorts last and is the first one returned by `pop()`.
+    /// The fee rates are compared exactly by cross-multiplying
+    /// (`self.fee * other.weight` against `other.fee * self.weight`) in `u128`
+    /// since integer division truncates every fee rate below 1 sat/wu to zero.
+    /// Ties are broken by txid (the smaller txid sorts last) and then by the
+    /// remaining fields so that the ordering agrees with the derived `PartialEq`.
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        let self_fee_rate = self.fee as u128 * other.weight as u128;
+        let other_fee_rate = other.fee as u128 * self.weight as u128;
+
+        self_fee_rate
+            .cmp(&other_fee_rate)
+            .then_with(|| other.txid.cmp(&self.txid))
+            .then_with(|| self.fee.cmp(&other.fee))
+            .then_with(|| self.weight.cmp(&other.weight))
+            .then_with(|| self.parent_txids.cmp(&other.parent_txids))
+            .then_with(|| self.sigop_cost.cmp(&other.sigop_cost))
+            .then_with(|| self.inputs.cmp(&other.inputs))
+            .then_with(|| self.arrival_time.cmp(&other.arrival_time))
+            .then_with(|| self.raw.cmp(&other.raw))
+    }
+}
+
+#[cfg(test)]
+mod fee_rate_ordering {
+    use crate::{Miner, Transaction};
+    use std::cmp::Ordering;
+
+    fn tx(txid: &str, fee: u64, weight: u32) -> Transaction {
+        Transaction::new(txid, fee, weight, &[])
+    }
+
+    // `a` pays more per weight unit than `b` when `a.fee * b.weight > b.fee * a.weight`
+    fn exact_cmp(a: &Transaction, b: &Transaction) -> Ordering {
+        (a.fee as u128 * b.weight as u128).cmp(&(b.fee as u128 * a.weight as u128))
+    }
+
+    #[test]
+    fn sub_one_sat_per_wu_is_not_a_tie() {
+        let low = tx("aa", 100, 1000);
+        let high = tx("bb", 900, 1000);
+
+        // The old `fee / weight` comparison truncated both rates to zero
+        assert_eq!(low.fee / low.weight as u64, high.fee / high.weight as u64);
+        assert_eq!(low.cmp(&high), Ordering::Less);
+
+        let mut mempool = vec![high.clone(), low.clone()];
+        mempool.sort();
+        assert_eq!(mempool.pop(), Some(high));
+    }
+
+    #[test]
+    fn equal_fee_rates_tie_break_by_txid() {
+        let first = tx("aa", 500, 1000);
+        let second = tx("bb", 1000, 2000);
+
+        let mut forward = vec![first.clone(), second.clone()];
+        let mut backward = vec![second.clone(), first.clone()];
+        forward.sort();
+        backward.sort();
+
+        assert_eq!(forward, backward);
+        assert_eq!(forward.last(), Some(&first));
+    }
+
+    #[test]
+    fn ordering_agrees_with_eq() {
+        let tx_a = tx("aa", 500, 1000);
+        let tx_b = tx("aa", 1000, 2000);
+
+        assert_ne!(tx_a, tx_b);
+        assert_ne!(tx_a.cmp(&tx_b), Ordering::Equal);
+        assert_eq!(tx_a.cmp(&tx_a.clone()), Ordering::Equal);
+    }
+
+    #[test]
+    fn bundled_mempool_is_sorted_by_exact_fee_rate() {
+        let miner = Miner::load_mempool("mempool.csv").unwrap();
+
+        // Integer division put most of the bundled mempool in the same
+        // zero sat/wu bucket so their order was arbitrary
+        let truncated_to_zero = miner
+            .mempool()
+            .iter()
+            .filter(|tx| tx.fee / tx.weight as u64 == 0)
+            .count();
+        assert!(truncated_to_zero > miner.mempool().len() / 2);
+
+        let mut old_order_ties = 0usize;
+        for pair in miner.mempool().windows(2) {
+            assert_ne!(exact_cmp(&pair[0], &pair[1]), Ordering::Greater);
+
+            if pair[0].fee / pair[0].weight as u64 == pair[1].fee / pair[1].weight as u64
+                && exact_cmp(&pair[0], &pair[1]) == Ordering::Less
+            {
+                old_order_ties += 1;
+            }
+        }
+        // Adjacent transactions the old comparison considered equal
+        // even though one pays strictly more per weight unit
+        assert!(old_order_ties > 0);
+
+        let mut reloaded = Miner::load_mempool("mempool.csv")
+            .unwrap()
+            .mempool()
+            .to_vec();
+        reloaded.reverse();
+        reloaded.sort();
+        assert_eq!(miner.mempool(), reloaded.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod record_parsing {
+    use crate::{Field, LineError, LineErrorKind, OutPoint, Transaction};
+
+    #[test]
+    fn malformed_records_report_the_field() {
+        let txid = "2e3da8fbc1eaca8ed9b7c2db9e6545d8ccac3c67deadee95db050e41c1eedfc0";
+
+        let cases = [
+            ("", Field::Txid, LineErrorKind::Missing),
+            (
+                "not-a-txid,1,1,",
+                Field::Txid,
+                LineErrorKind::InvalidTxid {
+                    value: "not-a-txid".to_owned(),
+                },
+            ),
+            (&format!("{txid},1"), Field::Weight, LineErrorKind::Missing),
+            (
+                &format!("{txid},1,0,"),
+                Field::Weight,
+                LineErrorKind::ZeroWeight,
+            ),
+            (
+                &format!("{txid},1,1,{}", &txid[1..]),
+                Field::Parents,
+                LineErrorKind::InvalidTxid {
+                    value: txid[1..].to_owned(),
+                },
+            ),
+        ];
+
+        for (record, field, kind) in cases {
+            let error = Transaction::parser(record, 7).unwrap_err();
+            assert_eq!(error, LineError::new(7, field, kind));
+        }
+
+        let error = Transaction::parser(&format!("{txid},-1,1,"), 3).unwrap_err();
+        assert_eq!(error.line(), 3);
+        assert_eq!(error.field(), Field::Fee);
+        assert!(matches!(error.kind(), LineErrorKind::InvalidInteger { .. }));
+
+        let error = Transaction::parser(&format!("{txid},1,1,,many"), 4).unwrap_err();
+        assert_eq!(error.field(), Field::SigopCost);
+
+        let error = Transaction::parser(&format!("{txid},1,1,,,{txid}"), 5).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &LineErrorKind::InvalidOutpoint {
+                value: txid.to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn inputs_are_parsed_as_outpoints() {
+        let txid = "2e3da8fbc1eaca8ed9b7c2db9e6545d8ccac3c67deadee95db050e41c1eedfc0";
+        let spent = "b0ef627c8dc2a706475d33d7712209ec779f7a8302aaeab86c64cf00316a3df8";
+
+        let tx =
+            Transaction::parser(&format!("{txid},1,400,{spent},,{spent}:0;{spent}:3"), 1).unwrap();
+        assert_eq!(tx.sigop_cost(), 0);
+        assert_eq!(
+            tx.inputs(),
+            &[OutPoint::new(spent, 0), OutPoint::new(spent, 3)]
+        );
+        assert_eq!(tx.inputs()[1].to_string(), format!("{spent}:3"));
+    }
+
+    #[test]
+    fn sigop_cost_is_optional() {
+        let txid = "2e3da8fbc1eaca8ed9b7c2db9e6545d8ccac3c67deadee95db050e41c1eedfc0";
+
+        assert_eq!(
+            Transaction::parser(&format!("{txid},1,400,"), 1)
+                .unwrap()
+                .sigop_cost(),
+            0
+        );
+        assert_eq!(
+            Transaction::parser(&format!("{txid},1,400,,80"), 1)
+                .unwrap()
+                .sigop_cost(),
+            80
+        );
+    }
+
+    #[test]
+    fn arrival_time_is_optional() {
+        let txid = "2e3da8fbc1eaca8ed9b7c2db9e6545d8ccac3c67deadee95db050e41c1eedfc0";
+
+        assert_eq!(
+            Transaction::parser(&format!("{txid},1,400,,,"), 1)
+                .unwrap()
+                .arrival_time(),
+            None
+        );
+        assert_eq!(
+            Transaction::parser(&format!("{txid},1,400,,,,1200"), 1)
+                .unwrap()
+                .arrival_time(),
+            Some(1200)
+        );
+        assert_eq!(
+            Transaction::parser(&format!("{txid},1,400,,,,soon"), 1)
+                .unwrap_err()
+                .field(),
+            Field::ArrivalTime
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use crate::{BlockLimits, Transaction};
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+
+/// Checks proposed blocks (lists of txids) against a mempool
+/// so that the output of different selection algorithms can be graded
+#[derive(Debug)]
+pub struct BlockValidator<'a> {
+    mempool: HashMap<&'a str, &'a Transaction>,
+    confirmed: HashSet<String>,
+    max_weight: u64,
+    max_sigop_cost: u64,
+}
+
+impl<'a> BlockValidator<'a> {
+    pub fn new(mempool: &'a [Transaction]) -> Self {
+        Self {
+            mempool: mempool.iter().map(|tx| (tx.txid(), tx)).collect(),
+            confirmed: HashSet::new(),
+            max_weight: BlockLimits::default().max_tx_weight(),
+            max_sigop_cost: BlockLimits::default().max_tx_sigop_cost(),
+        }
+    }
+
+    /// Txids already confirmed in previous blocks which may be spent by the block
+    pub fn with_confirmed<'t>(mut self, txids: impl IntoIterator<Item = &'t str>) -> Self {
+        self.confirmed
+            .extend(txids.into_iter().map(|txid| txid.to_owned()));
+
+        self
+    }
+
+    /// The weight limit for the transactions in the block, defaults to
+    /// the weight left after the [BlockLimits] reservation
+    pub fn with_max_weight(mut self, max_weight: u64) -> Self {
+        self.max_weight = max_weight;
+
+        self
+    }
+
+    /// The sigop cost limit for the transactions in the block, defaults
+    /// to what is left after the coinbase's
+    pub fn with_max_sigop_cost(mut self, max_sigop_cost: u64) -> Self {
+        self.max_sigop_cost = max_sigop_cost;
+
+        self
+    }
+
+    /// Validate a single block reporting every violation found
+    pub fn validate<T: AsRef<str>>(&self, block: &[T]) -> BlockReport {
+        let mut report = BlockReport::default();
+
+        let positions = block
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(position, txid)| (txid.as_ref(), position))
+            .collect::<HashMap<&str, usize>>();
+        let mut seen = HashSet::<&str>::new();
+
+        for (position, txid) in block.iter().enumerate() {
+            let txid = txid.as_ref();
+
+            if !seen.insert(txid) {
+                report.violations.push(BlockViolation::DuplicateTxid {
+                    position,
+                    txid: txid.to_owned(),
+                });
+                continue;
+            }
+
+            let tx = match self.mempool.get(txid) {
+                Some(tx) => tx,
+                None => {
+                    report.violations.push(BlockViolation::UnknownTxid {
+                        position,
+                        txid: txid.to_owned(),
+                    });
+                    continue;
+                }
+            };
+
+            report.tx_count += 1;
+            report.total_fee += tx.fee();
+            report.total_weight += tx.weight() as u64;
+            report.total_sigop_cost += tx.sigop_cost();
+
+            for parent_txid in tx.parent_txids() {
+                if self.confirmed.contains(parent_txid) {
+                    continue;
+                }
+
+                match positions.get(parent_txid.as_str()) {
+                    Some(&parent_position) if parent_position < position => (),
+                    Some(&parent_position) => {
+                        report.violations.push(BlockViolation::ParentAfterChild {
+                            position,
+                            txid: txid.to_owned(),
+                            parent_position,
+                            parent_txid: parent_txid.clone(),
+                        })
+                    }
+                    None => report.violations.push(BlockViolation::MissingParent {
+                        position,
+                        txid: txid.to_owned(),
+                        parent_txid: parent_txid.clone(),
+                    }),
+                }
+            }
+        }
+
+        if report.total_weight > self.max_weight {
+            report.violations.push(BlockViolation::WeightExceeded {
+                weight: report.total_weight,
+                max_weight: self.max_weight,
+            });
+        }
+
+        if report.total_sigop_cost > self.max_sigop_cost {
+            report.violations.push(BlockViolation::SigopCostExceeded {
+                sigop_cost: report.total_sigop_cost,
+                max_sigop_cost: self.max_sigop_cost,
+            });
+        }
+
+        report
+    }
+
+    /// Validate consecutive blocks where every block may spend
+    /// the transactions confirmed in the blocks before it
+    pub fn validate_chain<T: AsRef<str>>(mut self, blocks: &[Vec<T>]) -> Vec<BlockReport> {
+        let mut reports = Vec::<BlockReport>::new();
+
+        for block in blocks {
+            reports.push(self.validate(block));
+            self.confirmed
+                .extend(block.iter().map(|txid| txid.as_ref().to_owned()));
+        }
+
+        reports
+    }
+}
+
+/// The outcome of validating a block
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+pub struct BlockReport {
+    violations: Vec<BlockViolation>,
+    tx_count: usize,
+    total_fee: u64,
+    total_weight: u64,
+    total_sigop_cost: u64,
+}
+
+impl BlockReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn violations(&self) -> &[BlockViolation] {
+        self.violations.as_slice()
+    }
+
+    /// Number of known, non duplicate transactions in the block
+    pub fn tx_count(&self) -> usize {
+        self.tx_count
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.total_fee
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.total_weight
+    }
+
+    pub fn total_sigop_cost(&self) -> u64 {
+        self.total_sigop_cost
+    }
+}
+
+impl fmt::Display for BlockReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} transactions, fee: {} sats, weight: {} wu, sigop cost: {}, violations: {}",
+            self.tx_count,
+            self.total_fee,
+            self.total_weight,
+            self.total_sigop_cost,
+            self.violations.len()
+        )?;
+
+        for violation in self.violations.iter() {
+            writeln!(f, "- {}", violation)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A rule broken by a proposed block
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BlockViolation {
+    /// The txid is not in the mempool
+    UnknownTxid { position: usize, txid: String },
+    /// The txid already appeared earlier in the block
+    DuplicateTxid { position: usize, txid: String },
+    /// A parent is included in the block but after its child
+    ParentAfterChild {
+        position: usize,
+        txid: String,
+        parent_position: usize,
+        parent_txid: String,
+    },
+    /// A parent is neither confirmed nor included in the block
+    MissingParent {
+        position: usize,
+        txid: String,
+        parent_txid: String,
+    },
+    /// The transactions in the block weigh more than the block allows
+    WeightExceeded { weight: u64, max_weight: u64 },
+    /// The transactions in the block have more sigop cost than the block allows
+    SigopCostExceeded {
+        sigop_cost: u64,
+        max_sigop_cost: u64,
+    },
+}
+
+impl fmt::Display for BlockViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownTxid { position, txid } => {
+                write!(f, "#{position} {txid} is not in the mempool")
+            }
+            Self::DuplicateTxid { position, txid } => {
+                write!(f, "#{position} {txid} is a duplicate")
+            }
+            Self::ParentAfterChild {
+                position,
+                txid,
+                parent_position,
+                parent_txid,
+            } => write!(
+                f,
+                "#{position} {txid} comes before its parent #{parent_position} {parent_txid}"
+            ),
+            Self::MissingParent {
+                position,
+                txid,
+                parent_txid,
+            } => write!(
+                f,
+                "#{position} {txid} spends {parent_txid} which is neither confirmed nor in the block"
+            ),
+            Self::WeightExceeded { weight, max_weight } => {
+                write!(f, "block weight {weight} exceeds the maximum of {max_weight}")
+            }
+            Self::SigopCostExceeded {
+                sigop_cost,
+                max_sigop_cost,
+            } => write!(
+                f,
+                "block sigop cost {sigop_cost} exceeds the maximum of {max_sigop_cost}"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod block_validation {
+    use crate::{BlockValidator, BlockViolation, Miner, Transaction};
+
+    fn mempool() -> Vec<Transaction> {
+        vec![
+            Transaction::new("parent", 100, 1000, &[]),
+            Transaction::new("child", 900, 3000, &["parent"]).with_sigop_cost(60),
+            Transaction::new("other", 500, 1000, &[]).with_sigop_cost(60),
+        ]
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let mempool = mempool();
+        let validator = BlockValidator::new(&mempool)
+            .with_max_weight(4000)
+            .with_max_sigop_cost(100);
+
+        let report = validator.validate(&["child", "other", "unknown", "parent", "other"]);
+
+        assert!(!report.is_valid());
+        assert_eq!(report.tx_count(), 3);
+        assert_eq!(report.total_fee(), 1500);
+        assert_eq!(report.total_weight(), 5000);
+        assert_eq!(report.total_sigop_cost(), 120);
+        assert_eq!(
+            report.violations(),
+            &[
+                BlockViolation::ParentAfterChild {
+                    position: 0,
+                    txid: "child".to_owned(),
+                    parent_position: 3,
+                    parent_txid: "parent".to_owned(),
+                },
+                BlockViolation::UnknownTxid {
+                    position: 2,
+                    txid: "unknown".to_owned(),
+                },
+                BlockViolation::DuplicateTxid {
+                    position: 4,
+                    txid: "other".to_owned(),
+                },
+                BlockViolation::WeightExceeded {
+                    weight: 5000,
+                    max_weight: 4000,
+                },
+                BlockViolation::SigopCostExceeded {
+                    sigop_cost: 120,
+                    max_sigop_cost: 100,
+                },
+            ]
+        );
+
+        let report = validator.validate(&["child"]);
+        assert_eq!(
+            report.violations(),
+            &[BlockViolation::MissingParent {
+                position: 0,
+                txid: "child".to_owned(),
+                parent_txid: "parent".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn confirmed_parents_may_be_spent() {
+        let mempool = mempool();
+
+        let reports = BlockValidator::new(&mempool)
+            .with_max_weight(4000)
+            .validate_chain(&[vec!["parent", "other"], vec!["child"]]);
+
+        assert!(reports.iter().all(|report| report.is_valid()));
+        assert_eq!(reports[1].total_fee(), 900);
+    }
+
+    #[test]
+    fn mined_bundled_mempool_is_valid() {
+        let mempool = Miner::load_mempool("mempool.csv")
+            .unwrap()
+            .mempool()
+            .to_vec();
+        let mut miner = Miner::load_mempool("mempool.csv").unwrap();
+        miner.mine();
+
+        let blocks = miner
+            .finalized()
+            .iter()
+            .map(|block| block.iter().map(|tx| tx.txid()).collect::<Vec<&str>>())
+            .collect::<Vec<Vec<&str>>>();
+
+        let reports = BlockValidator::new(&mempool).validate_chain(&blocks);
+
+        assert!(reports.iter().all(|report| report.is_valid()));
+        assert_eq!(
+            reports.iter().map(|report| report.total_fee()).sum::<u64>(),
+            mempool.iter().map(|tx| tx.fee()).sum::<u64>()
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use crate::{MempoolGraph, Projection, WITNESS_SCALE_FACTOR};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Fill colors by fee rate, each used below its fee rate in sat/vB and
+/// the last one above them all
+pub const FEE_RATE_COLORS: [(f64, &str); 8] = [
+    (2.0, "#3288bd"),
+    (5.0, "#66c2a5"),
+    (10.0, "#abdda4"),
+    (20.0, "#e6f598"),
+    (50.0, "#fee08b"),
+    (100.0, "#fdae61"),
+    (200.0, "#f46d43"),
+    (f64::INFINITY, "#d53e4f"),
+];
+
+/// The mempool dependency graph annotated with where a projection put
+/// each transaction, exported as Graphviz DOT or a JSON node and edge
+/// list. Nodes are colored by their own fee rate and also carry their
+/// ancestor fee rate, which is what decides when a child is mined.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct MempoolVisualization {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+impl MempoolVisualization {
+    pub fn new(graph: &MempoolGraph, projection: &Projection) -> Self {
+        let mut blocks = vec![None; graph.len()];
+        for (index, block) in projection.blocks().iter().enumerate() {
+            for &position in block.positions() {
+                blocks[position] = Some(index + 1);
+            }
+        }
+
+        let mut unmineable = vec![None; graph.len()];
+        for tx in projection.unmineable() {
+            unmineable[tx.position()] = Some(tx.reason().to_string());
+        }
+
+        let nodes = (0..graph.len())
+            .map(|position| {
+                let tx = graph.transaction(position);
+                let ancestors = graph.ancestors(position);
+                let (ancestor_fee, ancestor_weight) = ancestors
+                    .iter()
+                    .filter(|&&ancestor| ancestor != position)
+                    .map(|&ancestor| graph.transaction(ancestor))
+                    .fold((tx.fee(), tx.weight() as u64), |(fee, weight), ancestor| {
+                        (fee + ancestor.fee(), weight + ancestor.weight() as u64)
+                    });
+                let own_fee_rate = fee_rate(tx.fee(), tx.weight() as u64);
+
+                GraphNode {
+                    txid: tx.txid().to_owned(),
+                    fee: tx.fee(),
+                    weight: tx.weight(),
+                    fee_rate: own_fee_rate,
+                    ancestor_fee_rate: fee_rate(ancestor_fee, ancestor_weight),
+                    color: fee_rate_color(own_fee_rate).to_owned(),
+                    block: blocks[position],
+                    unmineable: unmineable[position].take(),
+                }
+            })
+            .collect();
+
+        let edges = (0..graph.len())
+            .flat_map(|position| {
+                graph
+                    .parents(position)
+                    .iter()
+                    .map(move |&parent| GraphEdge {
+                        parent: graph.transaction(parent).txid().to_owned(),
+                        child: graph.transaction(position).txid().to_owned(),
+                    })
+            })
+            .collect();
+
+        Self { nodes, edges }
+    }
+
+    /// Transactions in mempool order
+    pub fn nodes(&self) -> &[GraphNode] {
+        self.nodes.as_slice()
+    }
+
+    /// Parent to child links between the transactions of the mempool
+    pub fn edges(&self) -> &[GraphEdge] {
+        self.edges.as_slice()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("mempool graph serializes to JSON")
+    }
+
+    /// A digraph with an edge from each parent to its children and a
+    /// cluster per projected block. Unmineable transactions are left out
+    /// of the clusters with a dashed red border and the reason as tooltip.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let blocks = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.block)
+            .max()
+            .unwrap_or(0);
+
+        // Writing to a `String` never fails
+        writeln!(dot, "digraph mempool {{").unwrap();
+        writeln!(dot, "  rankdir=LR;").unwrap();
+        writeln!(dot, "  node [shape=box, style=filled, fontname=monospace];").unwrap();
+
+        for block in 1..=blocks {
+            writeln!(dot, "  subgraph cluster_block_{block} {{").unwrap();
+            writeln!(dot, "    label=\"Block {block}\";").unwrap();
+            for node in self.nodes.iter().filter(|node| node.block == Some(block)) {
+                writeln!(dot, "    {}", node.to_dot()).unwrap();
+            }
+            writeln!(dot, "  }}").unwrap();
+        }
+
+        for node in self.nodes.iter().filter(|node| node.block.is_none()) {
+            writeln!(dot, "  {}", node.to_dot()).unwrap();
+        }
+
+        for edge in self.edges.iter() {
+            writeln!(dot, "  {} -> {};", quote(&edge.parent), quote(&edge.child)).unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+
+        dot
+    }
+}
+
+/// A transaction of the mempool graph
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct GraphNode {
+    txid: String,
+    fee: u64,
+    weight: u32,
+    fee_rate: f64,
+    ancestor_fee_rate: f64,
+    color: String,
+    block: Option<usize>,
+    unmineable: Option<String>,
+}
+
+impl GraphNode {
+    pub fn txid(&self) -> &str {
+        self.txid.as_str()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// Fee rate in sat/vB
+    pub fn fee_rate(&self) -> f64 {
+        self.fee_rate
+    }
+
+    /// Fee rate in sat/vB of the transaction together with its ancestors
+    pub fn ancestor_fee_rate(&self) -> f64 {
+        self.ancestor_fee_rate
+    }
+
+    /// Fill color picked from `FEE_RATE_COLORS`
+    pub fn color(&self) -> &str {
+        self.color.as_str()
+    }
+
+    /// 1-based index of the projected block holding the transaction
+    pub fn block(&self) -> Option<usize> {
+        self.block
+    }
+
+    /// Why the transaction is in no block
+    pub fn unmineable(&self) -> Option<&str> {
+        self.unmineable.as_deref()
+    }
+
+    fn to_dot(&self) -> String {
+        // Long txids are shortened in the label and kept whole as node id
+        let short_txid = match self.txid.get(..16) {
+            Some(prefix) if self.txid.len() > 16 => format!("{prefix}…"),
+            _ => self.txid.clone(),
+        };
+        let label = format!(
+            "{short_txid}\\n{:.2} sat/vB\\nancestors {:.2} sat/vB",
+            self.fee_rate, self.ancestor_fee_rate
+        );
+
+        match &self.unmineable {
+            Some(reason) => format!(
+                "{} [label={}, fillcolor=\"{}\", color=red, style=\"filled,dashed\", tooltip={}];",
+                quote(&self.txid),
+                quote(&label),
+                self.color,
+                quote(reason)
+            ),
+            None => format!(
+                "{} [label={}, fillcolor=\"{}\"];",
+                quote(&self.txid),
+                quote(&label),
+                self.color
+            ),
+        }
+    }
+}
+
+/// A child spending an output of its parent
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct GraphEdge {
+    parent: String,
+    child: String,
+}
+
+impl GraphEdge {
+    pub fn parent(&self) -> &str {
+        self.parent.as_str()
+    }
+
+    pub fn child(&self) -> &str {
+        self.child.as_str()
+    }
+}
+
+/// Color of a fee rate in sat/vB
+pub fn fee_rate_color(fee_rate: f64) -> &'static str {
+    FEE_RATE_COLORS
+        .iter()
+        .find(|&&(below, _)| fee_rate < below)
+        .map_or(
+            FEE_RATE_COLORS[FEE_RATE_COLORS.len() - 1].1,
+            |&(_, color)| color,
+        )
+}
+
+fn fee_rate(fee: u64, weight: u64) -> f64 {
+    (fee * WITNESS_SCALE_FACTOR) as f64 / weight.max(1) as f64
+}
+
+// A DOT string literal, `label` escapes such as `\n` are kept
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod mempool_visualization {
+    use crate::{
+        fee_rate_color, BlockLimits, MempoolGraph, MempoolVisualization, Projection, Transaction,
+    };
+
+    fn mempool() -> Vec<Transaction> {
+        vec![
+            Transaction::new("parent", 400, 400, &[]),
+            Transaction::new("child", 20_000, 400, &["parent"]),
+            Transaction::new("heavy", 4_000, 3_000, &[]),
+            Transaction::new("orphan", 1_000, 400, &["gone"]),
+        ]
+    }
+
+    #[test]
+    fn nodes_carry_fee_rates_blocks_and_reasons() {
+        let mempool = mempool();
+        let graph = MempoolGraph::new(&mempool);
+        let limits = BlockLimits::default()
+            .with_max_block_weight(BlockLimits::default().reserved_weight() + 3_000);
+        let visualization =
+            MempoolVisualization::new(&graph, &Projection::ancestor_packages(&graph, limits));
+
+        let nodes = visualization.nodes();
+        assert_eq!(nodes[0].fee_rate(), 4.0);
+        assert_eq!(nodes[0].color(), fee_rate_color(4.0));
+        assert_eq!(nodes[1].fee_rate(), 200.0);
+        assert_eq!(nodes[1].ancestor_fee_rate(), 102.0);
+        assert_eq!(nodes[1].color(), "#d53e4f");
+
+        // The parent and child package outbids the heavy transaction
+        assert_eq!(nodes[0].block(), Some(1));
+        assert_eq!(nodes[1].block(), Some(1));
+        assert_eq!(nodes[2].block(), Some(2));
+        assert_eq!(nodes[3].block(), None);
+        assert!(nodes[3].unmineable().unwrap().contains("gone"));
+
+        assert_eq!(visualization.edges().len(), 1);
+        assert_eq!(visualization.edges()[0].parent(), "parent");
+        assert_eq!(visualization.edges()[0].child(), "child");
+
+        let json = serde_json::from_str::<serde_json::Value>(&visualization.to_json()).unwrap();
+        assert_eq!(json["nodes"][2]["block"], 2);
+        assert!(json["nodes"][3]["block"].is_null());
+        assert_eq!(json["edges"][0]["child"], "child");
+    }
+
+    #[test]
+    fn dot_clusters_blocks_and_marks_unmineable() {
+        let mempool = mempool();
+        let graph = MempoolGraph::new(&mempool);
+        let visualization = MempoolVisualization::new(
+            &graph,
+            &Projection::ancestor_packages(&graph, BlockLimits::default()),
+        );
+        let dot = visualization.to_dot();
+
+        assert!(dot.starts_with("digraph mempool {"));
+        assert!(dot.contains("subgraph cluster_block_1 {"));
+        assert!(!dot.contains("cluster_block_2"));
+        assert!(dot.contains("  \"parent\" -> \"child\";"));
+        assert!(dot.contains(
+            "  \"orphan\" [label=\"orphan\\n10.00 sat/vB\\nancestors 10.00 sat/vB\", \
+             fillcolor=\"#e6f598\", color=red, style=\"filled,dashed\", \
+             tooltip=\"spends gone which is missing from the mempool\"];"
+        ));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+}
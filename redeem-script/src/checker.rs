@@ -0,0 +1,456 @@
+use bitcoin::{
+    consensus::Encodable,
+    hashes::{sha256d, Hash},
+    sighash::SighashCache,
+    Amount, Script, Sequence, Transaction,
+};
+
+/// Bitcoin Core's `LOCKTIME_THRESHOLD`, lock times below it are heights
+pub const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+/// `nSequence` bit disabling its relative lock time (BIP68)
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+/// `nSequence` bit making its relative lock time count 512 seconds units
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: i64 = 1 << 22;
+/// `nSequence` bits holding the relative lock time
+pub const SEQUENCE_LOCKTIME_MASK: i64 = 0x0000_ffff;
+
+/// Which signature hash a script is checked under
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SigVersion {
+    /// scriptSig, scriptPubKey and P2SH redeem scripts
+    Base,
+    /// Segwit v0 witness scripts, signed with BIP143
+    WitnessV0,
+}
+
+/// What the interpreter needs to know about the spending transaction.
+/// `sighash` is the callback `OP_CHECKSIG` and `OP_CHECKMULTISIG` hash
+/// the transaction with, the interpreter verifies the ECDSA signature
+/// against the hash it returns.
+pub trait SignatureChecker {
+    /// The message a signature with `sighash_type` commits to, `None` if
+    /// there is nothing to sign. For `SigVersion::Base` the signature and
+    /// `OP_CODESEPARATOR`s have already been removed from `script_code`.
+    fn sighash(
+        &self,
+        script_code: &Script,
+        sighash_type: u32,
+        sig_version: SigVersion,
+    ) -> Option<[u8; 32]>;
+
+    /// Whether the transaction is locked until at least `lock_time`
+    fn check_lock_time(&self, lock_time: i64) -> bool;
+
+    /// Whether the input is locked for at least the relative `sequence`
+    fn check_sequence(&self, sequence: i64) -> bool;
+}
+
+/// A checker without a transaction, every signature and lock time fails
+/// as in Bitcoin Core's `BaseSignatureChecker`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct BaseSignatureChecker;
+
+impl SignatureChecker for BaseSignatureChecker {
+    fn sighash(&self, _: &Script, _: u32, _: SigVersion) -> Option<[u8; 32]> {
+        None
+    }
+
+    fn check_lock_time(&self, _: i64) -> bool {
+        false
+    }
+
+    fn check_sequence(&self, _: i64) -> bool {
+        false
+    }
+}
+
+/// Checks the signatures and lock times of one input of a transaction
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TransactionSignatureChecker<'a> {
+    tx: &'a Transaction,
+    input_index: usize,
+    amount: Amount,
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+    /// `amount` is the value of the output the input spends, which
+    /// segwit signatures commit to
+    pub fn new(tx: &'a Transaction, input_index: usize, amount: Amount) -> Self {
+        Self {
+            tx,
+            input_index,
+            amount,
+        }
+    }
+
+    pub fn tx(&self) -> &Transaction {
+        self.tx
+    }
+
+    pub fn input_index(&self) -> usize {
+        self.input_index
+    }
+
+    pub fn amount(&self) -> Amount {
+        self.amount
+    }
+
+    // BIP143 hashing the raw `sighash_type` the way Bitcoin Core does,
+    // rust-bitcoin only takes the standard types
+    fn segwit_v0_sighash(&self, script_code: &Script, sighash_type: u32) -> Option<[u8; 32]> {
+        let input = self.tx.input.get(self.input_index)?;
+        let base_type = sighash_type & 0x1f;
+        let anyone_can_pay = sighash_type & 0x80 != 0;
+        let (none, single) = (base_type == 0x02, base_type == 0x03);
+
+        let hash = |write: &dyn Fn(&mut Vec<u8>)| {
+            let mut data = Vec::<u8>::new();
+            write(&mut data);
+            sha256d::Hash::hash(&data)
+        };
+        let hash_prevouts = if anyone_can_pay {
+            sha256d::Hash::all_zeros()
+        } else {
+            hash(&|data| {
+                for input in self.tx.input.iter() {
+                    input.previous_output.consensus_encode(data).unwrap();
+                }
+            })
+        };
+        let hash_sequence = if anyone_can_pay || none || single {
+            sha256d::Hash::all_zeros()
+        } else {
+            hash(&|data| {
+                for input in self.tx.input.iter() {
+                    input.sequence.consensus_encode(data).unwrap();
+                }
+            })
+        };
+        let hash_outputs = if !none && !single {
+            hash(&|data| {
+                for output in self.tx.output.iter() {
+                    output.consensus_encode(data).unwrap();
+                }
+            })
+        } else if single && self.input_index < self.tx.output.len() {
+            hash(&|data| {
+                self.tx.output[self.input_index]
+                    .consensus_encode(data)
+                    .unwrap();
+            })
+        } else {
+            sha256d::Hash::all_zeros()
+        };
+
+        // Encoding into a `Vec` never fails
+        let mut preimage = Vec::<u8>::new();
+        self.tx.version.consensus_encode(&mut preimage).unwrap();
+        hash_prevouts.consensus_encode(&mut preimage).unwrap();
+        hash_sequence.consensus_encode(&mut preimage).unwrap();
+        input
+            .previous_output
+            .consensus_encode(&mut preimage)
+            .unwrap();
+        script_code.consensus_encode(&mut preimage).unwrap();
+        self.amount.consensus_encode(&mut preimage).unwrap();
+        input.sequence.consensus_encode(&mut preimage).unwrap();
+        hash_outputs.consensus_encode(&mut preimage).unwrap();
+        self.tx.lock_time.consensus_encode(&mut preimage).unwrap();
+        sighash_type.consensus_encode(&mut preimage).unwrap();
+
+        Some(sha256d::Hash::hash(&preimage).to_byte_array())
+    }
+}
+
+impl SignatureChecker for TransactionSignatureChecker<'_> {
+    fn sighash(
+        &self,
+        script_code: &Script,
+        sighash_type: u32,
+        sig_version: SigVersion,
+    ) -> Option<[u8; 32]> {
+        match sig_version {
+            SigVersion::Base => SighashCache::new(self.tx)
+                .legacy_signature_hash(self.input_index, script_code, sighash_type)
+                .ok()
+                .map(|sighash| sighash.to_byte_array()),
+            SigVersion::WitnessV0 => self.segwit_v0_sighash(script_code, sighash_type),
+        }
+    }
+
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        let tx_lock_time = self.tx.lock_time.to_consensus_u32() as i64;
+
+        // Heights can only be compared with heights and times with times
+        if (tx_lock_time < LOCKTIME_THRESHOLD) != (lock_time < LOCKTIME_THRESHOLD) {
+            return false;
+        }
+        if lock_time > tx_lock_time {
+            return false;
+        }
+
+        // A final input opts out of the transaction's lock time
+        self.tx
+            .input
+            .get(self.input_index)
+            .is_some_and(|input| input.sequence != Sequence::MAX)
+    }
+
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let Some(input) = self.tx.input.get(self.input_index) else {
+            return false;
+        };
+        let tx_sequence = input.sequence.to_consensus_u32() as i64;
+
+        // Relative lock times need BIP68, from version 2 on
+        if (self.tx.version.0 as u32) < 2 {
+            return false;
+        }
+        if tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return false;
+        }
+
+        let mask = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
+        let (tx_sequence, sequence) = (tx_sequence & mask, sequence & mask);
+        if (tx_sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) != (sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) {
+            return false;
+        }
+
+        sequence <= tx_sequence
+    }
+}
+
+#[cfg(test)]
+mod signature_checking {
+    use crate::{
+        Interpreter, ScriptError, SigVersion, SignatureChecker, TransactionSignatureChecker,
+    };
+    use bitcoin::{
+        absolute::LockTime,
+        hashes::Hash,
+        opcodes::all::OP_CHECKMULTISIG,
+        script::{Builder, PushBytesBuf},
+        secp256k1::{All, Message, Secp256k1, SecretKey},
+        sighash::{EcdsaSighashType, SighashCache},
+        transaction::Version,
+        Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    };
+
+    const AMOUNT: Amount = Amount::from_sat(100_000);
+
+    fn keys(secp: &Secp256k1<All>) -> Vec<(SecretKey, PublicKey)> {
+        (1..=3)
+            .map(|byte| {
+                let secret_key = SecretKey::from_slice(&[byte; 32]).unwrap();
+                (secret_key, PublicKey::new(secret_key.public_key(secp)))
+            })
+            .collect()
+    }
+
+    fn spending_transaction() -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: AMOUNT - Amount::from_sat(1_000),
+                script_pubkey: ScriptBuf::new_op_return([]),
+            }],
+        }
+    }
+
+    // A DER signature with the sighash type appended
+    fn sign(
+        secp: &Secp256k1<All>,
+        secret_key: &SecretKey,
+        sighash: [u8; 32],
+        sighash_type: u8,
+    ) -> Vec<u8> {
+        let mut signature = secp
+            .sign_ecdsa(&Message::from_digest(sighash), secret_key)
+            .serialize_der()
+            .to_vec();
+        signature.push(sighash_type);
+
+        signature
+    }
+
+    fn push(data: &[u8]) -> PushBytesBuf {
+        PushBytesBuf::try_from(data.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn verifies_p2pkh_signatures() {
+        let secp = Secp256k1::new();
+        let (secret_key, public_key) = keys(&secp).remove(0);
+        let script_pubkey = ScriptBuf::new_p2pkh(&public_key.pubkey_hash());
+
+        let mut tx = spending_transaction();
+        let sighash = SighashCache::new(&tx)
+            .legacy_signature_hash(0, &script_pubkey, 0x01)
+            .unwrap();
+        let signature = sign(&secp, &secret_key, sighash.to_byte_array(), 0x01);
+        tx.input[0].script_sig = Builder::new()
+            .push_slice(push(&signature))
+            .push_key(&public_key)
+            .into_script();
+
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT);
+        let result = Interpreter::new(&checker).verify(
+            &tx.input[0].script_sig,
+            &script_pubkey,
+            &Witness::new(),
+        );
+        assert_eq!(result, Ok(()));
+
+        // The signature commits to the outputs
+        let mut tampered = tx.clone();
+        tampered.output[0].value = AMOUNT;
+        let checker = TransactionSignatureChecker::new(&tampered, 0, AMOUNT);
+        let result = Interpreter::new(&checker).verify(
+            &tampered.input[0].script_sig,
+            &script_pubkey,
+            &Witness::new(),
+        );
+        assert_eq!(result, Err(ScriptError::SigNullFail));
+    }
+
+    #[test]
+    fn verifies_p2wpkh_signatures_against_the_amount() {
+        let secp = Secp256k1::new();
+        let (secret_key, public_key) = keys(&secp).remove(0);
+        let script_pubkey = ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash().unwrap());
+
+        let mut tx = spending_transaction();
+        let sighash = SighashCache::new(&tx)
+            .p2wpkh_signature_hash(0, &script_pubkey, AMOUNT, EcdsaSighashType::All)
+            .unwrap();
+        let signature = sign(&secp, &secret_key, sighash.to_byte_array(), 0x01);
+        tx.input[0].witness = Witness::from_slice(&[signature, public_key.to_bytes()]);
+
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT);
+        let result = Interpreter::new(&checker).verify(
+            &ScriptBuf::new(),
+            &script_pubkey,
+            &tx.input[0].witness,
+        );
+        assert_eq!(result, Ok(()));
+
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT + Amount::from_sat(1));
+        let result = Interpreter::new(&checker).verify(
+            &ScriptBuf::new(),
+            &script_pubkey,
+            &tx.input[0].witness,
+        );
+        assert_eq!(result, Err(ScriptError::SigNullFail));
+    }
+
+    #[test]
+    fn verifies_p2sh_multisig_in_key_order() {
+        let secp = Secp256k1::new();
+        let keys = keys(&secp);
+        let redeem_script = keys
+            .iter()
+            .fold(Builder::new().push_int(2), |builder, (_, public_key)| {
+                builder.push_key(public_key)
+            })
+            .push_int(3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let script_pubkey = redeem_script.to_p2sh();
+
+        let mut tx = spending_transaction();
+        let sighash = SighashCache::new(&tx)
+            .legacy_signature_hash(0, &redeem_script, 0x01)
+            .unwrap()
+            .to_byte_array();
+        let first = sign(&secp, &keys[0].0, sighash, 0x01);
+        let third = sign(&secp, &keys[2].0, sighash, 0x01);
+
+        let script_sig = |signatures: [&[u8]; 2]| {
+            signatures
+                .iter()
+                .fold(Builder::new().push_int(0), |builder, signature| {
+                    builder.push_slice(push(signature))
+                })
+                .push_slice(push(redeem_script.as_bytes()))
+                .into_script()
+        };
+
+        tx.input[0].script_sig = script_sig([&first, &third]);
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT);
+        let result = Interpreter::new(&checker).verify(
+            &tx.input[0].script_sig,
+            &script_pubkey,
+            &Witness::new(),
+        );
+        assert_eq!(result, Ok(()));
+
+        // Signatures must come in the order of their keys
+        tx.input[0].script_sig = script_sig([&third, &first]);
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT);
+        let result = Interpreter::new(&checker).verify(
+            &tx.input[0].script_sig,
+            &script_pubkey,
+            &Witness::new(),
+        );
+        assert_eq!(result, Err(ScriptError::SigNullFail));
+    }
+
+    #[test]
+    fn segwit_sighash_matches_bip143() {
+        let tx = spending_transaction();
+        let witness_script = Builder::new().push_int(1).into_script();
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT);
+
+        for sighash_type in [
+            EcdsaSighashType::All,
+            EcdsaSighashType::None,
+            EcdsaSighashType::Single,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+            EcdsaSighashType::NonePlusAnyoneCanPay,
+            EcdsaSighashType::SinglePlusAnyoneCanPay,
+        ] {
+            let expected = SighashCache::new(&tx)
+                .p2wsh_signature_hash(0, &witness_script, AMOUNT, sighash_type)
+                .unwrap();
+            assert_eq!(
+                checker.sighash(
+                    &witness_script,
+                    sighash_type.to_u32(),
+                    SigVersion::WitnessV0
+                ),
+                Some(expected.to_byte_array())
+            );
+        }
+    }
+
+    #[test]
+    fn checks_absolute_and_relative_lock_times() {
+        let mut tx = spending_transaction();
+        tx.lock_time = LockTime::from_consensus(500);
+        tx.input[0].sequence = Sequence::from_height(10);
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT);
+
+        assert!(checker.check_lock_time(500));
+        assert!(!checker.check_lock_time(501));
+        // A time can't be compared with a height
+        assert!(!checker.check_lock_time(500_000_000));
+
+        assert!(checker.check_sequence(10));
+        assert!(!checker.check_sequence(11));
+        assert!(!checker
+            .check_sequence(Sequence::from_512_second_intervals(1).to_consensus_u32() as i64));
+
+        // Final inputs opt out of the lock time
+        tx.input[0].sequence = Sequence::MAX;
+        let checker = TransactionSignatureChecker::new(&tx, 0, AMOUNT);
+        assert!(!checker.check_lock_time(500));
+        assert!(!checker.check_sequence(10));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{HashType, OutputType, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_ELEMENT_SIZE};
+use core::fmt;
+
+/// Why a script failed, one variant per `SCRIPT_ERR_*` of Bitcoin Core
+/// that can be raised without taproot
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptError {
+    /// A script number was longer than allowed or not minimally encoded
+    UnknownError,
+    EvalFalse,
+    OpReturn,
+    ScriptSize,
+    PushSize,
+    OpCount,
+    StackSize,
+    SigCount,
+    PubkeyCount,
+    Verify,
+    EqualVerify,
+    CheckMultisigVerify,
+    CheckSigVerify,
+    NumEqualVerify,
+    BadOpcode,
+    DisabledOpcode,
+    InvalidStackOperation,
+    InvalidAltstackOperation,
+    UnbalancedConditional,
+    NegativeLocktime,
+    UnsatisfiedLocktime,
+    SigHashtype,
+    SigDer,
+    MinimalData,
+    SigPushOnly,
+    SigHighS,
+    SigNullDummy,
+    PubkeyType,
+    CleanStack,
+    MinimalIf,
+    SigNullFail,
+    DiscourageUpgradableNops,
+    DiscourageUpgradableWitnessProgram,
+    WitnessProgramWrongLength,
+    WitnessProgramWitnessEmpty,
+    WitnessProgramMismatch,
+    WitnessMalleated,
+    WitnessMalleatedP2sh,
+    WitnessUnexpected,
+    WitnessPubkeyType,
+    OpCodeSeparator,
+    SigFindAndDelete,
+}
+
+impl ScriptError {
+    /// The name of the error in Bitcoin Core's `script_tests.json`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownError => "UNKNOWN_ERROR",
+            Self::EvalFalse => "EVAL_FALSE",
+            Self::OpReturn => "OP_RETURN",
+            Self::ScriptSize => "SCRIPT_SIZE",
+            Self::PushSize => "PUSH_SIZE",
+            Self::OpCount => "OP_COUNT",
+            Self::StackSize => "STACK_SIZE",
+            Self::SigCount => "SIG_COUNT",
+            Self::PubkeyCount => "PUBKEY_COUNT",
+            Self::Verify => "VERIFY",
+            Self::EqualVerify => "EQUALVERIFY",
+            Self::CheckMultisigVerify => "CHECKMULTISIGVERIFY",
+            Self::CheckSigVerify => "CHECKSIGVERIFY",
+            Self::NumEqualVerify => "NUMEQUALVERIFY",
+            Self::BadOpcode => "BAD_OPCODE",
+            Self::DisabledOpcode => "DISABLED_OPCODE",
+            Self::InvalidStackOperation => "INVALID_STACK_OPERATION",
+            Self::InvalidAltstackOperation => "INVALID_ALTSTACK_OPERATION",
+            Self::UnbalancedConditional => "UNBALANCED_CONDITIONAL",
+            Self::NegativeLocktime => "NEGATIVE_LOCKTIME",
+            Self::UnsatisfiedLocktime => "UNSATISFIED_LOCKTIME",
+            Self::SigHashtype => "SIG_HASHTYPE",
+            Self::SigDer => "SIG_DER",
+            Self::MinimalData => "MINIMALDATA",
+            Self::SigPushOnly => "SIG_PUSHONLY",
+            Self::SigHighS => "SIG_HIGH_S",
+            Self::SigNullDummy => "SIG_NULLDUMMY",
+            Self::PubkeyType => "PUBKEYTYPE",
+            Self::CleanStack => "CLEANSTACK",
+            Self::MinimalIf => "MINIMALIF",
+            Self::SigNullFail => "NULLFAIL",
+            Self::DiscourageUpgradableNops => "DISCOURAGE_UPGRADABLE_NOPS",
+            Self::DiscourageUpgradableWitnessProgram => "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM",
+            Self::WitnessProgramWrongLength => "WITNESS_PROGRAM_WRONG_LENGTH",
+            Self::WitnessProgramWitnessEmpty => "WITNESS_PROGRAM_WITNESS_EMPTY",
+            Self::WitnessProgramMismatch => "WITNESS_PROGRAM_MISMATCH",
+            Self::WitnessMalleated => "WITNESS_MALLEATED",
+            Self::WitnessMalleatedP2sh => "WITNESS_MALLEATED_P2SH",
+            Self::WitnessUnexpected => "WITNESS_UNEXPECTED",
+            Self::WitnessPubkeyType => "WITNESS_PUBKEYTYPE",
+            Self::OpCodeSeparator => "OP_CODESEPARATOR",
+            Self::SigFindAndDelete => "SIG_FINDANDDELETE",
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::UnknownError => "unknown error",
+            Self::EvalFalse => "script evaluated without error but finished with a false or empty top stack element",
+            Self::OpReturn => "OP_RETURN was encountered",
+            Self::ScriptSize => "script is too big",
+            Self::PushSize => "push value size limit exceeded",
+            Self::OpCount => "operation limit exceeded",
+            Self::StackSize => "stack size limit exceeded",
+            Self::SigCount => "signature count negative or greater than pubkey count",
+            Self::PubkeyCount => "pubkey count negative or limit exceeded",
+            Self::Verify => "script failed an OP_VERIFY operation",
+            Self::EqualVerify => "script failed an OP_EQUALVERIFY operation",
+            Self::CheckMultisigVerify => "script failed an OP_CHECKMULTISIGVERIFY operation",
+            Self::CheckSigVerify => "script failed an OP_CHECKSIGVERIFY operation",
+            Self::NumEqualVerify => "script failed an OP_NUMEQUALVERIFY operation",
+            Self::BadOpcode => "opcode missing or not understood",
+            Self::DisabledOpcode => "attempted to use a disabled opcode",
+            Self::InvalidStackOperation => "operation not valid with the current stack size",
+            Self::InvalidAltstackOperation => "operation not valid with the current altstack size",
+            Self::UnbalancedConditional => "invalid OP_IF construction",
+            Self::NegativeLocktime => "negative locktime",
+            Self::UnsatisfiedLocktime => "locktime requirement not satisfied",
+            Self::SigHashtype => "signature hash type missing or not understood",
+            Self::SigDer => "non-canonical DER signature",
+            Self::MinimalData => "data push larger than necessary",
+            Self::SigPushOnly => "only push operators allowed in signatures",
+            Self::SigHighS => "non-canonical signature: S value is unnecessarily high",
+            Self::SigNullDummy => "dummy CHECKMULTISIG argument must be zero",
+            Self::PubkeyType => "public key is neither compressed or uncompressed",
+            Self::CleanStack => "stack size must be exactly one after execution",
+            Self::MinimalIf => "OP_IF/NOTIF argument must be minimal",
+            Self::SigNullFail => "signature must be zero for failed CHECK(MULTI)SIG operation",
+            Self::DiscourageUpgradableNops => "NOPx reserved for soft-fork upgrades",
+            Self::DiscourageUpgradableWitnessProgram => {
+                "witness version reserved for soft-fork upgrades"
+            }
+            Self::WitnessProgramWrongLength => "witness program has incorrect length",
+            Self::WitnessProgramWitnessEmpty => "witness program was passed an empty witness",
+            Self::WitnessProgramMismatch => "witness program hash mismatch",
+            Self::WitnessMalleated => "witness requires empty scriptSig",
+            Self::WitnessMalleatedP2sh => "witness requires only-redeemscript scriptSig",
+            Self::WitnessUnexpected => "witness provided for non-witness script",
+            Self::WitnessPubkeyType => "using non-compressed keys in segwit",
+            Self::OpCodeSeparator => "using OP_CODESEPARATOR in non-witness script",
+            Self::SigFindAndDelete => "signature is found in scriptCode",
+        };
+
+        write!(f, "{message}")
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A verification flag name `VerifyFlags` does not know
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UnknownVerifyFlag {
+    name: String,
+}
+
+impl UnknownVerifyFlag {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl fmt::Display for UnknownVerifyFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown script verification flag `{}`", self.name)
+    }
+}
+
+impl std::error::Error for UnknownVerifyFlag {}
+
+/// Why a script template could not be built or paid to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TemplateError {
+    /// The threshold is zero or above the number of keys
+    InvalidThreshold { threshold: usize, keys: usize },
+    /// More keys than `OP_CHECKMULTISIG` accepts
+    TooManyKeys { keys: usize },
+    /// A hash of the wrong length for its hash type
+    HashLength { hash_type: HashType, length: usize },
+    /// P2SH redeem scripts are pushed and limited to 520 bytes
+    ScriptTooLarge { size: usize },
+    /// Witness scripts may only check compressed keys under policy
+    UncompressedKey { output_type: OutputType },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidThreshold { threshold, keys } => {
+                write!(f, "threshold {threshold} is not between 1 and {keys} keys")
+            }
+            Self::TooManyKeys { keys } => write!(
+                f,
+                "{keys} keys are more than the {MAX_PUBKEYS_PER_MULTISIG} of OP_CHECKMULTISIG"
+            ),
+            Self::HashLength { hash_type, length } => write!(
+                f,
+                "{hash_type} hashes are {} bytes, not {length}",
+                hash_type.digest_len()
+            ),
+            Self::ScriptTooLarge { size } => write!(
+                f,
+                "redeem script of {size} bytes exceeds the {MAX_SCRIPT_ELEMENT_SIZE} bytes of a P2SH push"
+            ),
+            Self::UncompressedKey { output_type } => write!(
+                f,
+                "{output_type} outputs cannot be spent with uncompressed keys under standard policy"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
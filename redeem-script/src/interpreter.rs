@@ -0,0 +1,1560 @@
+use crate::{
+    ScriptError, SigVersion, SignatureChecker, UnknownVerifyFlag, SEQUENCE_LOCKTIME_DISABLE_FLAG,
+};
+use bitcoin::{
+    hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash},
+    opcodes::{all::*, Opcode},
+    secp256k1::{ecdsa, Message, PublicKey, Secp256k1, VerifyOnly},
+    Script, Witness,
+};
+use core::fmt;
+use std::{ops::BitOr, str::FromStr};
+
+/// Largest element that can be pushed onto the stack
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Most non-push operations a script may run
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Most public keys an `OP_CHECKMULTISIG` may check
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+/// Largest script that can be run
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Most elements the stack and altstack may hold together
+pub const MAX_STACK_SIZE: usize = 1_000;
+
+/// Script verification flags, named as in Bitcoin Core. Only the
+/// consensus rules that are not flags (and never taproot) apply without
+/// any of them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    pub const NONE: Self = Self(0);
+    /// Evaluate P2SH redeem scripts (BIP16)
+    pub const P2SH: Self = Self(1 << 0);
+    /// Signatures and public keys must be strictly encoded
+    pub const STRICTENC: Self = Self(1 << 1);
+    /// Signatures must be strict DER (BIP66)
+    pub const DERSIG: Self = Self(1 << 2);
+    /// Signatures must have an S value in the lower half of the order
+    pub const LOW_S: Self = Self(1 << 3);
+    /// The extra `OP_CHECKMULTISIG` element must be empty (BIP147)
+    pub const NULLDUMMY: Self = Self(1 << 4);
+    /// scriptSig may only push data
+    pub const SIGPUSHONLY: Self = Self(1 << 5);
+    /// Pushes and numbers must be minimally encoded
+    pub const MINIMALDATA: Self = Self(1 << 6);
+    /// Fail on the `OP_NOP`s reserved for soft forks
+    pub const DISCOURAGE_UPGRADABLE_NOPS: Self = Self(1 << 7);
+    /// Exactly one element may be left on the stack
+    pub const CLEANSTACK: Self = Self(1 << 8);
+    /// Enforce `OP_CHECKLOCKTIMEVERIFY` (BIP65)
+    pub const CHECKLOCKTIMEVERIFY: Self = Self(1 << 9);
+    /// Enforce `OP_CHECKSEQUENCEVERIFY` (BIP112)
+    pub const CHECKSEQUENCEVERIFY: Self = Self(1 << 10);
+    /// Verify segwit v0 programs (BIP141)
+    pub const WITNESS: Self = Self(1 << 11);
+    /// Fail on witness versions reserved for soft forks
+    pub const DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM: Self = Self(1 << 12);
+    /// `OP_IF` arguments in witness scripts must be empty or `0x01`
+    pub const MINIMALIF: Self = Self(1 << 13);
+    /// Failed signature checks must have an empty signature
+    pub const NULLFAIL: Self = Self(1 << 14);
+    /// Public keys in witness scripts must be compressed
+    pub const WITNESS_PUBKEYTYPE: Self = Self(1 << 15);
+    /// Reject `OP_CODESEPARATOR` and signatures found in legacy script code
+    pub const CONST_SCRIPTCODE: Self = Self(1 << 16);
+
+    /// Bitcoin Core's `MANDATORY_SCRIPT_VERIFY_FLAGS` without taproot
+    pub const MANDATORY: Self = Self(
+        Self::P2SH.0
+            | Self::DERSIG.0
+            | Self::NULLDUMMY.0
+            | Self::CHECKLOCKTIMEVERIFY.0
+            | Self::CHECKSEQUENCEVERIFY.0
+            | Self::WITNESS.0,
+    );
+    /// Bitcoin Core's `STANDARD_SCRIPT_VERIFY_FLAGS` without taproot
+    pub const STANDARD: Self = Self(
+        Self::MANDATORY.0
+            | Self::STRICTENC.0
+            | Self::MINIMALDATA.0
+            | Self::DISCOURAGE_UPGRADABLE_NOPS.0
+            | Self::CLEANSTACK.0
+            | Self::MINIMALIF.0
+            | Self::NULLFAIL.0
+            | Self::LOW_S.0
+            | Self::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM.0
+            | Self::WITNESS_PUBKEYTYPE.0
+            | Self::CONST_SCRIPTCODE.0,
+    );
+
+    const NAMES: [(&'static str, Self); 18] = [
+        ("NONE", Self::NONE),
+        ("P2SH", Self::P2SH),
+        ("STRICTENC", Self::STRICTENC),
+        ("DERSIG", Self::DERSIG),
+        ("LOW_S", Self::LOW_S),
+        ("NULLDUMMY", Self::NULLDUMMY),
+        ("SIGPUSHONLY", Self::SIGPUSHONLY),
+        ("MINIMALDATA", Self::MINIMALDATA),
+        (
+            "DISCOURAGE_UPGRADABLE_NOPS",
+            Self::DISCOURAGE_UPGRADABLE_NOPS,
+        ),
+        ("CLEANSTACK", Self::CLEANSTACK),
+        ("CHECKLOCKTIMEVERIFY", Self::CHECKLOCKTIMEVERIFY),
+        ("CHECKSEQUENCEVERIFY", Self::CHECKSEQUENCEVERIFY),
+        ("WITNESS", Self::WITNESS),
+        (
+            "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM",
+            Self::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM,
+        ),
+        ("MINIMALIF", Self::MINIMALIF),
+        ("NULLFAIL", Self::NULLFAIL),
+        ("WITNESS_PUBKEYTYPE", Self::WITNESS_PUBKEYTYPE),
+        ("CONST_SCRIPTCODE", Self::CONST_SCRIPTCODE),
+    ];
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for VerifyFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl FromStr for VerifyFlags {
+    type Err = UnknownVerifyFlag;
+
+    /// Comma separated flag names as in Bitcoin Core's test vectors
+    fn from_str(names: &str) -> Result<Self, Self::Err> {
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .try_fold(Self::NONE, |flags, name| {
+                Self::NAMES
+                    .iter()
+                    .find(|(flag_name, _)| *flag_name == name)
+                    .map(|&(_, flag)| flags | flag)
+                    .ok_or_else(|| UnknownVerifyFlag::new(name))
+            })
+    }
+}
+
+/// The script an operation of a trace belongs to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptKind {
+    ScriptSig,
+    ScriptPubKey,
+    /// The P2SH script pushed last by the scriptSig
+    RedeemScript,
+    /// The segwit v0 script, the last witness element of a P2WSH spend
+    /// or the implied `OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY
+    /// OP_CHECKSIG` of a P2WPKH spend
+    WitnessScript,
+}
+
+impl ScriptKind {
+    fn sig_version(&self) -> SigVersion {
+        match self {
+            Self::WitnessScript => SigVersion::WitnessV0,
+            _ => SigVersion::Base,
+        }
+    }
+}
+
+impl fmt::Display for ScriptKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::ScriptSig => "scriptSig",
+            Self::ScriptPubKey => "scriptPubKey",
+            Self::RedeemScript => "redeemScript",
+            Self::WitnessScript => "witnessScript",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Every operation the interpreter ran with the stack it left behind
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Trace {
+    steps: Vec<TraceStep>,
+}
+
+impl Trace {
+    pub fn steps(&self) -> &[TraceStep] {
+        self.steps.as_slice()
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in self.steps.iter() {
+            writeln!(f, "{step}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// One operation of a script
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraceStep {
+    script: ScriptKind,
+    offset: usize,
+    operation: String,
+    executed: bool,
+    stack: Vec<Vec<u8>>,
+    altstack: Vec<Vec<u8>>,
+    error: Option<ScriptError>,
+}
+
+impl TraceStep {
+    pub fn script(&self) -> ScriptKind {
+        self.script
+    }
+
+    /// Byte offset of the operation in its script
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The opcode name, or the pushed data in hex
+    pub fn operation(&self) -> &str {
+        self.operation.as_str()
+    }
+
+    /// False inside a branch that is not taken
+    pub fn executed(&self) -> bool {
+        self.executed
+    }
+
+    /// The stack after the operation, top last
+    pub fn stack(&self) -> &[Vec<u8>] {
+        self.stack.as_slice()
+    }
+
+    pub fn altstack(&self) -> &[Vec<u8>] {
+        self.altstack.as_slice()
+    }
+
+    /// The error the operation failed with, the stack is shown as the
+    /// failed operation left it
+    pub fn error(&self) -> Option<ScriptError> {
+        self.error
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let elements = |stack: &[Vec<u8>]| {
+            stack
+                .iter()
+                .map(|element| match element.is_empty() {
+                    true => "[]".to_owned(),
+                    false => hex::encode(element),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        write!(
+            f,
+            "{:<13} {:>5}  {:<22} {}",
+            self.script.to_string(),
+            self.offset,
+            self.operation,
+            if self.executed { " " } else { "-" }
+        )?;
+        match self.error {
+            Some(error) => write!(f, " {} ({error})", error.code()),
+            None => {
+                write!(f, " [{}]", elements(&self.stack))?;
+                if !self.altstack.is_empty() {
+                    write!(f, " alt [{}]", elements(&self.altstack))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A stack based interpreter for legacy, P2SH and segwit v0 scripts
+/// following Bitcoin Core's `EvalScript` and `VerifyScript`. Signatures
+/// and lock times are checked through a `SignatureChecker`, and every
+/// operation can be recorded in a `Trace`.
+pub struct Interpreter<'a> {
+    flags: VerifyFlags,
+    checker: &'a dyn SignatureChecker,
+    secp: Secp256k1<VerifyOnly>,
+    trace: Option<Trace>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// An interpreter enforcing `VerifyFlags::STANDARD`
+    pub fn new(checker: &'a dyn SignatureChecker) -> Self {
+        Self {
+            flags: VerifyFlags::STANDARD,
+            checker,
+            secp: Secp256k1::verification_only(),
+            trace: None,
+        }
+    }
+
+    pub fn with_flags(mut self, flags: VerifyFlags) -> Self {
+        self.flags = flags;
+
+        self
+    }
+
+    /// Record every operation run from now on
+    pub fn with_trace(mut self) -> Self {
+        self.trace = Some(Trace::default());
+
+        self
+    }
+
+    pub fn flags(&self) -> VerifyFlags {
+        self.flags
+    }
+
+    /// The operations recorded so far, `None` unless tracing
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Check that `script_sig` and `witness` satisfy `script_pubkey`,
+    /// evaluating P2SH redeem scripts and segwit v0 programs nested in it
+    pub fn verify(
+        &mut self,
+        script_sig: &Script,
+        script_pubkey: &Script,
+        witness: &Witness,
+    ) -> Result<(), ScriptError> {
+        let flags = self.flags;
+        if flags.contains(VerifyFlags::SIGPUSHONLY) && !is_push_only(script_sig) {
+            return Err(ScriptError::SigPushOnly);
+        }
+
+        let mut stack = Vec::<Vec<u8>>::new();
+        self.eval(&mut stack, script_sig, ScriptKind::ScriptSig)?;
+        let p2sh_stack = stack.clone();
+        self.eval(&mut stack, script_pubkey, ScriptKind::ScriptPubKey)?;
+        if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return Err(ScriptError::EvalFalse);
+        }
+
+        let mut had_witness = false;
+        if flags.contains(VerifyFlags::WITNESS) {
+            if let Some((version, program)) = witness_program(script_pubkey.as_bytes()) {
+                had_witness = true;
+                if !script_sig.is_empty() {
+                    return Err(ScriptError::WitnessMalleated);
+                }
+                self.verify_witness_program(witness, version, program, false)?;
+                // Witness programs leave no stack to keep clean
+                stack.truncate(1);
+            }
+        }
+
+        if flags.contains(VerifyFlags::P2SH) && script_pubkey.is_p2sh() {
+            if !is_push_only(script_sig) {
+                return Err(ScriptError::SigPushOnly);
+            }
+
+            stack = p2sh_stack;
+            // The scriptPubKey would have failed on an empty stack
+            let redeem_script = stack.pop().ok_or(ScriptError::InvalidStackOperation)?;
+            self.eval(
+                &mut stack,
+                Script::from_bytes(&redeem_script),
+                ScriptKind::RedeemScript,
+            )?;
+            if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+                return Err(ScriptError::EvalFalse);
+            }
+
+            if flags.contains(VerifyFlags::WITNESS) {
+                if let Some((version, program)) = witness_program(&redeem_script) {
+                    had_witness = true;
+                    // The scriptSig must be exactly one push of the redeem script
+                    if script_sig.as_bytes() != push_data(&redeem_script) {
+                        return Err(ScriptError::WitnessMalleatedP2sh);
+                    }
+                    self.verify_witness_program(witness, version, program, true)?;
+                    stack.truncate(1);
+                }
+            }
+        }
+
+        if flags.contains(VerifyFlags::CLEANSTACK) && stack.len() != 1 {
+            return Err(ScriptError::CleanStack);
+        }
+        if flags.contains(VerifyFlags::WITNESS) && !had_witness && !witness.is_empty() {
+            return Err(ScriptError::WitnessUnexpected);
+        }
+
+        Ok(())
+    }
+
+    fn verify_witness_program(
+        &mut self,
+        witness: &Witness,
+        version: u8,
+        program: &[u8],
+        is_p2sh: bool,
+    ) -> Result<(), ScriptError> {
+        let mut stack = witness.iter().map(<[u8]>::to_vec).collect::<Vec<Vec<u8>>>();
+
+        let script = match (version, program.len()) {
+            (0, 32) => {
+                let script = stack.pop().ok_or(ScriptError::WitnessProgramWitnessEmpty)?;
+                if sha256::Hash::hash(&script).as_byte_array() != program {
+                    return Err(ScriptError::WitnessProgramMismatch);
+                }
+
+                script
+            }
+            (0, 20) => {
+                if stack.len() != 2 {
+                    return Err(ScriptError::WitnessProgramMismatch);
+                }
+
+                [&[0x76, 0xa9, 0x14][..], program, &[0x88, 0xac]].concat()
+            }
+            (0, _) => return Err(ScriptError::WitnessProgramWrongLength),
+            // Taproot is not verified, like a node without the taproot flag
+            (1, 32) if !is_p2sh => return Ok(()),
+            _ => {
+                if self
+                    .flags
+                    .contains(VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM)
+                {
+                    return Err(ScriptError::DiscourageUpgradableWitnessProgram);
+                }
+
+                // Unknown versions are anyone can spend until a soft fork
+                return Ok(());
+            }
+        };
+
+        if stack
+            .iter()
+            .any(|element| element.len() > MAX_SCRIPT_ELEMENT_SIZE)
+        {
+            return Err(ScriptError::PushSize);
+        }
+
+        self.eval(
+            &mut stack,
+            Script::from_bytes(&script),
+            ScriptKind::WitnessScript,
+        )?;
+
+        // Witness scripts must leave a clean stack
+        if stack.len() != 1 {
+            return Err(ScriptError::CleanStack);
+        }
+        if !cast_to_bool(&stack[0]) {
+            return Err(ScriptError::EvalFalse);
+        }
+
+        Ok(())
+    }
+
+    /// Run `script` on `stack`. `kind` decides how signatures are hashed
+    /// and is what the trace reports the operations under.
+    pub fn eval(
+        &mut self,
+        stack: &mut Vec<Vec<u8>>,
+        script: &Script,
+        kind: ScriptKind,
+    ) -> Result<(), ScriptError> {
+        if script.len() > MAX_SCRIPT_SIZE {
+            return Err(ScriptError::ScriptSize);
+        }
+
+        let mut state = EvalState {
+            script: script.as_bytes(),
+            sig_version: kind.sig_version(),
+            code_start: 0,
+            op_count: 0,
+            conditions: Vec::new(),
+            altstack: Vec::new(),
+        };
+
+        let mut pc = 0;
+        while pc < state.script.len() {
+            let offset = pc;
+            let executed = state.conditions.iter().all(|&condition| condition);
+
+            let (operation, result) = match read_op(state.script, &mut pc) {
+                Some((opcode, data)) => (
+                    describe_op(opcode, data),
+                    self.step(stack, &mut state, opcode, data, executed, pc),
+                ),
+                None => (
+                    hex::encode(&state.script[offset..]),
+                    Err(ScriptError::BadOpcode),
+                ),
+            };
+
+            if let Some(trace) = self.trace.as_mut() {
+                trace.steps.push(TraceStep {
+                    script: kind,
+                    offset,
+                    operation,
+                    executed,
+                    stack: stack.clone(),
+                    altstack: state.altstack.clone(),
+                    error: result.err(),
+                });
+            }
+            result?;
+        }
+
+        if !state.conditions.is_empty() {
+            return Err(ScriptError::UnbalancedConditional);
+        }
+
+        Ok(())
+    }
+
+    // Run one operation, `pc` is the offset just after it
+    fn step(
+        &self,
+        stack: &mut Vec<Vec<u8>>,
+        state: &mut EvalState,
+        opcode: Opcode,
+        data: &[u8],
+        executed: bool,
+        pc: usize,
+    ) -> Result<(), ScriptError> {
+        let flags = self.flags;
+        let require_minimal = flags.contains(VerifyFlags::MINIMALDATA);
+        let code = opcode.to_u8();
+
+        if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err(ScriptError::PushSize);
+        }
+        // `OP_RESERVED` and the pushes do not count towards the limit
+        if code > OP_PUSHNUM_16.to_u8() {
+            state.op_count += 1;
+            if state.op_count > MAX_OPS_PER_SCRIPT {
+                return Err(ScriptError::OpCount);
+            }
+        }
+
+        // Disabled since 2010 even in branches that are not taken
+        if matches!(
+            opcode,
+            OP_CAT
+                | OP_SUBSTR
+                | OP_LEFT
+                | OP_RIGHT
+                | OP_INVERT
+                | OP_AND
+                | OP_OR
+                | OP_XOR
+                | OP_2MUL
+                | OP_2DIV
+                | OP_MUL
+                | OP_DIV
+                | OP_MOD
+                | OP_LSHIFT
+                | OP_RSHIFT
+        ) {
+            return Err(ScriptError::DisabledOpcode);
+        }
+        if opcode == OP_CODESEPARATOR
+            && state.sig_version == SigVersion::Base
+            && flags.contains(VerifyFlags::CONST_SCRIPTCODE)
+        {
+            return Err(ScriptError::OpCodeSeparator);
+        }
+
+        if code <= OP_PUSHDATA4.to_u8() {
+            if executed {
+                if require_minimal && !is_minimal_push(code, data) {
+                    return Err(ScriptError::MinimalData);
+                }
+                stack.push(data.to_vec());
+            }
+        } else if executed || (OP_IF.to_u8()..=OP_ENDIF.to_u8()).contains(&code) {
+            self.execute(stack, state, opcode, executed, pc)?;
+        }
+
+        if stack.len() + state.altstack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+
+        Ok(())
+    }
+
+    fn execute(
+        &self,
+        stack: &mut Vec<Vec<u8>>,
+        state: &mut EvalState,
+        opcode: Opcode,
+        executed: bool,
+        pc: usize,
+    ) -> Result<(), ScriptError> {
+        let flags = self.flags;
+        let require_minimal = flags.contains(VerifyFlags::MINIMALDATA);
+        let code = opcode.to_u8();
+        let num = |element: &[u8]| decode_num(element, require_minimal, 4);
+
+        match opcode {
+            OP_PUSHNUM_NEG1 => stack.push(encode_num(-1)),
+            _ if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&code) => {
+                stack.push(encode_num((code - OP_PUSHNUM_1.to_u8() + 1) as i64))
+            }
+
+            OP_NOP => {}
+            // `OP_NOP2` and `OP_NOP3` while their soft forks are not enforced
+            OP_CLTV => {
+                if flags.contains(VerifyFlags::CHECKLOCKTIMEVERIFY) {
+                    let lock_time = decode_num(top(stack, 1)?, require_minimal, 5)?;
+                    if lock_time < 0 {
+                        return Err(ScriptError::NegativeLocktime);
+                    }
+                    if !self.checker.check_lock_time(lock_time) {
+                        return Err(ScriptError::UnsatisfiedLocktime);
+                    }
+                }
+            }
+            OP_CSV => {
+                if flags.contains(VerifyFlags::CHECKSEQUENCEVERIFY) {
+                    let sequence = decode_num(top(stack, 1)?, require_minimal, 5)?;
+                    if sequence < 0 {
+                        return Err(ScriptError::NegativeLocktime);
+                    }
+                    // Reserved for soft forks while the disable flag is set
+                    if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0
+                        && !self.checker.check_sequence(sequence)
+                    {
+                        return Err(ScriptError::UnsatisfiedLocktime);
+                    }
+                }
+            }
+            OP_NOP1 | OP_NOP4 | OP_NOP5 | OP_NOP6 | OP_NOP7 | OP_NOP8 | OP_NOP9 | OP_NOP10 => {
+                if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                    return Err(ScriptError::DiscourageUpgradableNops);
+                }
+            }
+
+            OP_IF | OP_NOTIF => {
+                let mut value = false;
+                if executed {
+                    let condition = stack.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                    if state.sig_version == SigVersion::WitnessV0
+                        && flags.contains(VerifyFlags::MINIMALIF)
+                        && (condition.len() > 1 || (condition.len() == 1 && condition[0] != 1))
+                    {
+                        return Err(ScriptError::MinimalIf);
+                    }
+                    value = cast_to_bool(&condition) == (opcode == OP_IF);
+                }
+                state.conditions.push(value);
+            }
+            OP_ELSE => {
+                let condition = state
+                    .conditions
+                    .last_mut()
+                    .ok_or(ScriptError::UnbalancedConditional)?;
+                *condition = !*condition;
+            }
+            OP_ENDIF => {
+                state
+                    .conditions
+                    .pop()
+                    .ok_or(ScriptError::UnbalancedConditional)?;
+            }
+            OP_VERIFY => {
+                if !cast_to_bool(top(stack, 1)?) {
+                    return Err(ScriptError::Verify);
+                }
+                stack.pop();
+            }
+            OP_RETURN => return Err(ScriptError::OpReturn),
+
+            OP_TOALTSTACK => {
+                let element = stack.pop().ok_or(ScriptError::InvalidStackOperation)?;
+                state.altstack.push(element);
+            }
+            OP_FROMALTSTACK => {
+                let element = state
+                    .altstack
+                    .pop()
+                    .ok_or(ScriptError::InvalidAltstackOperation)?;
+                stack.push(element);
+            }
+            OP_2DROP => {
+                require(stack, 2)?;
+                stack.truncate(stack.len() - 2);
+            }
+            OP_2DUP => {
+                require(stack, 2)?;
+                stack.extend_from_within(stack.len() - 2..);
+            }
+            OP_3DUP => {
+                require(stack, 3)?;
+                stack.extend_from_within(stack.len() - 3..);
+            }
+            OP_2OVER => {
+                require(stack, 4)?;
+                stack.extend_from_within(stack.len() - 4..stack.len() - 2);
+            }
+            OP_2ROT => {
+                require(stack, 6)?;
+                let moved = stack
+                    .drain(stack.len() - 6..stack.len() - 4)
+                    .collect::<Vec<_>>();
+                stack.extend(moved);
+            }
+            OP_2SWAP => {
+                require(stack, 4)?;
+                let len = stack.len();
+                stack.swap(len - 4, len - 2);
+                stack.swap(len - 3, len - 1);
+            }
+            OP_IFDUP => {
+                let element = top(stack, 1)?;
+                if cast_to_bool(element) {
+                    stack.push(element.clone());
+                }
+            }
+            OP_DEPTH => stack.push(encode_num(stack.len() as i64)),
+            OP_DROP => {
+                stack.pop().ok_or(ScriptError::InvalidStackOperation)?;
+            }
+            OP_DUP => stack.push(top(stack, 1)?.clone()),
+            OP_NIP => {
+                require(stack, 2)?;
+                stack.remove(stack.len() - 2);
+            }
+            OP_OVER => stack.push(top(stack, 2)?.clone()),
+            OP_PICK | OP_ROLL => {
+                require(stack, 2)?;
+                let depth = num(top(stack, 1)?)?;
+                stack.pop();
+                if depth < 0 || depth >= stack.len() as i64 {
+                    return Err(ScriptError::InvalidStackOperation);
+                }
+
+                let position = stack.len() - 1 - depth as usize;
+                let element = match opcode == OP_ROLL {
+                    true => stack.remove(position),
+                    false => stack[position].clone(),
+                };
+                stack.push(element);
+            }
+            OP_ROT => {
+                require(stack, 3)?;
+                let element = stack.remove(stack.len() - 3);
+                stack.push(element);
+            }
+            OP_SWAP => {
+                require(stack, 2)?;
+                let len = stack.len();
+                stack.swap(len - 2, len - 1);
+            }
+            OP_TUCK => {
+                let element = top(stack, 1)?.clone();
+                require(stack, 2)?;
+                stack.insert(stack.len() - 2, element);
+            }
+            OP_SIZE => stack.push(encode_num(top(stack, 1)?.len() as i64)),
+
+            OP_EQUAL | OP_EQUALVERIFY => {
+                require(stack, 2)?;
+                let equal = stack.pop() == stack.pop();
+                stack.push(encode_bool(equal));
+                if opcode == OP_EQUALVERIFY {
+                    if !equal {
+                        return Err(ScriptError::EqualVerify);
+                    }
+                    stack.pop();
+                }
+            }
+
+            OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                let value = num(top(stack, 1)?)?;
+                let result = match opcode {
+                    OP_1ADD => value + 1,
+                    OP_1SUB => value - 1,
+                    OP_NEGATE => -value,
+                    OP_ABS => value.abs(),
+                    OP_NOT => (value == 0) as i64,
+                    _ => (value != 0) as i64,
+                };
+                stack.pop();
+                stack.push(encode_num(result));
+            }
+            OP_ADD
+            | OP_SUB
+            | OP_BOOLAND
+            | OP_BOOLOR
+            | OP_NUMEQUAL
+            | OP_NUMEQUALVERIFY
+            | OP_NUMNOTEQUAL
+            | OP_LESSTHAN
+            | OP_GREATERTHAN
+            | OP_LESSTHANOREQUAL
+            | OP_GREATERTHANOREQUAL
+            | OP_MIN
+            | OP_MAX => {
+                require(stack, 2)?;
+                let (a, b) = (num(top(stack, 2)?)?, num(top(stack, 1)?)?);
+                let result = match opcode {
+                    OP_ADD => a + b,
+                    OP_SUB => a - b,
+                    OP_BOOLAND => (a != 0 && b != 0) as i64,
+                    OP_BOOLOR => (a != 0 || b != 0) as i64,
+                    OP_NUMEQUAL | OP_NUMEQUALVERIFY => (a == b) as i64,
+                    OP_NUMNOTEQUAL => (a != b) as i64,
+                    OP_LESSTHAN => (a < b) as i64,
+                    OP_GREATERTHAN => (a > b) as i64,
+                    OP_LESSTHANOREQUAL => (a <= b) as i64,
+                    OP_GREATERTHANOREQUAL => (a >= b) as i64,
+                    OP_MIN => a.min(b),
+                    _ => a.max(b),
+                };
+                stack.truncate(stack.len() - 2);
+                stack.push(encode_num(result));
+
+                if opcode == OP_NUMEQUALVERIFY {
+                    if result == 0 {
+                        return Err(ScriptError::NumEqualVerify);
+                    }
+                    stack.pop();
+                }
+            }
+            OP_WITHIN => {
+                require(stack, 3)?;
+                let value = num(top(stack, 3)?)?;
+                let (min, max) = (num(top(stack, 2)?)?, num(top(stack, 1)?)?);
+                stack.truncate(stack.len() - 3);
+                stack.push(encode_bool(min <= value && value < max));
+            }
+
+            OP_RIPEMD160 | OP_SHA1 | OP_SHA256 | OP_HASH160 | OP_HASH256 => {
+                let element = stack.pop().ok_or(ScriptError::InvalidStackOperation)?;
+                stack.push(match opcode {
+                    OP_RIPEMD160 => ripemd160::Hash::hash(&element).to_byte_array().to_vec(),
+                    OP_SHA1 => sha1::Hash::hash(&element).to_byte_array().to_vec(),
+                    OP_SHA256 => sha256::Hash::hash(&element).to_byte_array().to_vec(),
+                    OP_HASH160 => hash160::Hash::hash(&element).to_byte_array().to_vec(),
+                    _ => sha256d::Hash::hash(&element).to_byte_array().to_vec(),
+                });
+            }
+
+            // Signatures commit to the script after the last executed separator
+            OP_CODESEPARATOR => state.code_start = pc,
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                require(stack, 2)?;
+                let (signature, pubkey) = (top(stack, 2)?, top(stack, 1)?);
+
+                let mut script_code = state.script[state.code_start..].to_vec();
+                if state.sig_version == SigVersion::Base {
+                    let found = find_and_delete(&mut script_code, &push_data(signature));
+                    if found && flags.contains(VerifyFlags::CONST_SCRIPTCODE) {
+                        return Err(ScriptError::SigFindAndDelete);
+                    }
+                }
+
+                self.check_signature_encoding(signature)?;
+                self.check_pubkey_encoding(pubkey, state.sig_version)?;
+                let success =
+                    self.check_signature(signature, pubkey, &script_code, state.sig_version);
+                if !success && flags.contains(VerifyFlags::NULLFAIL) && !signature.is_empty() {
+                    return Err(ScriptError::SigNullFail);
+                }
+
+                stack.truncate(stack.len() - 2);
+                stack.push(encode_bool(success));
+                if opcode == OP_CHECKSIGVERIFY {
+                    if !success {
+                        return Err(ScriptError::CheckSigVerify);
+                    }
+                    stack.pop();
+                }
+            }
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                let success = self.check_multisig(stack, state)?;
+                if opcode == OP_CHECKMULTISIGVERIFY {
+                    if !success {
+                        return Err(ScriptError::CheckMultisigVerify);
+                    }
+                    stack.pop();
+                }
+            }
+
+            // `OP_VER`, `OP_RESERVED`s, `OP_VERIF`s, `OP_CHECKSIGADD` outside
+            // tapscript and the unassigned opcodes
+            _ => return Err(ScriptError::BadOpcode),
+        }
+
+        Ok(())
+    }
+
+    // `OP_CHECKMULTISIG` as Bitcoin Core runs it, including the extra
+    // element it pops. Leaves the result on the stack.
+    fn check_multisig(
+        &self,
+        stack: &mut Vec<Vec<u8>>,
+        state: &mut EvalState,
+    ) -> Result<bool, ScriptError> {
+        let flags = self.flags;
+        let require_minimal = flags.contains(VerifyFlags::MINIMALDATA);
+
+        let mut i = 1;
+        let key_count = decode_num(top(stack, i)?, require_minimal, 4)?;
+        if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&key_count) {
+            return Err(ScriptError::PubkeyCount);
+        }
+        state.op_count += key_count as usize;
+        if state.op_count > MAX_OPS_PER_SCRIPT {
+            return Err(ScriptError::OpCount);
+        }
+
+        i += 1;
+        let mut key = i;
+        i += key_count as usize;
+        let sig_count = decode_num(top(stack, i)?, require_minimal, 4)?;
+        if sig_count < 0 || sig_count > key_count {
+            return Err(ScriptError::SigCount);
+        }
+
+        i += 1;
+        let mut sig = i;
+        i += sig_count as usize;
+        require(stack, i)?;
+
+        let mut script_code = state.script[state.code_start..].to_vec();
+        if state.sig_version == SigVersion::Base {
+            for k in 0..sig_count as usize {
+                let found = find_and_delete(&mut script_code, &push_data(top(stack, sig + k)?));
+                if found && flags.contains(VerifyFlags::CONST_SCRIPTCODE) {
+                    return Err(ScriptError::SigFindAndDelete);
+                }
+            }
+        }
+
+        // Signatures have to match the keys in order
+        let (mut keys_left, mut sigs_left) = (key_count, sig_count);
+        let mut success = true;
+        while success && sigs_left > 0 {
+            let (signature, pubkey) = (top(stack, sig)?, top(stack, key)?);
+            self.check_signature_encoding(signature)?;
+            self.check_pubkey_encoding(pubkey, state.sig_version)?;
+
+            if self.check_signature(signature, pubkey, &script_code, state.sig_version) {
+                sig += 1;
+                sigs_left -= 1;
+            }
+            key += 1;
+            keys_left -= 1;
+
+            if sigs_left > keys_left {
+                success = false;
+            }
+        }
+
+        // Every signature must be empty when the check fails under NULLFAIL
+        let signatures = stack.len() + 1 - i..stack.len() - key_count as usize - 2;
+        if !success
+            && flags.contains(VerifyFlags::NULLFAIL)
+            && stack[signatures]
+                .iter()
+                .any(|signature| !signature.is_empty())
+        {
+            return Err(ScriptError::SigNullFail);
+        }
+        stack.truncate(stack.len() - (i - 1));
+
+        // The extra element Satoshi's implementation pops by mistake
+        let dummy = stack.pop().ok_or(ScriptError::InvalidStackOperation)?;
+        if flags.contains(VerifyFlags::NULLDUMMY) && !dummy.is_empty() {
+            return Err(ScriptError::SigNullDummy);
+        }
+        stack.push(encode_bool(success));
+
+        Ok(success)
+    }
+
+    fn check_signature_encoding(&self, signature: &[u8]) -> Result<(), ScriptError> {
+        let flags = self.flags;
+
+        // An empty signature is a compact way to provide an invalid one
+        if signature.is_empty() {
+            return Ok(());
+        }
+        if (flags.contains(VerifyFlags::DERSIG)
+            || flags.contains(VerifyFlags::LOW_S)
+            || flags.contains(VerifyFlags::STRICTENC))
+            && !is_valid_signature_encoding(signature)
+        {
+            return Err(ScriptError::SigDer);
+        }
+        if flags.contains(VerifyFlags::LOW_S) && !is_low_s(signature) {
+            return Err(ScriptError::SigHighS);
+        }
+        if flags.contains(VerifyFlags::STRICTENC) {
+            let base_type = signature[signature.len() - 1] & !0x80;
+            if !(0x01..=0x03).contains(&base_type) {
+                return Err(ScriptError::SigHashtype);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_pubkey_encoding(
+        &self,
+        pubkey: &[u8],
+        sig_version: SigVersion,
+    ) -> Result<(), ScriptError> {
+        let compressed = pubkey.len() == 33 && matches!(pubkey[0], 0x02 | 0x03);
+        let uncompressed = pubkey.len() == 65 && pubkey[0] == 0x04;
+
+        if self.flags.contains(VerifyFlags::STRICTENC) && !compressed && !uncompressed {
+            return Err(ScriptError::PubkeyType);
+        }
+        if self.flags.contains(VerifyFlags::WITNESS_PUBKEYTYPE)
+            && sig_version == SigVersion::WitnessV0
+            && !compressed
+        {
+            return Err(ScriptError::WitnessPubkeyType);
+        }
+
+        Ok(())
+    }
+
+    // Verify a DER signature with its sighash type byte against the hash
+    // the checker gives for the script code
+    fn check_signature(
+        &self,
+        signature: &[u8],
+        pubkey: &[u8],
+        script_code: &[u8],
+        sig_version: SigVersion,
+    ) -> bool {
+        let Some((&sighash_type, der)) = signature.split_last() else {
+            return false;
+        };
+        // Bitcoin Core only takes keys whose length matches their prefix
+        let expected_len = match pubkey.first() {
+            Some(0x02 | 0x03) => 33,
+            Some(0x04 | 0x06 | 0x07) => 65,
+            _ => return false,
+        };
+        if pubkey.len() != expected_len {
+            return false;
+        }
+        let Ok(pubkey) = PublicKey::from_slice(pubkey) else {
+            return false;
+        };
+        let Ok(mut signature) = ecdsa::Signature::from_der_lax(der) else {
+            return false;
+        };
+        signature.normalize_s();
+
+        let script_code = match sig_version {
+            SigVersion::Base => remove_code_separators(script_code),
+            SigVersion::WitnessV0 => script_code.to_vec(),
+        };
+        let Some(sighash) = self.checker.sighash(
+            Script::from_bytes(&script_code),
+            sighash_type as u32,
+            sig_version,
+        ) else {
+            return false;
+        };
+
+        self.secp
+            .verify_ecdsa(&Message::from_digest(sighash), &signature, &pubkey)
+            .is_ok()
+    }
+}
+
+// What a single run of `Interpreter::eval` keeps besides the stack
+struct EvalState<'s> {
+    script: &'s [u8],
+    sig_version: SigVersion,
+    // Offset just after the last executed `OP_CODESEPARATOR`
+    code_start: usize,
+    op_count: usize,
+    // One entry per open `OP_IF`, whether its branch is being executed
+    conditions: Vec<bool>,
+    altstack: Vec<Vec<u8>>,
+}
+
+/// Whether an element counts as true, any non-zero byte except for a
+/// negative zero
+pub fn cast_to_bool(element: &[u8]) -> bool {
+    match element.split_last() {
+        Some((&last, rest)) => rest.iter().any(|&byte| byte != 0) || (last != 0 && last != 0x80),
+        None => false,
+    }
+}
+
+/// Decode a little endian sign and magnitude script number of at most
+/// `max_size` bytes. Bitcoin Core raises these errors as exceptions
+/// reported as `SCRIPT_ERR_UNKNOWN_ERROR`.
+pub fn decode_num(
+    element: &[u8],
+    require_minimal: bool,
+    max_size: usize,
+) -> Result<i64, ScriptError> {
+    if element.len() > max_size {
+        return Err(ScriptError::UnknownError);
+    }
+    let Some((&last, rest)) = element.split_last() else {
+        return Ok(0);
+    };
+    // A zero last byte is only needed to keep the sign bit of the one before
+    if require_minimal && last & 0x7f == 0 && rest.last().is_none_or(|&byte| byte & 0x80 == 0) {
+        return Err(ScriptError::UnknownError);
+    }
+
+    let magnitude = element
+        .iter()
+        .enumerate()
+        .fold(0i64, |value, (index, &byte)| {
+            let byte = if index == element.len() - 1 {
+                byte & 0x7f
+            } else {
+                byte
+            };
+            value | (byte as i64) << (8 * index)
+        });
+
+    Ok(if last & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    })
+}
+
+/// Encode a script number minimally
+pub fn encode_num(value: i64) -> Vec<u8> {
+    let mut magnitude = value.unsigned_abs();
+    let mut element = Vec::<u8>::new();
+    while magnitude > 0 {
+        element.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+
+    match element.last_mut() {
+        Some(last) if *last & 0x80 != 0 => element.push(if value < 0 { 0x80 } else { 0 }),
+        Some(last) if value < 0 => *last |= 0x80,
+        _ => {}
+    }
+
+    element
+}
+
+fn encode_bool(value: bool) -> Vec<u8> {
+    encode_num(value as i64)
+}
+
+// The element `depth` from the top, 1 being the top
+fn top(stack: &[Vec<u8>], depth: usize) -> Result<&Vec<u8>, ScriptError> {
+    require(stack, depth)?;
+
+    Ok(&stack[stack.len() - depth])
+}
+
+fn require(stack: &[Vec<u8>], len: usize) -> Result<(), ScriptError> {
+    match stack.len() < len {
+        true => Err(ScriptError::InvalidStackOperation),
+        false => Ok(()),
+    }
+}
+
+/// Read the operation at `pc` and its pushed data, moving `pc` past it.
+/// `None` if a push runs past the end of the script.
+fn read_op<'s>(script: &'s [u8], pc: &mut usize) -> Option<(Opcode, &'s [u8])> {
+    let code = *script.get(*pc)?;
+    *pc += 1;
+
+    let len_size = match code {
+        0x4c => 1,
+        0x4d => 2,
+        0x4e => 4,
+        _ => 0,
+    };
+    let len = if code < 0x4c {
+        code as usize
+    } else if len_size > 0 {
+        let bytes = script.get(*pc..*pc + len_size)?;
+        *pc += len_size;
+        bytes
+            .iter()
+            .rev()
+            .fold(0usize, |len, &byte| len << 8 | byte as usize)
+    } else {
+        0
+    };
+
+    let data = script.get(*pc..pc.checked_add(len)?)?;
+    *pc += len;
+
+    Some((Opcode::from(code), data))
+}
+
+fn describe_op(opcode: Opcode, data: &[u8]) -> String {
+    let code = opcode.to_u8();
+    if code == 0 {
+        "OP_0".to_owned()
+    } else if code <= OP_PUSHDATA4.to_u8() {
+        hex::encode(data)
+    } else {
+        opcode_name(opcode)
+    }
+}
+
+/// The name Bitcoin Core gives an opcode
+pub fn opcode_name(opcode: Opcode) -> String {
+    let code = opcode.to_u8();
+    match opcode {
+        OP_PUSHBYTES_0 => "OP_0".to_owned(),
+        OP_PUSHNUM_NEG1 => "OP_1NEGATE".to_owned(),
+        OP_CLTV => "OP_CHECKLOCKTIMEVERIFY".to_owned(),
+        OP_CSV => "OP_CHECKSEQUENCEVERIFY".to_owned(),
+        _ if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&code) => {
+            format!("OP_{}", code - OP_PUSHNUM_1.to_u8() + 1)
+        }
+        _ if code > OP_CHECKSIGADD.to_u8() && opcode != OP_INVALIDOPCODE => "OP_UNKNOWN".to_owned(),
+        _ => opcode.to_string(),
+    }
+}
+
+/// Whether every operation of the script is a push, counting
+/// `OP_RESERVED` as Bitcoin Core does
+pub fn is_push_only(script: &Script) -> bool {
+    let script = script.as_bytes();
+    let mut pc = 0;
+    while pc < script.len() {
+        match read_op(script, &mut pc) {
+            Some((opcode, _)) if opcode.to_u8() <= OP_PUSHNUM_16.to_u8() => {}
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+/// The version and program of a segwit scriptPubKey
+pub fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    if !(4..=42).contains(&script.len()) || script[1] as usize + 2 != script.len() {
+        return None;
+    }
+
+    match script[0] {
+        0 => Some((0, &script[2..])),
+        version @ 0x51..=0x60 => Some((version - 0x50, &script[2..])),
+        _ => None,
+    }
+}
+
+/// The script pushing `data` with the smallest push opcode, as Bitcoin
+/// Core's `CScript() << data`
+pub fn push_data(data: &[u8]) -> Vec<u8> {
+    let mut script = match data.len() {
+        0..=0x4b => vec![data.len() as u8],
+        0x4c..=0xff => vec![0x4c, data.len() as u8],
+        0x100..=0xffff => [&[0x4d][..], &(data.len() as u16).to_le_bytes()].concat(),
+        _ => [&[0x4e][..], &(data.len() as u32).to_le_bytes()].concat(),
+    };
+    script.extend_from_slice(data);
+
+    script
+}
+
+fn is_minimal_push(code: u8, data: &[u8]) -> bool {
+    match data {
+        [] => code == 0,
+        [value @ 1..=16] => code == OP_PUSHNUM_1.to_u8() + value - 1,
+        [0x81] => code == OP_PUSHNUM_NEG1.to_u8(),
+        _ if data.len() <= 75 => code as usize == data.len(),
+        _ if data.len() <= 0xff => code == OP_PUSHDATA1.to_u8(),
+        _ if data.len() <= 0xffff => code == OP_PUSHDATA2.to_u8(),
+        _ => true,
+    }
+}
+
+// Remove every occurrence of `pattern` that starts at an operation
+// boundary, as Bitcoin Core's `FindAndDelete`
+fn find_and_delete(script: &mut Vec<u8>, pattern: &[u8]) -> bool {
+    let mut result = Vec::<u8>::with_capacity(script.len());
+    let mut found = false;
+    let (mut pc, mut copied) = (0, 0);
+
+    loop {
+        result.extend_from_slice(&script[copied..pc]);
+        while script[pc..].starts_with(pattern) {
+            pc += pattern.len();
+            found = true;
+        }
+        copied = pc;
+
+        if pc >= script.len() || read_op(script, &mut pc).is_none() {
+            break;
+        }
+    }
+
+    if found {
+        result.extend_from_slice(&script[copied..]);
+        *script = result;
+    }
+
+    found
+}
+
+// Legacy signatures hash the script code without its separators
+fn remove_code_separators(script: &[u8]) -> Vec<u8> {
+    let mut result = Vec::<u8>::with_capacity(script.len());
+    let mut pc = 0;
+    while pc < script.len() {
+        let start = pc;
+        match read_op(script, &mut pc) {
+            Some((OP_CODESEPARATOR, _)) => {}
+            Some(_) => result.extend_from_slice(&script[start..pc]),
+            None => {
+                result.extend_from_slice(&script[start..]);
+                break;
+            }
+        }
+    }
+
+    result
+}
+
+// BIP66 strict DER with the sighash type byte appended
+fn is_valid_signature_encoding(signature: &[u8]) -> bool {
+    let len = signature.len();
+    if !(9..=73).contains(&len) || signature[0] != 0x30 || signature[1] as usize != len - 3 {
+        return false;
+    }
+
+    let r_len = signature[3] as usize;
+    if 5 + r_len >= len {
+        return false;
+    }
+    let s_len = signature[5 + r_len] as usize;
+    if r_len + s_len + 7 != len {
+        return false;
+    }
+
+    // Both integers are positive and without needless leading zeros
+    let r = &signature[4..4 + r_len];
+    let s = &signature[6 + r_len..6 + r_len + s_len];
+    signature[2] == 0x02
+        && signature[4 + r_len] == 0x02
+        && [r, s].iter().all(|integer| {
+            !integer.is_empty()
+                && integer[0] & 0x80 == 0
+                && !(integer.len() > 1 && integer[0] == 0 && integer[1] & 0x80 == 0)
+        })
+}
+
+fn is_low_s(signature: &[u8]) -> bool {
+    let Ok(parsed) = ecdsa::Signature::from_der_lax(&signature[..signature.len() - 1]) else {
+        return false;
+    };
+    let mut normalized = parsed;
+    normalized.normalize_s();
+
+    normalized == parsed
+}
+
+#[cfg(test)]
+mod core_script_tests {
+    use crate::{
+        encode_num, opcode_name, push_data, Interpreter, TransactionSignatureChecker, VerifyFlags,
+    };
+    use bitcoin::{
+        absolute::LockTime, opcodes::Opcode, transaction::Version, Amount, OutPoint, ScriptBuf,
+        Sequence, Transaction, TxIn, TxOut, Witness,
+    };
+    use std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+    };
+
+    // Bitcoin Core's `ParseScript` from its test framework
+    fn parse_script(source: &str) -> ScriptBuf {
+        let mut names = HashMap::<String, u8>::new();
+        for code in (0x61..=0xff).chain([0x50]) {
+            let name = opcode_name(Opcode::from(code));
+            if name != "OP_UNKNOWN" {
+                names.insert(name.trim_start_matches("OP_").to_owned(), code);
+                names.insert(name, code);
+            }
+        }
+        names.insert("NOP2".to_owned(), 0xb1);
+        names.insert("NOP3".to_owned(), 0xb2);
+
+        let mut script = Vec::<u8>::new();
+        for token in source.split_whitespace() {
+            let digits = token.strip_prefix('-').unwrap_or(token);
+            if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+                match token.parse::<i64>().unwrap() {
+                    0 => script.push(0x00),
+                    -1 => script.push(0x4f),
+                    number @ 1..=16 => script.push(0x50 + number as u8),
+                    number => script.extend(push_data(&encode_num(number))),
+                }
+            } else if let Some(bytes) = token.strip_prefix("0x") {
+                script.extend(hex::decode(bytes).unwrap());
+            } else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+                script.extend(push_data(&token.as_bytes()[1..token.len() - 1]));
+            } else {
+                script.push(names[token]);
+            }
+        }
+
+        ScriptBuf::from_bytes(script)
+    }
+
+    // The transaction paying to the tested scriptPubKey
+    fn crediting_transaction(script_pubkey: ScriptBuf, amount: Amount) -> Transaction {
+        Transaction {
+            version: Version::ONE,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::from_bytes(vec![0x00, 0x00]),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: amount,
+                script_pubkey,
+            }],
+        }
+    }
+
+    // The transaction spending it with the tested scriptSig and witness
+    fn spending_transaction(
+        credit: &Transaction,
+        script_sig: ScriptBuf,
+        witness: Witness,
+    ) -> Transaction {
+        Transaction {
+            version: Version::ONE,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(credit.txid(), 0),
+                script_sig,
+                sequence: Sequence::MAX,
+                witness,
+            }],
+            output: vec![TxOut {
+                value: credit.output[0].value,
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn parses_core_script_notation() {
+        assert_eq!(
+            parse_script("0 -1 16 17 -17 1000").as_bytes(),
+            [0x00, 0x4f, 0x60, 0x01, 0x11, 0x01, 0x91, 0x02, 0xe8, 0x03]
+        );
+        assert_eq!(
+            parse_script("'Az' 0x4c 0x01 0x07").as_bytes(),
+            [0x02, b'A', b'z', 0x4c, 0x01, 0x07]
+        );
+        assert_eq!(
+            parse_script("DUP OP_HASH160 NOP2 CHECKSEQUENCEVERIFY").as_bytes(),
+            [0x76, 0xa9, 0xb1, 0xb2]
+        );
+    }
+
+    #[test]
+    fn matches_core_script_test_vectors() {
+        let vectors = serde_json::from_str::<Vec<Vec<serde_json::Value>>>(
+            &fs::read_to_string("script_tests.json").unwrap(),
+        )
+        .unwrap();
+
+        // Flags of taproot and later soft forks this interpreter does not implement
+        const TAPROOT_FLAGS: [&str; 4] = [
+            "TAPROOT",
+            "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION",
+            "DISCOURAGE_OP_SUCCESS",
+            "DISCOURAGE_UPGRADABLE_PUBKEYTYPE",
+        ];
+
+        let mut checked = 0;
+        let mut skipped = BTreeMap::<&str, usize>::new();
+        let mut failures = Vec::<String>::new();
+        for vector in vectors.iter().filter(|vector| vector.len() > 1) {
+            let mut row = vector.as_slice();
+            let mut witness = Witness::new();
+            let mut amount = Amount::ZERO;
+            if let Some(items) = row[0].as_array() {
+                let (value, items) = items.split_last().unwrap();
+                // Taproot vectors fill in their scripts with #SCRIPT# templates
+                if items
+                    .iter()
+                    .any(|item| item.as_str().unwrap().contains('#'))
+                {
+                    *skipped.entry("taproot templates").or_default() += 1;
+                    continue;
+                }
+                for item in items {
+                    witness.push(hex::decode(item.as_str().unwrap()).unwrap());
+                }
+                amount = Amount::from_sat((value.as_f64().unwrap() * 100_000_000.0).round() as u64);
+                row = &row[1..];
+            }
+
+            let script_sig = row[0].as_str().unwrap();
+            let script_pubkey = row[1].as_str().unwrap();
+            let expected = row[3].as_str().unwrap();
+            let flags = match row[2].as_str().unwrap().parse::<VerifyFlags>() {
+                Ok(flags) => flags,
+                Err(error) if TAPROOT_FLAGS.contains(&error.name()) => {
+                    *skipped.entry("taproot flags").or_default() += 1;
+                    continue;
+                }
+                Err(error) => {
+                    failures.push(format!("{vector:?}: {error}"));
+                    continue;
+                }
+            };
+
+            let credit = crediting_transaction(parse_script(script_pubkey), amount);
+            let spend = spending_transaction(&credit, parse_script(script_sig), witness);
+            let checker = TransactionSignatureChecker::new(&spend, 0, amount);
+            let result = Interpreter::new(&checker).with_flags(flags).verify(
+                &spend.input[0].script_sig,
+                &credit.output[0].script_pubkey,
+                &spend.input[0].witness,
+            );
+
+            let actual = result.err().map_or("OK", |error| error.code());
+            if actual != expected {
+                failures.push(format!("{vector:?}: got {actual}"));
+            }
+            checked += 1;
+        }
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+        assert_eq!(checked, 224);
+        assert_eq!(skipped, BTreeMap::new());
+    }
+}
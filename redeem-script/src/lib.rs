@@ -0,0 +1,11 @@
+mod error;
+pub use error::*;
+
+mod checker;
+pub use checker::*;
+
+mod interpreter;
+pub use interpreter::*;
+
+mod template;
+pub use template::*;
@@ -1,21 +1,36 @@
 use bitcoin::{
     ecdsa::Signature,
-    hashes::{sha256, Hash},
+    hashes::Hash,
     locktime::absolute,
-    opcodes,
+    script::{Builder, PushBytes, PushBytesBuf},
     secp256k1::{rand, All, Message, Secp256k1, SecretKey},
     sighash::{EcdsaSighashType, SighashCache},
     Address, Amount, Network, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
     Txid, WPubkeyHash, Witness,
 };
+use redeem_script::{
+    HashLock, HashType, Interpreter, OutputType, ScriptError, ScriptTemplate, Trace,
+    TransactionSignatureChecker,
+};
 
 fn main() {
-    let tx_exec = TxExec::new();
-    let tx_out = tx_exec.build();
-    let tx_spend = tx_exec.spend(&tx_out);
+    for output_type in [OutputType::P2sh, OutputType::P2wsh, OutputType::P2shP2wsh] {
+        let tx_exec = TxExec::new().with_output_type(output_type);
+        let tx_out = tx_exec.build();
+        let tx_spend = tx_exec.spend(&tx_out);
 
-    dbg!(&tx_out);
-    dbg!(&tx_spend);
+        println!(
+            "{output_type} redeem script in hex format: {}",
+            tx_exec.redeem_script().to_hex_string()
+        );
+        match tx_exec.verify_spend(&tx_out, &tx_spend) {
+            Ok(trace) => {
+                print!("{trace}");
+                println!("{output_type} spend verifies");
+            }
+            Err(error) => println!("{output_type} spend fails: {error}"),
+        }
+    }
 }
 
 pub struct TxExec {
@@ -25,8 +40,15 @@ pub struct TxExec {
     secret_key: SecretKey,
     public_key: PublicKey,
     wpkh: WPubkeyHash,
-    receiver_secp: Secp256k1<All>,
-    receiver_secret_key: SecretKey,
+    output_type: OutputType,
+    hash_type: HashType,
+    preimage: Vec<u8>,
+}
+
+impl Default for TxExec {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TxExec {
@@ -36,9 +58,6 @@ impl TxExec {
         let public_key = PublicKey::new(secret_key.public_key(&secp));
         let wpkh = public_key.wpubkey_hash().unwrap();
 
-        let receiver_secp = Secp256k1::new();
-        let receiver_secret_key = SecretKey::new(&mut rand::thread_rng());
-
         Self {
             network: Network::Regtest,
             tx_version: bitcoin::transaction::Version::TWO,
@@ -46,27 +65,43 @@ impl TxExec {
             secret_key,
             public_key,
             wpkh,
-            receiver_secp,
-            receiver_secret_key,
+            output_type: OutputType::default(),
+            hash_type: HashType::default(),
+            preimage: PREIMAGE.to_vec(),
         }
     }
 
-    pub fn receiver_address(&self) -> Address {
-        let preimage_bytes = b"Btrust Builders";
-        let locking_hash = sha256::Hash::hash(preimage_bytes);
+    pub fn with_output_type(mut self, output_type: OutputType) -> Self {
+        self.output_type = output_type;
 
-        let redeem_script = bitcoin::script::Builder::new()
-            .push_opcode(opcodes::all::OP_SHA256)
-            .push_slice(&locking_hash.as_byte_array())
-            .push_opcode(opcodes::all::OP_EQUAL)
-            .into_script();
+        self
+    }
 
-        println!(
-            "Redeem script in hex format: {:?}",
-            redeem_script.to_string()
-        );
+    /// Lock the receiving output to the `hash_type` hash of `preimage`
+    pub fn with_hash_lock(mut self, hash_type: HashType, preimage: &[u8]) -> Self {
+        self.hash_type = hash_type;
+        self.preimage = preimage.to_vec();
+
+        self
+    }
+
+    pub fn output_type(&self) -> OutputType {
+        self.output_type
+    }
 
-        Address::p2sh(&redeem_script, self.network).unwrap()
+    pub fn hash_lock(&self) -> HashLock {
+        HashLock::new(self.hash_type, &self.preimage)
+    }
+
+    /// `OP_<hash> <hash> OP_EQUAL`, unlocked by pushing the preimage
+    pub fn redeem_script(&self) -> ScriptBuf {
+        self.hash_lock().script()
+    }
+
+    pub fn receiver_address(&self) -> Address {
+        self.hash_lock()
+            .address(self.output_type, self.network)
+            .unwrap()
     }
 
     pub fn sender_address(&self) -> Address {
@@ -122,7 +157,14 @@ impl TxExec {
         tx
     }
 
+    /// Spend the hash-lock output of `previous_tx` by revealing the preimage
     pub fn spend(&self, previous_tx: &Transaction) -> Transaction {
+        self.spend_with_preimage(previous_tx, &self.preimage)
+    }
+
+    /// Spend the hash-lock output of `previous_tx` with `preimage`, no
+    /// signature is needed as the redeem script only checks the hash
+    pub fn spend_with_preimage(&self, previous_tx: &Transaction, preimage: &[u8]) -> Transaction {
         let previous_tx_out = &previous_tx.output[0];
         let current_balance = previous_tx_out.value;
         let fee = Amount::from_sat(1000);
@@ -133,47 +175,72 @@ impl TxExec {
             script_pubkey: self.receiver_address().script_pubkey(),
         };
 
+        let redeem_script = self.redeem_script();
+        let preimage = PushBytesBuf::try_from(preimage.to_vec()).unwrap();
+        let (script_sig, witness) = match self.output_type {
+            OutputType::P2sh => {
+                let script_sig = Builder::new()
+                    .push_slice(&preimage)
+                    .push_slice(<&PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+                    .into_script();
+
+                (script_sig, Witness::new())
+            }
+            OutputType::P2wsh => (
+                ScriptBuf::new(),
+                Witness::from_slice(&[preimage.as_bytes(), redeem_script.as_bytes()]),
+            ),
+            OutputType::P2shP2wsh => {
+                // The P2SH redeem script is the P2WSH program
+                let witness_program = redeem_script.to_p2wsh();
+                let script_sig = Builder::new()
+                    .push_slice(<&PushBytes>::try_from(witness_program.as_bytes()).unwrap())
+                    .into_script();
+
+                (
+                    script_sig,
+                    Witness::from_slice(&[preimage.as_bytes(), redeem_script.as_bytes()]),
+                )
+            }
+        };
+
         let input = TxIn {
             previous_output: OutPoint {
                 txid: previous_tx.txid(),
                 vout: 0,
             },
-            script_sig: previous_tx.output[0].script_pubkey.clone(),
+            script_sig,
             sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
-            witness: Witness::default(),
+            witness,
         };
 
-        let unsigned_tx = Transaction {
+        Transaction {
             version: self.tx_version,
             lock_time: absolute::LockTime::ZERO,
-            input: vec![input.clone()],
+            input: vec![input],
             output: vec![spend],
-        };
-
-        let mut sighash_cache = SighashCache::new(unsigned_tx);
-        let sighash = sighash_cache
-            .p2wsh_signature_hash(
-                0,
-                &previous_tx_out.script_pubkey,
-                DUMMY_UTXO_AMOUNT,
-                EcdsaSighashType::All,
-            )
-            .unwrap();
-
-        let msg = Message::from(sighash);
-        let sig = self.secp.sign_ecdsa(&msg, &self.receiver_secret_key);
-
-        let mut tx = sighash_cache.into_transaction();
-
-        let pk = self.secret_key.public_key(&self.receiver_secp);
-        let witness = &mut tx.input[0].witness;
-        witness.push_ecdsa_signature(&Signature {
-            sig,
-            hash_ty: EcdsaSighashType::All,
-        });
-        witness.push(pk.serialize());
+        }
+    }
 
-        tx
+    /// Run the scripts of the first input of `spend` against the output
+    /// of `previous_tx` it spends, under the standard verification flags
+    pub fn verify_spend(
+        &self,
+        previous_tx: &Transaction,
+        spend: &Transaction,
+    ) -> Result<Trace, ScriptError> {
+        let input = &spend.input[0];
+        let previous_tx_out = &previous_tx.output[input.previous_output.vout as usize];
+
+        let checker = TransactionSignatureChecker::new(spend, 0, previous_tx_out.value);
+        let mut interpreter = Interpreter::new(&checker).with_trace();
+        interpreter.verify(
+            &input.script_sig,
+            &previous_tx_out.script_pubkey,
+            &input.witness,
+        )?;
+
+        Ok(interpreter.trace().cloned().unwrap_or_default())
     }
 }
 
@@ -193,14 +260,18 @@ fn dummy_utxo(wpkh: &WPubkeyHash) -> (OutPoint, TxOut) {
     (out_point, utxo)
 }
 
+const PREIMAGE: &[u8] = b"Btrust Builders";
 const DUMMY_UTXO_AMOUNT: Amount = Amount::from_sat(20_000_000);
 const SPEND_AMOUNT: Amount = Amount::from_sat(5_000_000);
 const CHANGE_AMOUNT: Amount = Amount::from_sat(14_999_000); // minus 1000 satoshis fee
 
 #[cfg(test)]
 mod check_consistency {
-    use crate::TxExec;
-    use bitcoin::{transaction::Version, Amount};
+    use crate::{dummy_utxo, TxExec, PREIMAGE};
+    use bitcoin::{opcodes::all::OP_HASH160, transaction::Version, Amount};
+    use redeem_script::{
+        HashType, Interpreter, OutputType, ScriptError, TransactionSignatureChecker,
+    };
 
     #[test]
     fn run_test() {
@@ -226,5 +297,82 @@ mod check_consistency {
 
         assert!(previous_tx_out.script_pubkey.is_p2sh());
         assert!(current_tx_out.script_pubkey.is_p2sh());
+
+        assert!(tx_exec.verify_spend(&tx_out, &tx_spend).is_ok());
+    }
+
+    #[test]
+    fn funding_transaction_verifies() {
+        let tx_exec = TxExec::new();
+        let tx_out = tx_exec.build();
+        let (_, utxo) = dummy_utxo(&tx_exec.wpkh);
+
+        let checker = TransactionSignatureChecker::new(&tx_out, 0, utxo.value);
+        let result = Interpreter::new(&checker).verify(
+            &tx_out.input[0].script_sig,
+            &utxo.script_pubkey,
+            &tx_out.input[0].witness,
+        );
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn preimage_unlocks_every_output_type() {
+        for output_type in [OutputType::P2sh, OutputType::P2wsh, OutputType::P2shP2wsh] {
+            let tx_exec = TxExec::new().with_output_type(output_type);
+            let tx_out = tx_exec.build();
+            let tx_spend = tx_exec.spend(&tx_out);
+
+            let trace = tx_exec.verify_spend(&tx_out, &tx_spend).unwrap();
+            // The redeem script ran and its OP_EQUAL matched the hash
+            let last = trace.steps().last().unwrap();
+            assert_eq!(last.operation(), "OP_EQUAL");
+            assert_eq!(last.stack(), [vec![0x01]]);
+
+            let wrong_spend = tx_exec.spend_with_preimage(&tx_out, b"Btrust Builder");
+            assert_eq!(
+                tx_exec.verify_spend(&tx_out, &wrong_spend).unwrap_err(),
+                ScriptError::EvalFalse
+            );
+        }
+    }
+
+    #[test]
+    fn hash_lock_is_configurable() {
+        let tx_exec = TxExec::new()
+            .with_output_type(OutputType::P2shP2wsh)
+            .with_hash_lock(HashType::Hash160, b"another preimage");
+        let tx_out = tx_exec.build();
+        let tx_spend = tx_exec.spend(&tx_out);
+
+        assert_eq!(tx_exec.redeem_script().as_bytes()[0], OP_HASH160.to_u8());
+        assert!(tx_exec.verify_spend(&tx_out, &tx_spend).is_ok());
+    }
+
+    #[test]
+    fn spends_put_the_preimage_where_the_output_type_expects() {
+        let p2sh = TxExec::new();
+        let tx_spend = p2sh.spend(&p2sh.build());
+        assert!(tx_spend.input[0].witness.is_empty());
+        assert!(tx_spend.input[0]
+            .script_sig
+            .as_bytes()
+            .ends_with(p2sh.redeem_script().as_bytes()));
+
+        let p2wsh = TxExec::new().with_output_type(OutputType::P2wsh);
+        let tx_spend = p2wsh.spend(&p2wsh.build());
+        assert!(tx_spend.input[0].script_sig.is_empty());
+        assert_eq!(tx_spend.input[0].witness.len(), 2);
+        assert_eq!(tx_spend.input[0].witness.nth(0), Some(PREIMAGE));
+
+        let p2sh_p2wsh = TxExec::new().with_output_type(OutputType::P2shP2wsh);
+        let tx_out = p2sh_p2wsh.build();
+        let tx_spend = p2sh_p2wsh.spend(&tx_out);
+        assert!(tx_out.output[0].script_pubkey.is_p2sh());
+        assert!(tx_spend.input[0].script_sig.is_push_only());
+        assert_eq!(
+            tx_spend.input[0].witness.last(),
+            Some(p2sh_p2wsh.redeem_script().as_bytes())
+        );
     }
 }
@@ -0,0 +1,672 @@
+use crate::{TemplateError, MAX_PUBKEYS_PER_MULTISIG};
+use bitcoin::{
+    absolute,
+    hashes::{hash160, ripemd160, sha256, sha256d, Hash},
+    opcodes::{all::*, Opcode},
+    relative,
+    script::{Builder, PushBytesBuf},
+    Address, Network, PublicKey, ScriptBuf, Sequence,
+};
+use core::fmt;
+
+/// How a redeem script is wrapped in the output paying to it
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OutputType {
+    /// The redeem script and its inputs are pushed in the scriptSig
+    #[default]
+    P2sh,
+    /// The witness script and its inputs are in the witness
+    P2wsh,
+    /// A P2WSH program pushed in the scriptSig of a P2SH output
+    P2shP2wsh,
+}
+
+impl fmt::Display for OutputType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::P2sh => write!(f, "P2SH"),
+            Self::P2wsh => write!(f, "P2WSH"),
+            Self::P2shP2wsh => write!(f, "P2SH-P2WSH"),
+        }
+    }
+}
+
+/// A redeem or witness script built from a common template. The same
+/// script can be paid to as P2SH, P2WSH or P2SH-P2WSH.
+pub trait ScriptTemplate {
+    fn script(&self) -> ScriptBuf;
+
+    /// Public keys the script checks signatures against
+    fn public_keys(&self) -> Vec<PublicKey> {
+        Vec::new()
+    }
+
+    /// Witness outputs are refused when a key is uncompressed, since
+    /// policy (`WITNESS_PUBKEYTYPE`) would never relay their spends
+    fn address(&self, output_type: OutputType, network: Network) -> Result<Address, TemplateError> {
+        let script = self.script();
+
+        if output_type != OutputType::P2sh && self.public_keys().iter().any(|key| !key.compressed) {
+            return Err(TemplateError::UncompressedKey { output_type });
+        }
+
+        match output_type {
+            OutputType::P2sh => Address::p2sh(&script, network)
+                .map_err(|_| TemplateError::ScriptTooLarge { size: script.len() }),
+            OutputType::P2wsh => Ok(Address::p2wsh(&script, network)),
+            OutputType::P2shP2wsh => Ok(Address::p2shwsh(&script, network)),
+        }
+    }
+}
+
+/// The hash opcode a hash-lock checks its preimage with
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum HashType {
+    #[default]
+    Sha256,
+    /// RIPEMD160 of SHA256
+    Hash160,
+    /// Double SHA256
+    Hash256,
+    Ripemd160,
+}
+
+impl HashType {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Self::Sha256 => OP_SHA256,
+            Self::Hash160 => OP_HASH160,
+            Self::Hash256 => OP_HASH256,
+            Self::Ripemd160 => OP_RIPEMD160,
+        }
+    }
+
+    /// Length in bytes of the hashes
+    pub fn digest_len(&self) -> usize {
+        match self {
+            Self::Sha256 | Self::Hash256 => 32,
+            Self::Hash160 | Self::Ripemd160 => 20,
+        }
+    }
+
+    pub fn hash(&self, preimage: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha256 => sha256::Hash::hash(preimage).to_byte_array().to_vec(),
+            Self::Hash160 => hash160::Hash::hash(preimage).to_byte_array().to_vec(),
+            Self::Hash256 => sha256d::Hash::hash(preimage).to_byte_array().to_vec(),
+            Self::Ripemd160 => ripemd160::Hash::hash(preimage).to_byte_array().to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for HashType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sha256 => write!(f, "SHA256"),
+            Self::Hash160 => write!(f, "HASH160"),
+            Self::Hash256 => write!(f, "HASH256"),
+            Self::Ripemd160 => write!(f, "RIPEMD160"),
+        }
+    }
+}
+
+/// `OP_<hash> <hash> OP_EQUAL`, unlocked by pushing the preimage
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HashLock {
+    hash_type: HashType,
+    hash: Vec<u8>,
+}
+
+impl HashLock {
+    /// Lock to the hash of `preimage`
+    pub fn new(hash_type: HashType, preimage: &[u8]) -> Self {
+        Self {
+            hash_type,
+            hash: hash_type.hash(preimage),
+        }
+    }
+
+    /// Lock to a hash whose preimage is not known yet
+    pub fn from_hash(hash_type: HashType, hash: &[u8]) -> Result<Self, TemplateError> {
+        if hash.len() != hash_type.digest_len() {
+            return Err(TemplateError::HashLength {
+                hash_type,
+                length: hash.len(),
+            });
+        }
+
+        Ok(Self {
+            hash_type,
+            hash: hash.to_vec(),
+        })
+    }
+
+    pub fn hash_type(&self) -> HashType {
+        self.hash_type
+    }
+
+    pub fn hash(&self) -> &[u8] {
+        self.hash.as_slice()
+    }
+
+    // The hash check ending in `opcode`, `OP_EQUAL` or `OP_EQUALVERIFY`
+    fn push_check(&self, builder: Builder, opcode: Opcode) -> Builder {
+        builder
+            .push_opcode(self.hash_type.opcode())
+            .push_slice(PushBytesBuf::try_from(self.hash.clone()).unwrap())
+            .push_opcode(opcode)
+    }
+}
+
+impl ScriptTemplate for HashLock {
+    fn script(&self) -> ScriptBuf {
+        self.push_check(Builder::new(), OP_EQUAL).into_script()
+    }
+}
+
+/// `<m> <key>... <n> OP_CHECKMULTISIG`, unlocked by `OP_0` and `m`
+/// signatures in the order of their keys
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Multisig {
+    threshold: usize,
+    keys: Vec<PublicKey>,
+}
+
+impl Multisig {
+    /// `threshold` of `keys`, kept in the order given
+    pub fn new(threshold: usize, keys: &[PublicKey]) -> Result<Self, TemplateError> {
+        if keys.len() > MAX_PUBKEYS_PER_MULTISIG as usize {
+            return Err(TemplateError::TooManyKeys { keys: keys.len() });
+        }
+        if threshold == 0 || threshold > keys.len() {
+            return Err(TemplateError::InvalidThreshold {
+                threshold,
+                keys: keys.len(),
+            });
+        }
+
+        Ok(Self {
+            threshold,
+            keys: keys.to_vec(),
+        })
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        self.keys.as_slice()
+    }
+}
+
+impl ScriptTemplate for Multisig {
+    fn script(&self) -> ScriptBuf {
+        self.keys
+            .iter()
+            .fold(
+                Builder::new().push_int(self.threshold as i64),
+                |builder, key| builder.push_key(key),
+            )
+            .push_int(self.keys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    fn public_keys(&self) -> Vec<PublicKey> {
+        self.keys.clone()
+    }
+}
+
+/// When a timelocked branch can be spent
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Timelock {
+    /// From a block height or time on, checked by `OP_CHECKLOCKTIMEVERIFY`
+    Absolute(absolute::LockTime),
+    /// Blocks or time after the output confirmed, checked by
+    /// `OP_CHECKSEQUENCEVERIFY`
+    Relative(relative::LockTime),
+}
+
+impl Timelock {
+    /// `<lock time> OP_CHECKLOCKTIMEVERIFY OP_DROP` or its relative
+    /// counterpart
+    fn push_check(&self, builder: Builder) -> Builder {
+        let (value, opcode) = match self {
+            Self::Absolute(lock_time) => (lock_time.to_consensus_u32(), OP_CLTV),
+            Self::Relative(relative::LockTime::Blocks(height)) => (
+                Sequence::from_height(height.value()).to_consensus_u32(),
+                OP_CSV,
+            ),
+            Self::Relative(relative::LockTime::Time(time)) => (
+                Sequence::from_512_second_intervals(time.value()).to_consensus_u32(),
+                OP_CSV,
+            ),
+        };
+
+        builder
+            .push_int(value as i64)
+            .push_opcode(opcode)
+            .push_opcode(OP_DROP)
+    }
+}
+
+/// `<timelock> OP_DROP <key> OP_CHECKSIG`, unlocked by a signature
+/// once the timelock has passed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TimelockedKey {
+    timelock: Timelock,
+    key: PublicKey,
+}
+
+impl TimelockedKey {
+    pub fn new(timelock: Timelock, key: PublicKey) -> Self {
+        Self { timelock, key }
+    }
+
+    pub fn timelock(&self) -> Timelock {
+        self.timelock
+    }
+
+    pub fn key(&self) -> PublicKey {
+        self.key
+    }
+}
+
+impl ScriptTemplate for TimelockedKey {
+    fn script(&self) -> ScriptBuf {
+        self.timelock
+            .push_check(Builder::new())
+            .push_key(&self.key)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    fn public_keys(&self) -> Vec<PublicKey> {
+        vec![self.key]
+    }
+}
+
+/// A hash-time-locked contract, paying the receiver against the
+/// preimage or refunding the sender once the timeout has passed:
+///
+/// ```text
+/// OP_IF
+///     OP_<hash> <hash> OP_EQUALVERIFY <receiver>
+/// OP_ELSE
+///     <timeout> OP_CHECKLOCKTIMEVERIFY OP_DROP <sender>
+/// OP_ENDIF
+/// OP_CHECKSIG
+/// ```
+///
+/// Relative timeouts use `OP_CHECKSEQUENCEVERIFY` instead. The receiver
+/// spends with `<signature> <preimage> 1`, the sender with `<signature> 0`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Htlc {
+    hash_lock: HashLock,
+    receiver: PublicKey,
+    sender: PublicKey,
+    timeout: Timelock,
+}
+
+impl Htlc {
+    pub fn new(
+        hash_lock: HashLock,
+        receiver: PublicKey,
+        sender: PublicKey,
+        timeout: Timelock,
+    ) -> Self {
+        Self {
+            hash_lock,
+            receiver,
+            sender,
+            timeout,
+        }
+    }
+
+    pub fn hash_lock(&self) -> &HashLock {
+        &self.hash_lock
+    }
+
+    pub fn receiver(&self) -> PublicKey {
+        self.receiver
+    }
+
+    pub fn sender(&self) -> PublicKey {
+        self.sender
+    }
+
+    pub fn timeout(&self) -> Timelock {
+        self.timeout
+    }
+}
+
+impl ScriptTemplate for Htlc {
+    fn script(&self) -> ScriptBuf {
+        let builder = Builder::new().push_opcode(OP_IF);
+        let builder = self
+            .hash_lock
+            .push_check(builder, OP_EQUALVERIFY)
+            .push_key(&self.receiver)
+            .push_opcode(OP_ELSE);
+
+        self.timeout
+            .push_check(builder)
+            .push_key(&self.sender)
+            .push_opcode(OP_ENDIF)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    fn public_keys(&self) -> Vec<PublicKey> {
+        vec![self.receiver, self.sender]
+    }
+}
+
+#[cfg(test)]
+mod script_templates {
+    use crate::{
+        cast_to_bool, BaseSignatureChecker, HashLock, HashType, Htlc, Interpreter, Multisig,
+        OutputType, ScriptError, ScriptKind, ScriptTemplate, TemplateError, Timelock,
+        TimelockedKey, TransactionSignatureChecker,
+    };
+    use bitcoin::{
+        absolute,
+        hashes::Hash,
+        relative,
+        secp256k1::{All, Message, Secp256k1, SecretKey},
+        sighash::{EcdsaSighashType, SighashCache},
+        transaction::Version,
+        Amount, Network, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+        Witness,
+    };
+
+    const AMOUNT: Amount = Amount::from_sat(100_000);
+
+    fn keys(secp: &Secp256k1<All>, count: u8) -> Vec<(SecretKey, PublicKey)> {
+        (1..=count)
+            .map(|byte| {
+                let secret_key = SecretKey::from_slice(&[byte; 32]).unwrap();
+                (secret_key, PublicKey::new(secret_key.public_key(secp)))
+            })
+            .collect()
+    }
+
+    fn spending_transaction(lock_time: absolute::LockTime, sequence: Sequence) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: AMOUNT - Amount::from_sat(1_000),
+                script_pubkey: ScriptBuf::new_op_return([]),
+            }],
+        }
+    }
+
+    // Sign the P2WSH input of `tx` and verify it with `witness` followed
+    // by the witness script
+    fn verify_p2wsh(
+        tx: &mut Transaction,
+        template: &dyn ScriptTemplate,
+        witness: impl FnOnce(&dyn Fn(&SecretKey) -> Vec<u8>) -> Vec<Vec<u8>>,
+    ) -> Result<(), ScriptError> {
+        let secp = Secp256k1::new();
+        let witness_script = template.script();
+        let sighash = SighashCache::new(&*tx)
+            .p2wsh_signature_hash(0, &witness_script, AMOUNT, EcdsaSighashType::All)
+            .unwrap();
+        let sign = |secret_key: &SecretKey| {
+            let message = Message::from_digest(sighash.to_byte_array());
+            let mut signature = secp
+                .sign_ecdsa(&message, secret_key)
+                .serialize_der()
+                .to_vec();
+            signature.push(EcdsaSighashType::All as u8);
+
+            signature
+        };
+
+        let mut items = witness(&sign);
+        items.push(witness_script.to_bytes());
+        tx.input[0].witness = Witness::from_slice(&items);
+
+        let script_pubkey = witness_script.to_p2wsh();
+        let checker = TransactionSignatureChecker::new(tx, 0, AMOUNT);
+        Interpreter::new(&checker).verify(
+            &tx.input[0].script_sig,
+            &script_pubkey,
+            &tx.input[0].witness,
+        )
+    }
+
+    #[test]
+    fn hash_locks_check_their_preimage() {
+        for hash_type in [
+            HashType::Sha256,
+            HashType::Hash160,
+            HashType::Hash256,
+            HashType::Ripemd160,
+        ] {
+            let hash_lock = HashLock::new(hash_type, b"preimage");
+            assert_eq!(hash_lock.hash().len(), hash_type.digest_len());
+
+            for (preimage, unlocks) in [(&b"preimage"[..], true), (&b"guess"[..], false)] {
+                let mut stack = vec![preimage.to_vec()];
+                Interpreter::new(&BaseSignatureChecker)
+                    .eval(&mut stack, &hash_lock.script(), ScriptKind::RedeemScript)
+                    .unwrap();
+                assert_eq!(cast_to_bool(&stack[0]), unlocks, "{hash_type}");
+            }
+        }
+
+        let hash = HashType::Hash160.hash(b"preimage");
+        assert_eq!(
+            HashLock::from_hash(HashType::Hash160, &hash).unwrap(),
+            HashLock::new(HashType::Hash160, b"preimage")
+        );
+        assert_eq!(
+            HashLock::from_hash(HashType::Sha256, &hash),
+            Err(TemplateError::HashLength {
+                hash_type: HashType::Sha256,
+                length: 20
+            })
+        );
+    }
+
+    #[test]
+    fn multisig_needs_threshold_signatures_in_key_order() {
+        let secp = Secp256k1::new();
+        let keys = keys(&secp, 3);
+        let public_keys = keys.iter().map(|&(_, key)| key).collect::<Vec<_>>();
+        let multisig = Multisig::new(2, &public_keys).unwrap();
+        let mut tx = spending_transaction(absolute::LockTime::ZERO, Sequence::MAX);
+
+        let result = verify_p2wsh(&mut tx, &multisig, |sign| {
+            vec![vec![], sign(&keys[0].0), sign(&keys[2].0)]
+        });
+        assert_eq!(result, Ok(()));
+
+        let result = verify_p2wsh(&mut tx, &multisig, |sign| {
+            vec![vec![], sign(&keys[2].0), sign(&keys[0].0)]
+        });
+        assert_eq!(result, Err(ScriptError::SigNullFail));
+
+        assert_eq!(
+            Multisig::new(4, &public_keys),
+            Err(TemplateError::InvalidThreshold {
+                threshold: 4,
+                keys: 3
+            })
+        );
+        assert_eq!(
+            Multisig::new(0, &public_keys),
+            Err(TemplateError::InvalidThreshold {
+                threshold: 0,
+                keys: 3
+            })
+        );
+        assert_eq!(
+            Multisig::new(1, &vec![public_keys[0]; 21]),
+            Err(TemplateError::TooManyKeys { keys: 21 })
+        );
+    }
+
+    #[test]
+    fn htlc_pays_the_preimage_or_refunds_after_the_timeout() {
+        let secp = Secp256k1::new();
+        let keys = keys(&secp, 2);
+        let (receiver, sender) = (keys[0], keys[1]);
+        let timeout = absolute::LockTime::from_height(800_000).unwrap();
+        let htlc = Htlc::new(
+            HashLock::new(HashType::Sha256, b"secret"),
+            receiver.1,
+            sender.1,
+            Timelock::Absolute(timeout),
+        );
+
+        let mut tx =
+            spending_transaction(absolute::LockTime::ZERO, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let result = verify_p2wsh(&mut tx, &htlc, |sign| {
+            vec![sign(&receiver.0), b"secret".to_vec(), vec![0x01]]
+        });
+        assert_eq!(result, Ok(()));
+
+        let result = verify_p2wsh(&mut tx, &htlc, |sign| {
+            vec![sign(&receiver.0), b"guess".to_vec(), vec![0x01]]
+        });
+        assert_eq!(result, Err(ScriptError::EqualVerify));
+
+        // The sender has to wait for the timeout
+        let result = verify_p2wsh(&mut tx, &htlc, |sign| vec![sign(&sender.0), vec![]]);
+        assert_eq!(result, Err(ScriptError::UnsatisfiedLocktime));
+
+        let mut tx = spending_transaction(timeout, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let result = verify_p2wsh(&mut tx, &htlc, |sign| vec![sign(&sender.0), vec![]]);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn timelocked_keys_wait_for_their_lock_time() {
+        let secp = Secp256k1::new();
+        let (secret_key, public_key) = keys(&secp, 1)[0];
+        let relative = TimelockedKey::new(
+            Timelock::Relative(relative::LockTime::Blocks(relative::Height::from(144))),
+            public_key,
+        );
+        let absolute = TimelockedKey::new(
+            Timelock::Absolute(absolute::LockTime::from_height(1_000).unwrap()),
+            public_key,
+        );
+
+        let cases = [
+            (
+                &relative,
+                absolute::LockTime::ZERO,
+                Sequence::from_height(143),
+                false,
+            ),
+            (
+                &relative,
+                absolute::LockTime::ZERO,
+                Sequence::from_height(144),
+                true,
+            ),
+            (
+                &absolute,
+                absolute::LockTime::from_height(999).unwrap(),
+                Sequence::ZERO,
+                false,
+            ),
+            (
+                &absolute,
+                absolute::LockTime::from_height(1_000).unwrap(),
+                Sequence::ZERO,
+                true,
+            ),
+        ];
+        for (template, lock_time, sequence, unlocked) in cases {
+            let mut tx = spending_transaction(lock_time, sequence);
+            let result = verify_p2wsh(&mut tx, template, |sign| vec![sign(&secret_key)]);
+            let expected = if unlocked {
+                Ok(())
+            } else {
+                Err(ScriptError::UnsatisfiedLocktime)
+            };
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn addresses_wrap_the_script() {
+        let secp = Secp256k1::new();
+        let public_keys = keys(&secp, 20)
+            .into_iter()
+            .map(|(_, key)| key)
+            .collect::<Vec<_>>();
+        let hash_lock = HashLock::new(HashType::Hash256, b"preimage");
+        let script = hash_lock.script();
+
+        let p2sh = hash_lock
+            .address(OutputType::P2sh, Network::Bitcoin)
+            .unwrap();
+        assert_eq!(p2sh.script_pubkey(), script.to_p2sh());
+        let p2wsh = hash_lock
+            .address(OutputType::P2wsh, Network::Bitcoin)
+            .unwrap();
+        assert_eq!(p2wsh.script_pubkey(), script.to_p2wsh());
+        let p2sh_p2wsh = hash_lock
+            .address(OutputType::P2shP2wsh, Network::Bitcoin)
+            .unwrap();
+        assert_eq!(p2sh_p2wsh.script_pubkey(), script.to_p2wsh().to_p2sh());
+
+        // 20 keys no longer fit a P2SH push but are fine in a witness
+        let multisig = Multisig::new(11, &public_keys).unwrap();
+        assert_eq!(
+            multisig.address(OutputType::P2sh, Network::Bitcoin),
+            Err(TemplateError::ScriptTooLarge { size: 684 })
+        );
+        assert!(multisig
+            .address(OutputType::P2wsh, Network::Bitcoin)
+            .is_ok());
+    }
+
+    #[test]
+    fn witness_outputs_need_compressed_keys() {
+        let secp = Secp256k1::new();
+        let (secret_key, compressed) = keys(&secp, 1)[0];
+        let uncompressed = PublicKey::new_uncompressed(compressed.inner);
+        let timelocked =
+            TimelockedKey::new(Timelock::Absolute(absolute::LockTime::ZERO), uncompressed);
+        let templates: [&dyn ScriptTemplate; 3] = [
+            &Multisig::new(1, &[compressed, uncompressed]).unwrap(),
+            &timelocked,
+            &Htlc::new(
+                HashLock::new(HashType::Sha256, b"preimage"),
+                compressed,
+                uncompressed,
+                Timelock::Absolute(absolute::LockTime::ZERO),
+            ),
+        ];
+
+        for template in templates {
+            for output_type in [OutputType::P2wsh, OutputType::P2shP2wsh] {
+                assert_eq!(
+                    template.address(output_type, Network::Bitcoin),
+                    Err(TemplateError::UncompressedKey { output_type })
+                );
+            }
+            assert!(template.address(OutputType::P2sh, Network::Bitcoin).is_ok());
+        }
+
+        // The witness output could never be spent under standard flags
+        let mut tx = spending_transaction(absolute::LockTime::ZERO, Sequence::ZERO);
+        let result = verify_p2wsh(&mut tx, &timelocked, |sign| vec![sign(&secret_key)]);
+        assert_eq!(result, Err(ScriptError::WitnessPubkeyType));
+    }
+}